@@ -0,0 +1,16 @@
+#![no_main]
+
+//! Feeds arbitrary bytes to the manifest parser, both in one piece and through a tiny read buffer,
+//! checking that it never panics and that both ways of reading agree.
+
+libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+  let limits = repors::ManifestLimits::default().with_max_bytes(1024 * 1024);
+  let whole = repors::Manifest::from_reader_with_limits(std::io::Cursor::new(data), limits);
+  let streamed = repors::Manifest::from_reader_with_limits(std::io::BufReader::with_capacity(7, data), limits);
+
+  match (whole, streamed) {
+    (Ok(whole), Ok(streamed)) => assert_eq!(whole, streamed),
+    (Err(_), Err(_)) => (),
+    (whole, streamed) => panic!("parsers disagree - {whole:?} vs {streamed:?}"),
+  }
+});
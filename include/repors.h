@@ -0,0 +1,58 @@
+/*
+ * The C ABI of the repors library, available when it is built with the `ffi` feature:
+ *
+ *   cargo rustc --lib --release --no-default-features --features ffi,vendored-openssl --crate-type cdylib
+ *
+ * Functions that fail return NULL (or -1) and leave a description of the failure, retrievable with
+ * repors_last_error(), on the calling thread.
+ */
+#ifndef REPORS_H
+#define REPORS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define REPORS_JOB_STARTED 0
+#define REPORS_JOB_PROGRESS 1
+#define REPORS_JOB_FINISHED 2
+#define REPORS_JOB_FAILED 3
+#define REPORS_PLACED 4
+
+typedef struct repors_manifest repors_manifest;
+
+/*
+ * Receives the user_data it was registered with, one of the REPORS_* events, the subject of the
+ * event (the manifest path of a source, or the destination of a placement; only valid during the
+ * call) and, for REPORS_JOB_PROGRESS, the number of bytes received so far. Called from worker
+ * threads.
+ */
+typedef void (*repors_progress_cb)(void *user_data, int event, const char *subject, uint64_t received_bytes);
+
+/* Parses the manifest at path. Release with repors_manifest_free unless passed to repors_execute. */
+repors_manifest *repors_manifest_open(const char *path);
+
+/* The number of sources listed by the manifest. */
+size_t repors_manifest_len(const repors_manifest *manifest);
+
+/* Releases a manifest; NULL is ignored. */
+void repors_manifest_free(repors_manifest *manifest);
+
+/*
+ * Clones every source of the manifest into destination using threads workers, reporting progress
+ * to callback (which may be NULL). Consumes the manifest. Returns 0 on success, -1 on failure.
+ */
+int repors_execute(repors_manifest *manifest, const char *destination, size_t threads,
+                   repors_progress_cb callback, void *user_data);
+
+/* The last failure on the calling thread, or NULL. Valid until the next failing call. */
+const char *repors_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,125 @@
+use std::io;
+use std::io::Write;
+
+/// Records destructive filesystem operations (and their outcome) as newline-delimited json. The
+/// default value records nothing, and simply performs the operations.
+#[derive(Clone, Default)]
+pub struct AuditLog {
+  /// Where records are appended, if anywhere.
+  sink: Option<std::sync::Arc<std::sync::Mutex<std::fs::File>>>,
+}
+
+impl std::fmt::Debug for AuditLog {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    formatter
+      .debug_struct("AuditLog")
+      .field("enabled", &self.sink.is_some())
+      .finish()
+  }
+}
+
+impl AuditLog {
+  /// Opens (creating if necessary) the audit file at `path`; records are appended to it.
+  pub fn open<P>(path: P) -> io::Result<Self>
+  where
+    P: AsRef<std::path::Path>,
+  {
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+
+    Ok(Self {
+      sink: Some(std::sync::Arc::new(std::sync::Mutex::new(file))),
+    })
+  }
+
+  /// Audited `std::fs::remove_dir_all`.
+  pub fn remove_dir_all(&self, path: &std::path::Path) -> io::Result<()> {
+    let result = std::fs::remove_dir_all(path);
+    self.record("remove_dir_all", &[path], &result);
+    result
+  }
+
+  /// Audited `std::fs::remove_dir`.
+  pub fn remove_dir(&self, path: &std::path::Path) -> io::Result<()> {
+    let result = std::fs::remove_dir(path);
+    self.record("remove_dir", &[path], &result);
+    result
+  }
+
+  /// Audited `std::fs::create_dir_all`.
+  pub fn create_dir_all(&self, path: &std::path::Path) -> io::Result<()> {
+    let result = std::fs::create_dir_all(path);
+    self.record("create_dir_all", &[path], &result);
+    result
+  }
+
+  /// Audited `std::fs::rename`.
+  pub fn rename(&self, from: &std::path::Path, to: &std::path::Path) -> io::Result<()> {
+    let result = std::fs::rename(from, to);
+    self.record("rename", &[from, to], &result);
+    result
+  }
+
+  /// Audited `std::fs::remove_file`.
+  pub fn remove_file(&self, path: &std::path::Path) -> io::Result<()> {
+    let result = std::fs::remove_file(path);
+    self.record("remove_file", &[path], &result);
+    result
+  }
+
+  /// Audited `std::fs::copy`.
+  pub fn copy(&self, from: &std::path::Path, to: &std::path::Path) -> io::Result<()> {
+    let result = std::fs::copy(from, to).map(|_| ());
+    self.record("copy", &[from, to], &result);
+    result
+  }
+
+  /// Audited symlink creation: `link` is created pointing at `target` which, when relative, is
+  /// relative to the directory of `link`. On windows the kind of link (file or directory) follows
+  /// what `target` currently is.
+  pub fn symlink(&self, target: &std::path::Path, link: &std::path::Path) -> io::Result<()> {
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(target, link);
+    #[cfg(windows)]
+    let result = match link.parent().map(|parent| parent.join(target)) {
+      Some(resolved) if resolved.is_dir() => std::os::windows::fs::symlink_dir(target, link),
+      _ => std::os::windows::fs::symlink_file(target, link),
+    };
+    #[cfg(not(any(unix, windows)))]
+    let result = Err(io::Error::new(
+      io::ErrorKind::Unsupported,
+      "symlinks are not supported on this platform",
+    ));
+
+    self.record("symlink", &[target, link], &result);
+    result
+  }
+
+  /// Appends a record of an operation. Failing to write the audit record is logged, but does not
+  /// fail the operation itself.
+  fn record(&self, operation: &str, paths: &[&std::path::Path], result: &io::Result<()>) {
+    let Some(sink) = self.sink.as_ref() else {
+      return;
+    };
+
+    let timestamp = std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)
+      .map(|elapsed| elapsed.as_millis() as u64)
+      .unwrap_or_default();
+
+    let record = serde_json::json!({
+      "timestamp_ms": timestamp,
+      "operation": operation,
+      "paths": paths.iter().map(|path| path.display().to_string()).collect::<Vec<String>>(),
+      "error": result.as_ref().err().map(|error| error.to_string()),
+    });
+
+    let written = sink
+      .lock()
+      .map_err(|error| io::Error::other(error.to_string()))
+      .and_then(|mut file| writeln!(file, "{record}"));
+
+    if let Err(error) = written {
+      log::error!("unable to write audit record for {operation} - {error}");
+    }
+  }
+}
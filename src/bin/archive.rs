@@ -0,0 +1,311 @@
+//! Reproducible tarballs of a checkout, for shipping the layers of a manifest to machines that
+//! cannot reach its remotes. Entries are written in sorted order with normalized owners, modes and
+//! modification times, so archiving the same checkout twice produces the same bytes.
+
+use std::io::{self, Read, Write};
+
+/// The size of a tar block; headers take one and contents are padded to a multiple of it.
+const BLOCK: usize = 512;
+
+/// The largest size (or modification time) the octal fields of a ustar header hold.
+const USTAR_MAX: u64 = 0o77_777_777_777;
+
+/// How an archive is compressed, chosen from the extension of its path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+  /// A plain `.tar`.
+  None,
+  /// A `.tar.gz` (or `.tgz`), compressed by `gzip`.
+  Gzip,
+  /// A `.tar.zst` (or `.tzst`), compressed by `zstd`.
+  Zstd,
+}
+
+impl Compression {
+  /// The compression of the archive at `path`, from its extension.
+  pub fn from_path(path: &std::path::Path) -> io::Result<Self> {
+    let name = path
+      .file_name()
+      .map(|name| name.to_string_lossy().to_ascii_lowercase())
+      .unwrap_or_default();
+    match name {
+      name if name.ends_with(".tar") => Ok(Self::None),
+      name if name.ends_with(".tar.gz") || name.ends_with(".tgz") => Ok(Self::Gzip),
+      name if name.ends_with(".tar.zst") || name.ends_with(".tzst") => Ok(Self::Zstd),
+      _ => Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("'{}' is not a .tar, .tar.gz or .tar.zst archive", path.display()),
+      )),
+    }
+  }
+
+  /// The program (and its arguments) compressing stdin to stdout, if any. Neither records the
+  /// name or time of its input, which would make archives differ between runs.
+  fn command(self) -> Option<(&'static str, &'static [&'static str])> {
+    match self {
+      Self::None => None,
+      Self::Gzip => Some(("gzip", &["-c", "-n"])),
+      Self::Zstd => Some(("zstd", &["-c", "-q"])),
+    }
+  }
+}
+
+/// The paths (relative to the destination `root`) archived for `sources`: everything within the
+/// checkout of each project, the files copied and linked out of them, and the directories leading
+/// to both. When `exclude_git`, `.git` directories (and the `.git` files of submodules) are left
+/// out.
+pub fn paths(
+  root: &std::path::Path,
+  sources: &[repors::Source],
+  exclude_git: bool,
+) -> io::Result<std::collections::BTreeSet<std::path::PathBuf>> {
+  let mut paths = std::collections::BTreeSet::new();
+
+  for source in sources {
+    let project = source.relative_path();
+    let placed = source
+      .copyfiles
+      .iter()
+      .map(repors::CopyFile::relative_dest)
+      .chain(source.linkfiles.iter().map(repors::LinkFile::relative_dest))
+      .filter(|path| root.join(path).symlink_metadata().is_ok())
+      .collect::<Vec<_>>();
+
+    for path in placed.iter().chain(std::iter::once(&project)) {
+      paths.extend(
+        path
+          .ancestors()
+          .skip(1)
+          .filter(|parent| !parent.as_os_str().is_empty())
+          .map(std::path::Path::to_path_buf),
+      );
+    }
+    paths.extend(placed);
+    walk(root, &project, exclude_git, &mut paths)?;
+  }
+
+  Ok(paths)
+}
+
+/// Adds `path` (relative to `root`) to `paths`, along with everything within it when it is a
+/// directory; symlinks are not followed.
+fn walk(
+  root: &std::path::Path,
+  path: &std::path::Path,
+  exclude_git: bool,
+  paths: &mut std::collections::BTreeSet<std::path::PathBuf>,
+) -> io::Result<()> {
+  if exclude_git && path.file_name().is_some_and(|name| name == ".git") {
+    return Ok(());
+  }
+
+  let metadata = root.join(path).symlink_metadata()?;
+  paths.insert(path.to_path_buf());
+  if metadata.is_dir() {
+    for entry in std::fs::read_dir(root.join(path))? {
+      walk(root, &path.join(entry?.file_name()), exclude_git, paths)?;
+    }
+  }
+  Ok(())
+}
+
+/// Writes a tar archive of `paths` (relative to `root`) to `output`, compressed as its extension
+/// says. Every entry is owned by root, modified at `mtime` (seconds since the epoch) and has a
+/// mode of 0644 (0755 for directories and executables).
+pub fn write(
+  root: &std::path::Path,
+  paths: &std::collections::BTreeSet<std::path::PathBuf>,
+  output: &std::path::Path,
+  mtime: u64,
+) -> io::Result<()> {
+  let compression = Compression::from_path(output)?;
+  let file = std::fs::File::create(output)?;
+
+  let Some((program, args)) = compression.command() else {
+    let mut writer = io::BufWriter::new(file);
+    write_tar(root, paths, mtime, &mut writer)?;
+    return writer.flush();
+  };
+
+  let mut child = std::process::Command::new(program)
+    .args(args)
+    .stdin(std::process::Stdio::piped())
+    .stdout(file)
+    .spawn()
+    .map_err(|error| {
+      io::Error::new(
+        error.kind(),
+        format!("unable to run '{program}' to compress the archive - {error}"),
+      )
+    })?;
+
+  let written = match child.stdin.take() {
+    Some(stdin) => {
+      let mut writer = io::BufWriter::new(stdin);
+      write_tar(root, paths, mtime, &mut writer).and_then(|_| writer.flush())
+    }
+    None => Err(io::Error::other(format!("unable to write to '{program}'"))),
+  };
+  let status = child.wait()?;
+  written?;
+  if status.success() {
+    Ok(())
+  } else {
+    Err(io::Error::other(format!(
+      "'{program}' failed compressing the archive ({status})"
+    )))
+  }
+}
+
+/// Writes the tar stream of `paths` to `writer`.
+fn write_tar<W>(
+  root: &std::path::Path,
+  paths: &std::collections::BTreeSet<std::path::PathBuf>,
+  mtime: u64,
+  writer: &mut W,
+) -> io::Result<()>
+where
+  W: Write,
+{
+  for path in paths {
+    let full = root.join(path);
+    let metadata = full.symlink_metadata()?;
+    let mut name = tar_name(path);
+
+    if metadata.is_dir() {
+      name.push(b'/');
+      write_header(writer, &name, 0o755, 0, mtime, b'5', b"")?;
+    } else if metadata.is_symlink() {
+      let target = std::fs::read_link(&full)?;
+      let target = target.as_os_str().as_encoded_bytes();
+      write_header(writer, &name, 0o777, 0, mtime, b'2', target)?;
+    } else {
+      let mode = if executable(&metadata) { 0o755 } else { 0o644 };
+      write_header(writer, &name, mode, metadata.len(), mtime, b'0', b"")?;
+      let copied = io::copy(&mut std::fs::File::open(&full)?.take(metadata.len()), writer)?;
+      if copied != metadata.len() {
+        return Err(io::Error::other(format!(
+          "'{}' changed while it was being archived",
+          path.display()
+        )));
+      }
+      pad(writer, copied)?;
+    }
+  }
+
+  writer.write_all(&[0; BLOCK * 2])
+}
+
+/// The bytes of `path` as tar names them, with `/` separating its components.
+fn tar_name(path: &std::path::Path) -> Vec<u8> {
+  let mut name = Vec::new();
+  for component in path.components() {
+    if !name.is_empty() {
+      name.push(b'/');
+    }
+    name.extend_from_slice(component.as_os_str().as_encoded_bytes());
+  }
+  name
+}
+
+/// True when `metadata` is that of an executable file.
+fn executable(metadata: &std::fs::Metadata) -> bool {
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+  }
+  #[cfg(not(unix))]
+  {
+    let _ = metadata;
+    false
+  }
+}
+
+/// Writes the ustar header of an entry, preceded by a pax extended header holding its name, link
+/// target or size when they do not fit the ustar fields.
+fn write_header<W>(
+  writer: &mut W,
+  name: &[u8],
+  mode: u32,
+  size: u64,
+  mtime: u64,
+  kind: u8,
+  link: &[u8],
+) -> io::Result<()>
+where
+  W: Write,
+{
+  let mut records = Vec::new();
+  if name.len() > 100 {
+    records.extend(pax_record("path", name));
+  }
+  if link.len() > 100 {
+    records.extend(pax_record("linkpath", link));
+  }
+  if size > USTAR_MAX {
+    records.extend(pax_record("size", size.to_string().as_bytes()));
+  }
+  if !records.is_empty() {
+    let len = records.len() as u64;
+    writer.write_all(&header(b"././@PaxHeader", 0o644, len, mtime, b'x', b""))?;
+    writer.write_all(&records)?;
+    pad(writer, len)?;
+  }
+
+  writer.write_all(&header(name, mode, size, mtime, kind, link))
+}
+
+/// A pax extended header record: `<length> <key>=<value>\n`, where the length counts itself.
+fn pax_record(key: &str, value: &[u8]) -> Vec<u8> {
+  let rest = key.len() + value.len() + 3;
+  let mut length = rest + 1;
+  while length != rest + length.to_string().len() {
+    length += 1;
+  }
+
+  let mut record = format!("{length} {key}=").into_bytes();
+  record.extend_from_slice(value);
+  record.push(b'\n');
+  record
+}
+
+/// A ustar header block; names, link targets and sizes too large for its fields are truncated
+/// (their pax records are what readers use).
+fn header(name: &[u8], mode: u32, size: u64, mtime: u64, kind: u8, link: &[u8]) -> [u8; BLOCK] {
+  let mut block = [0; BLOCK];
+  let mut field = |offset: usize, len: usize, value: &[u8]| {
+    let value = &value[..value.len().min(len)];
+    block[offset..offset + value.len()].copy_from_slice(value);
+  };
+  let octal = |value: u64, len: usize| format!("{:0width$o}", value.min(USTAR_MAX), width = len - 1);
+
+  field(0, 100, name);
+  field(100, 8, octal(u64::from(mode), 8).as_bytes());
+  field(108, 8, octal(0, 8).as_bytes());
+  field(116, 8, octal(0, 8).as_bytes());
+  field(124, 12, octal(size, 12).as_bytes());
+  field(136, 12, octal(mtime, 12).as_bytes());
+  field(148, 8, b"        ");
+  field(156, 1, &[kind]);
+  field(157, 100, link);
+  field(257, 8, b"ustar\x0000");
+  field(265, 32, b"root");
+  field(297, 32, b"root");
+
+  let checksum = block.iter().map(|byte| u32::from(*byte)).sum::<u32>();
+  block[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+  block
+}
+
+/// Pads the `written` bytes of an entry's contents to a whole number of blocks.
+fn pad<W>(writer: &mut W, written: u64) -> io::Result<()>
+where
+  W: Write,
+{
+  let remainder = (written % BLOCK as u64) as usize;
+  match remainder {
+    0 => Ok(()),
+    _ => writer.write_all(&[0; BLOCK][remainder..]),
+  }
+}
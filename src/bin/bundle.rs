@@ -0,0 +1,103 @@
+//! Git bundles of every project of a checkout, for moving a checkout onto machines without any
+//! network access. Each bundle is a standard (v2) git bundle holding the history of the commit a
+//! project has checked out, so `git clone` reads them as well.
+
+use std::io::{self, BufRead, Write};
+
+/// The first line of a (v2) git bundle.
+const SIGNATURE: &str = "# v2 git bundle";
+
+/// The name of the pinned manifest written alongside the bundles.
+pub const MANIFEST: &str = "manifest.xml";
+
+/// The ref a bundle advertises its commit as.
+const HEAD: &str = "HEAD";
+
+/// Wraps a libgit2 failure involving the bundle at `path` with some context.
+fn bundle_error(path: &std::path::Path, error: git2::Error) -> io::Error {
+  io::Error::other(format!("bundle '{}' - {}", path.display(), error.message()))
+}
+
+/// Where, within `dir`, the bundle of the project checked out at `source`'s path is kept: the path
+/// of the project followed by `.bundle`.
+pub fn path(dir: &std::path::Path, source: &repors::Source) -> std::path::PathBuf {
+  let mut path = dir.join(source.relative_path()).into_os_string();
+  path.push(".bundle");
+  path.into()
+}
+
+/// Writes the bundle of `commit` (and all of its history held by `repo`) to `path`.
+pub fn create(repo: &git2::Repository, commit: git2::Oid, path: &std::path::Path) -> io::Result<()> {
+  let fail = |error| bundle_error(path, error);
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+
+  let mut walk = repo.revwalk().map_err(fail)?;
+  walk.push(commit).map_err(fail)?;
+  let mut builder = repo.packbuilder().map_err(fail)?;
+  builder.insert_walk(&mut walk).map_err(fail)?;
+
+  let mut writer = io::BufWriter::new(std::fs::File::create(path)?);
+  write!(writer, "{SIGNATURE}\n{commit} {HEAD}\n\n")?;
+  let mut written = Ok(());
+  builder
+    .foreach(|chunk| {
+      written = writer.write_all(chunk);
+      written.is_ok()
+    })
+    .map_err(fail)?;
+  written?;
+  writer.flush()
+}
+
+/// Adds the objects of the bundle at `path` to the (bare) repository at `into`, creating it if
+/// needed, and points `branch` at the bundle's commit, which is returned.
+pub fn unbundle(path: &std::path::Path, into: &std::path::Path, branch: &str) -> io::Result<git2::Oid> {
+  let fail = |error| bundle_error(path, error);
+  let invalid = || {
+    io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("'{}' is not a v2 git bundle", path.display()),
+    )
+  };
+
+  let mut reader = io::BufReader::new(std::fs::File::open(path)?);
+  let mut line = String::new();
+  reader.read_line(&mut line)?;
+  if line.trim_end() != SIGNATURE {
+    return Err(invalid());
+  }
+
+  let mut tip = None;
+  loop {
+    line.clear();
+    if reader.read_line(&mut line)? == 0 {
+      return Err(invalid());
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    // Prerequisites (`-<oid>`) would name commits the bundle leaves out; ours never have any.
+    let (oid, name) = line.split_once(' ').ok_or_else(invalid)?;
+    if name == HEAD || tip.is_none() {
+      tip = Some(git2::Oid::from_str(oid).map_err(|_| invalid())?);
+    }
+  }
+  let tip = tip.ok_or_else(invalid)?;
+
+  let repo = match git2::Repository::open_bare(into) {
+    Ok(repo) => repo,
+    Err(_) => git2::Repository::init_bare(into).map_err(fail)?,
+  };
+  let odb = repo.odb().map_err(fail)?;
+  let mut writer = odb.packwriter().map_err(fail)?;
+  io::copy(&mut reader, &mut writer)?;
+  writer.commit().map_err(fail)?;
+
+  let branch = format!("refs/heads/{branch}");
+  repo.reference(&branch, tip, true, "unbundled").map_err(fail)?;
+  repo.set_head(&branch).map_err(fail)?;
+  Ok(tip)
+}
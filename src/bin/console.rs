@@ -0,0 +1,40 @@
+//! The observer responsible for everything the command line prints to stdout while executing.
+
+/// Prints human readable progress of an execution to stdout.
+pub struct ConsoleObserver {
+  /// The destination being populated.
+  pub destination: String,
+  /// The manifest the destination is being populated from.
+  pub manifest: String,
+  /// When true, the staging path of every source is printed once complete.
+  pub show_staging: bool,
+}
+
+impl repors::ProgressObserver for ConsoleObserver {
+  fn execution_started(&self, _sources: Option<usize>) {
+    println!(
+      "populating '{}' from '{}', please wait...",
+      self.destination, self.manifest
+    );
+  }
+
+  fn execution_finished(&self, report: &repors::ExecutionReport) {
+    for source in &report.sources {
+      let timings = &source.timings;
+      log::info!(
+        source = source.destination.as_str();
+        "clone {:?}, resolve {:?}, checkout {:?}, place {:?}",
+        timings.clone,
+        timings.resolve,
+        timings.checkout,
+        timings.place
+      );
+
+      if self.show_staging {
+        println!("  '{}' -> '{}'", source.staging.display(), source.destination);
+      }
+    }
+
+    println!("success! ({})", report.counts());
+  }
+}
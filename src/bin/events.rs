@@ -0,0 +1,239 @@
+//! An observer that writes newline-delimited json lifecycle events to a file, file descriptor or
+//! unix socket so that wrapper tools can display live status without parsing logs.
+
+use std::io;
+use std::io::Write;
+
+/// How often (at most) we will emit a `job_progress` event for a single source.
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// The mutable state behind our observer.
+struct Inner {
+  /// Where events are written.
+  sink: Box<dyn io::Write + Send>,
+  /// The last time a progress event was written, per source.
+  last_progress: std::collections::HashMap<String, std::time::Instant>,
+}
+
+/// Writes one json object per execution event to some sink.
+pub struct EventStream(std::sync::Mutex<Inner>);
+
+impl EventStream {
+  /// Opens the event stream target. Supported forms are `fd:<number>` (an already open file
+  /// descriptor), `unix:<path>` (a listening unix socket) and anything else is treated as a file
+  /// path that will be created (or truncated).
+  pub fn open(target: &str) -> io::Result<Self> {
+    let sink: Box<dyn io::Write + Send> = match (target.strip_prefix("fd:"), target.strip_prefix("unix:")) {
+      (Some(fd), _) => Box::new(open_fd(fd)?),
+      (_, Some(path)) => Box::new(open_socket(path)?),
+      _ => Box::new(std::fs::File::create(target)?),
+    };
+
+    Ok(Self(std::sync::Mutex::new(Inner {
+      sink,
+      last_progress: Default::default(),
+    })))
+  }
+
+  /// Writes events to stdout, for `--format json`.
+  pub fn stdout() -> Self {
+    Self(std::sync::Mutex::new(Inner {
+      sink: Box::new(io::stdout()),
+      last_progress: Default::default(),
+    }))
+  }
+
+  /// Writes a single event; failures are logged but otherwise ignored so that a closed reader does
+  /// not interrupt the execution.
+  fn emit(&self, event: &str, mut fields: serde_json::Map<String, serde_json::Value>) {
+    let timestamp = std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)
+      .map(|elapsed| elapsed.as_millis() as u64)
+      .unwrap_or_default();
+    fields.insert("event".to_string(), event.into());
+    fields.insert("timestamp_ms".to_string(), timestamp.into());
+
+    let Ok(mut inner) = self.0.lock() else {
+      return;
+    };
+
+    let line = serde_json::Value::Object(fields);
+    if let Err(error) = writeln!(inner.sink, "{line}").and_then(|_| inner.sink.flush()) {
+      log::warn!("unable to write to event stream - {error}");
+    }
+  }
+}
+
+/// Builds the field map shared by all source events.
+fn source_fields(source: &repors::Source) -> serde_json::Map<String, serde_json::Value> {
+  let mut fields = serde_json::Map::new();
+  fields.insert("source".to_string(), source.destination.as_str().into());
+  fields.insert("origin".to_string(), repors::redact(source.origin()).into());
+  fields
+}
+
+/// Builds the field map shared by all placement events.
+fn placement_fields(destination: &std::path::Path) -> serde_json::Map<String, serde_json::Value> {
+  let mut fields = serde_json::Map::new();
+  fields.insert(
+    "destination".to_string(),
+    destination.display().to_string().into(),
+  );
+  fields
+}
+
+/// The name of `outcome` in events.
+fn outcome_name(outcome: repors::SourceOutcome) -> &'static str {
+  match outcome {
+    repors::SourceOutcome::Cloned => "cloned",
+    repors::SourceOutcome::Updated => "updated",
+    repors::SourceOutcome::Cached => "cached",
+    repors::SourceOutcome::Skipped => "skipped",
+    _ => "other",
+  }
+}
+
+impl repors::ProgressObserver for EventStream {
+  fn execution_started(&self, sources: Option<usize>) {
+    let mut fields = serde_json::Map::new();
+    fields.insert("sources".to_string(), sources.into());
+    self.emit("execution_started", fields);
+  }
+
+  fn job_queued(&self, source: &repors::Source) {
+    self.emit("job_queued", source_fields(source));
+  }
+
+  fn job_started(&self, source: &repors::Source) {
+    self.emit("job_started", source_fields(source));
+  }
+
+  fn job_progress(&self, source: &repors::Source, progress: &repors::TransferProgress) {
+    {
+      let Ok(mut inner) = self.0.lock() else {
+        return;
+      };
+      let now = std::time::Instant::now();
+      let last = inner
+        .last_progress
+        .entry(source.destination.clone())
+        .or_insert(now);
+      let done = progress.received_objects == progress.total_objects;
+      if !done && *last != now && last.elapsed() < PROGRESS_INTERVAL {
+        return;
+      }
+      *last = now;
+    }
+
+    let mut fields = source_fields(source);
+    fields.insert("received_objects".to_string(), progress.received_objects.into());
+    fields.insert("total_objects".to_string(), progress.total_objects.into());
+    fields.insert("received_bytes".to_string(), progress.received_bytes.into());
+    self.emit("job_progress", fields);
+  }
+
+  fn checkout_finished(&self, source: &repors::Source, commit: git2::Oid) {
+    let mut fields = source_fields(source);
+    fields.insert("commit".to_string(), commit.to_string().into());
+    self.emit("checkout_finished", fields);
+  }
+
+  fn job_finished(&self, source: &repors::Source, result: Result<(), &repors::SourceError>) {
+    let mut fields = source_fields(source);
+    fields.insert("success".to_string(), result.is_ok().into());
+    if let Err(error) = result {
+      fields.insert("error".to_string(), error.to_string().into());
+    }
+    self.emit("job_finished", fields);
+  }
+
+  fn placement_started(&self, destination: &std::path::Path) {
+    self.emit("placement_started", placement_fields(destination));
+  }
+
+  fn placement_finished(&self, destination: &std::path::Path) {
+    self.emit("placement_finished", placement_fields(destination));
+  }
+
+  fn execution_finished(&self, report: &repors::ExecutionReport) {
+    let millis = |elapsed: std::time::Duration| elapsed.as_millis() as u64;
+    let sources = report
+      .sources
+      .iter()
+      .map(|source| {
+        serde_json::json!({
+          "source": source.destination,
+          "origin": repors::redact(&source.origin),
+          "commit": source.commit.to_string(),
+          "outcome": outcome_name(source.outcome),
+          "received_bytes": source.received_bytes,
+          "timings_ms": {
+            "clone": millis(source.timings.clone),
+            "resolve": millis(source.timings.resolve),
+            "checkout": millis(source.timings.checkout),
+            "place": millis(source.timings.place),
+          },
+        })
+      })
+      .collect::<Vec<_>>();
+    let counts = report.counts();
+
+    let mut fields = serde_json::Map::new();
+    fields.insert("sources".to_string(), sources.into());
+    fields.insert(
+      "counts".to_string(),
+      serde_json::json!({
+        "cloned": counts.cloned,
+        "updated": counts.updated,
+        "cached": counts.cached,
+        "skipped": counts.skipped,
+      }),
+    );
+    self.emit("execution_finished", fields);
+  }
+}
+
+/// Wraps an already-open file descriptor provided by our parent process.
+#[cfg(unix)]
+fn open_fd(fd: &str) -> io::Result<std::fs::File> {
+  use std::os::fd::FromRawFd;
+
+  let fd = fd.parse::<std::os::fd::RawFd>().map_err(|error| {
+    io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("invalid fd '{fd}' - {error}"),
+    )
+  })?;
+
+  if fd < 0 {
+    return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid negative fd"));
+  }
+
+  // SAFETY: the caller asked us to write to this descriptor, handing its ownership to us for the
+  // lifetime of the process.
+  Ok(unsafe { std::fs::File::from_raw_fd(fd) })
+}
+
+/// File descriptors are only supported on unix platforms.
+#[cfg(not(unix))]
+fn open_fd(_fd: &str) -> io::Result<std::fs::File> {
+  Err(io::Error::new(
+    io::ErrorKind::Unsupported,
+    "fd event streams are only supported on unix",
+  ))
+}
+
+/// Connects to a listening unix socket.
+#[cfg(unix)]
+fn open_socket(path: &str) -> io::Result<std::os::unix::net::UnixStream> {
+  std::os::unix::net::UnixStream::connect(path)
+}
+
+/// Unix sockets are only supported on unix platforms.
+#[cfg(not(unix))]
+fn open_socket(_path: &str) -> io::Result<std::fs::File> {
+  Err(io::Error::new(
+    io::ErrorKind::Unsupported,
+    "socket event streams are only supported on unix",
+  ))
+}
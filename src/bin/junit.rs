@@ -0,0 +1,141 @@
+//! An observer that records the result of every project so that a JUnit xml report can be written
+//! once execution completes, letting CI servers (Jenkins, GitLab) display sync failures natively.
+
+use std::io;
+
+/// What happened to a single project.
+struct Case {
+  /// The name of the project in the manifest.
+  name: String,
+  /// The path of the project in the manifest.
+  destination: String,
+  /// When a worker began cloning the project, if it did.
+  started: Option<std::time::Instant>,
+  /// How long the project took, once finished.
+  elapsed: Option<std::time::Duration>,
+  /// Why the project failed, if it did.
+  failure: Option<(String, String)>,
+}
+
+/// Records one test case per project. Clones share the same recording.
+#[derive(Clone, Default)]
+pub struct JunitObserver {
+  /// The recorded cases, in the order their projects were queued.
+  cases: std::sync::Arc<std::sync::Mutex<Vec<Case>>>,
+}
+
+impl JunitObserver {
+  /// Calls `update` with the case of `source`, if it was queued.
+  fn update<F>(&self, source: &repors::Source, update: F)
+  where
+    F: FnOnce(&mut Case),
+  {
+    let Ok(mut cases) = self.cases.lock() else {
+      return;
+    };
+    if let Some(case) = cases
+      .iter_mut()
+      .find(|case| case.destination == source.destination)
+    {
+      update(case);
+    }
+  }
+
+  /// Writes the report to `path`, as a single test suite named after `suite`. Projects that never
+  /// finished (e.g. because execution was interrupted) are reported as skipped.
+  pub fn write(&self, path: &std::path::Path, suite: &str) -> io::Result<()> {
+    use io::Write;
+    use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+
+    let cases = self
+      .cases
+      .lock()
+      .map_err(|error| io::Error::other(error.to_string()))?;
+    let seconds =
+      |elapsed: Option<std::time::Duration>| format!("{:.3}", elapsed.unwrap_or_default().as_secs_f64());
+    let count = |predicate: fn(&&Case) -> bool| cases.iter().filter(predicate).count().to_string();
+    let failures = count(|case| case.failure.is_some());
+    let skipped = count(|case| case.elapsed.is_none());
+    let total = cases.iter().filter_map(|case| case.elapsed).sum();
+
+    let file = std::fs::File::create(path)?;
+    let mut xml = quick_xml::Writer::new_with_indent(io::BufWriter::new(file), b' ', 2);
+    xml.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    let attributes = [
+      ("name", suite),
+      ("tests", &cases.len().to_string()),
+      ("failures", &failures),
+      ("errors", "0"),
+      ("skipped", &skipped),
+      ("time", &seconds(Some(total))),
+    ];
+    xml.write_event(Event::Start(
+      BytesStart::new("testsuites").with_attributes(attributes),
+    ))?;
+    xml.write_event(Event::Start(
+      BytesStart::new("testsuite").with_attributes(attributes),
+    ))?;
+
+    for case in cases.iter() {
+      let testcase = BytesStart::new("testcase").with_attributes([
+        ("classname", case.name.as_str()),
+        ("name", case.destination.as_str()),
+        ("time", &seconds(case.elapsed)),
+      ]);
+
+      match (&case.failure, case.elapsed) {
+        (Some((message, details)), _) => {
+          xml.write_event(Event::Start(testcase))?;
+          let failure = BytesStart::new("failure").with_attributes([("message", message.as_str())]);
+          xml.write_event(Event::Start(failure))?;
+          xml.write_event(Event::Text(BytesText::new(details)))?;
+          xml.write_event(Event::End(BytesEnd::new("failure")))?;
+          xml.write_event(Event::End(BytesEnd::new("testcase")))?;
+        }
+        (None, None) => {
+          xml.write_event(Event::Start(testcase))?;
+          xml.write_event(Event::Empty(BytesStart::new("skipped")))?;
+          xml.write_event(Event::End(BytesEnd::new("testcase")))?;
+        }
+        (None, Some(_)) => xml.write_event(Event::Empty(testcase))?,
+      }
+    }
+
+    xml.write_event(Event::End(BytesEnd::new("testsuite")))?;
+    xml.write_event(Event::End(BytesEnd::new("testsuites")))?;
+    let mut writer = xml.into_inner();
+    writer.write_all(b"\n")?;
+    writer.flush()
+  }
+}
+
+impl repors::ProgressObserver for JunitObserver {
+  fn job_queued(&self, source: &repors::Source) {
+    if let Ok(mut cases) = self.cases.lock() {
+      cases.push(Case {
+        name: source.name.clone(),
+        destination: source.destination.clone(),
+        started: None,
+        elapsed: None,
+        failure: None,
+      });
+    }
+  }
+
+  fn job_started(&self, source: &repors::Source) {
+    self.update(source, |case| case.started = Some(std::time::Instant::now()));
+  }
+
+  fn job_finished(&self, source: &repors::Source, result: Result<(), &repors::SourceError>) {
+    self.update(source, |case| {
+      case.elapsed = Some(case.started.map(|started| started.elapsed()).unwrap_or_default());
+      case.failure = result.err().map(|error| {
+        let details = match std::error::Error::source(error) {
+          Some(inner) => format!("{error} - {inner}"),
+          None => error.to_string(),
+        };
+        (error.to_string(), details)
+      });
+    });
+  }
+}
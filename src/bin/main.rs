@@ -4,30 +4,961 @@
 //! openembedded community for layer management during the production of linux images.
 
 use std::io;
+use std::io::Write;
 
 use clap::Parser;
 
+/// The observer printing our progress to stdout.
+mod console;
+
+/// The newline-delimited json event stream observer.
+mod events;
+
+/// The chrome trace recording observer.
+mod trace;
+
+/// The JUnit report recording observer.
+mod junit;
+
+/// The reproducible tarballs of a checkout.
+mod archive;
+
+/// The git bundles moving a checkout between machines.
+mod bundle;
+
+/// The progress bars drawn to a terminal while cloning.
+mod progress;
+
+/// The software bills of materials describing a checkout.
+mod sbom;
+
+/// The state recorded by `init` in a destination.
+mod workspace;
+
+/// The format used when writing log events to stderr.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum LogFormat {
+  /// The default, human readable `env_logger` output.
+  #[default]
+  Text,
+  /// One json object per log event, suitable for ingestion by log aggregation tooling.
+  Json,
+}
+
+/// This type collects the structured key-value pairs attached to a log record so they can be
+/// written out as fields of our json log events.
+#[derive(Default)]
+struct JsonFields(serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for JsonFields {
+  fn visit_pair(
+    &mut self,
+    key: log::kv::Key<'kvs>,
+    value: log::kv::Value<'kvs>,
+  ) -> Result<(), log::kv::Error> {
+    self.0.insert(
+      key.as_str().to_string(),
+      serde_json::Value::String(value.to_string()),
+    );
+    Ok(())
+  }
+}
+
+/// Writes a single log record as a json object on its own line.
+fn write_json_record(buf: &mut env_logger::fmt::Formatter, record: &log::Record<'_>) -> io::Result<()> {
+  let event = json_record(record, buf.timestamp().to_string());
+  writeln!(buf, "{event}")
+}
+
+/// The json object a log record, logged at `timestamp`, is written as.
+fn json_record(record: &log::Record<'_>, timestamp: String) -> serde_json::Value {
+  let mut fields = JsonFields::default();
+  if let Err(error) = record.key_values().visit(&mut fields) {
+    fields.0.insert(
+      "kv_error".to_string(),
+      serde_json::Value::String(error.to_string()),
+    );
+  }
+
+  let mut event = serde_json::Map::new();
+  event.insert("timestamp".to_string(), timestamp.into());
+  event.insert("level".to_string(), record.level().as_str().into());
+  event.insert("target".to_string(), record.target().into());
+  event.insert(
+    "source".to_string(),
+    fields.0.remove("source").unwrap_or(serde_json::Value::Null),
+  );
+  event.insert(
+    "phase".to_string(),
+    fields.0.remove("phase").unwrap_or(serde_json::Value::Null),
+  );
+  event.insert("message".to_string(), record.args().to_string().into());
+  event.extend(fields.0);
+  serde_json::Value::Object(event)
+}
+
+/// Prepares the global logger based on the format requested on the command line.
+fn init_logging(format: LogFormat) {
+  let mut builder = env_logger::Builder::from_default_env();
+
+  if let LogFormat::Json = format {
+    builder.format(write_json_record);
+  }
+
+  let _ = builder.try_init();
+}
+
 /// We want our  command line interface is split across subcommands so we can add more
 /// functionality in the future.
 #[derive(clap::Subcommand, Clone, Debug)]
 enum Subcommand {
   /// This command will actually perform the git cloning of all the repositories listed in a
   /// manifest xml file.
-  Execute {
-    /// The number of threads to spawn for handling the cloning process.
-    #[clap(long, default_value = "3")]
-    threads: usize,
-    /// The location (filesystem path) of our xml manifest file.
-    #[clap(long, short)]
-    manifest: String,
-    /// The filesystem location we will consider as the root of our operation, where the `path`
-    /// values from the manifest will be relative to.
-    #[clap(long, short)]
-    destination: Option<String>,
-    /// When true, if `destination` exists, we will delete it.
-    #[clap(long, short = 'x', default_value = "false")]
-    overwrite: bool,
-  },
+  Execute(ExecuteArgs),
+  /// Brings an existing destination up to date with a manifest, updating checkouts in place.
+  ///
+  /// Projects that already have a checkout are fetched (only the objects they are missing) and
+  /// reset to their manifest revision in place, while the rest are cloned as `execute` would.
+  Sync(ExecuteArgs),
+  /// Checks a manifest for problems without cloning anything or contacting any remote.
+  ///
+  /// Every problem found is printed, rather than only the first; the exit code is non-zero when
+  /// any of them is an error.
+  Validate(ValidateArgs),
+  /// Prints the projects of a manifest, once includes and removals have been applied.
+  List(ListArgs),
+  /// Compares the checkouts in a destination to a manifest, without fetching anything.
+  ///
+  /// Reports, for every project, whether it is missing, whether its HEAD is the commit of its
+  /// manifest revision and whether its working tree has local changes. The exit code is non-zero
+  /// when any project is not up to date.
+  Status(StatusArgs),
+  /// Lists the commits made to every project between two (pinned) manifests.
+  ///
+  /// Commits are read from the checkouts in a destination, which must have fetched both revisions
+  /// of every project that moved; nothing is fetched. The exit code is non-zero when the commits of
+  /// any project could not be read.
+  Changelog(ChangelogArgs),
+  /// Creates (or updates) a bare mirror of every project of a manifest.
+  ///
+  /// Every ref of each project's remote is fetched into `<name>.git` within the destination, the
+  /// layout `repo init --mirror` uses, so the result can be served as an internal mirror. Existing
+  /// mirrors are fetched into, pruning refs deleted upstream.
+  Mirror(MirrorArgs),
+  /// Prepares a destination for `sync`, recording the manifest it follows.
+  ///
+  /// The manifest repository is cloned into `.repors/manifests` within the destination and the
+  /// chosen manifest, branch and groups are written to `.repors/config`. Later `sync` runs given no
+  /// `--manifest` fetch that branch and use its manifest. Running `init` again changes what is
+  /// recorded.
+  Init(InitArgs),
+  /// Removes the checkouts of projects the manifest no longer lists.
+  ///
+  /// Only checkouts placed by an earlier `execute` or `sync` are considered, so other repositories
+  /// within the destination (e.g. in a build directory) are never removed. Directories left empty
+  /// are removed as well.
+  Prune(PruneArgs),
+  /// Checks that every project is checked out at its manifest revision, with a clean working tree.
+  ///
+  /// Unlike `status`, only mismatches are printed; revisions are resolved within each checkout,
+  /// without fetching anything. The exit code is non-zero when any project does not match.
+  Verify(VerifyArgs),
+  /// Writes a software bill of materials describing the checkout of a manifest.
+  ///
+  /// Every project is listed with the url it is fetched from, the commit checked out (read from
+  /// its checkout, without fetching anything) and its path, as a CycloneDX or SPDX json document.
+  /// Fails when any project has no checkout to describe.
+  Sbom(SbomArgs),
+  /// Writes a reproducible tarball of the checkout of a manifest.
+  ///
+  /// Every project's checkout (and the files copied or linked out of it) is archived from an
+  /// existing destination, as left by `execute` or `sync`, for shipping to machines that cannot
+  /// reach the remotes. Entries are sorted and their owners, modes and times normalized, so the
+  /// same checkout always produces the same archive. Fails when any project has no checkout.
+  Archive(ArchiveArgs),
+  /// Moves a checkout to machines without network access through git bundles.
+  #[command(subcommand)]
+  Bundle(BundleAction),
+  /// Fetches every project's remote into a reference directory, without checking anything out.
+  ///
+  /// Meant to be run ahead of time (e.g. nightly), so that an `execute` or `sync` given the same
+  /// `--reference` directory only has to download what changed since. Shallow projects, which
+  /// never use the reference directory, are left out.
+  Prefetch(PrefetchArgs),
+}
+
+/// What the `bundle` subcommand does.
+#[derive(clap::Subcommand, Clone, Debug)]
+enum BundleAction {
+  /// Writes a git bundle of every project of a checkout, along with a manifest pinning each one to
+  /// the commit it has checked out.
+  ///
+  /// Each bundle holds the history of the commit checked out, as read from the checkout itself;
+  /// nothing is fetched. Fails when any project has no checkout to bundle.
+  Create(BundleCreateArgs),
+  /// Recreates a checkout from the bundles written by `bundle create`, without network access.
+  ///
+  /// Every project is checked out from its bundle at the commit it was pinned to, and placed as
+  /// `sync` would. The checkouts keep the urls of the original remotes, so later syncs fetch from
+  /// them as usual.
+  Apply(BundleApplyArgs),
+}
+
+/// How remotes (and manifests at http(s) urls) are reached; shared by every subcommand that fetches
+/// or reads a manifest.
+#[derive(clap::Args, Clone, Debug, Default)]
+struct TransportArgs {
+  /// The proxy http(s) remotes are fetched through (e.g. `http://proxy.example.com:3128`), rather
+  /// than the one named by the `https_proxy`/`http_proxy` environment variables. Hosts listed in
+  /// `no_proxy` are reached directly either way.
+  #[clap(long)]
+  proxy: Option<String>,
+  /// Fetches remotes whose url starts with `prefix` through `proxy` instead, or directly when
+  /// `proxy` is empty, as in `--proxy-for https://git.internal/=`. May be repeated; when several
+  /// match, the longest `prefix` wins.
+  #[clap(long = "proxy-for", value_name = "PREFIX=PROXY")]
+  proxy_overrides: Vec<String>,
+  /// A file of (pem encoded) certificate authorities trusted instead of the system ones, for
+  /// servers whose certificates are issued by a private certificate authority.
+  #[clap(long)]
+  ca_bundle: Option<std::path::PathBuf>,
+  /// Accepts any certificate https remotes present, without verifying it. This leaves every
+  /// connection open to interception; prefer `--ca-bundle`.
+  #[clap(long)]
+  insecure_tls: bool,
+  /// The (comma separated) hosts projects, and the manifest repository, may be fetched from; a
+  /// `*.` prefix allows every subdomain, as in `--allowed-hosts git.internal,*.corp.example`. Any
+  /// other remote fails execution before it is contacted.
+  #[clap(long, value_delimiter = ',')]
+  allowed_hosts: Vec<String>,
+  /// A file of allowed hosts (see `allowed-hosts`), one per line. Blank lines and lines starting
+  /// with `#` are ignored.
+  #[clap(long)]
+  allowed_hosts_file: Option<std::path::PathBuf>,
+  /// Allows remotes reached over cleartext transports (`http://` and `git://`), which are refused
+  /// otherwise so that a typo'd fetch url does not silently download code unencrypted.
+  #[clap(long)]
+  allow_insecure_remotes: bool,
+}
+
+impl TransportArgs {
+  /// The proxies remotes are fetched through.
+  fn proxies(&self) -> io::Result<repors::Proxies> {
+    let mut proxies = repors::Proxies::from_env();
+    if let Some(proxy) = &self.proxy {
+      proxies = proxies.with_proxy(proxy.clone());
+    }
+    self
+      .proxy_overrides
+      .iter()
+      .try_fold(proxies, |proxies, rule| proxies.with_override_str(rule))
+  }
+
+  /// The remotes projects may be fetched from.
+  fn remote_policy(&self) -> io::Result<repors::RemotePolicy> {
+    let mut policy = repors::RemotePolicy::default().with_secure_only(!self.allow_insecure_remotes);
+    if let Some(path) = &self.allowed_hosts_file {
+      policy = policy.with_hosts_file(path).map_err(|error| {
+        io::Error::new(
+          error.kind(),
+          format!("unable to read allowed hosts '{}' - {error}", path.display()),
+        )
+      })?;
+    }
+    Ok(
+      self
+        .allowed_hosts
+        .iter()
+        .fold(policy, |policy, host| policy.with_host(host.clone())),
+    )
+  }
+
+  /// Fails when the manifest (or manifest repository) at `url` is on a remote that is not allowed.
+  fn check_manifest_url(&self, url: &str) -> io::Result<()> {
+    match self.remote_policy()?.refusal(url) {
+      None => Ok(()),
+      Some(reason) => Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("refusing the manifest at '{}' - {reason}", repors::redact(url)),
+      )),
+    }
+  }
+
+  /// How the certificates of https servers are verified.
+  fn tls_settings(&self) -> repors::Tls {
+    let tls = repors::Tls::default().with_insecure(self.insecure_tls);
+    match &self.ca_bundle {
+      Some(path) => tls.with_ca_bundle(path.clone()),
+      None => tls,
+    }
+  }
+
+  /// How the certificates of remotes are verified, once the certificate authority bundle (if any)
+  /// has been installed.
+  fn tls(&self) -> io::Result<repors::Tls> {
+    let tls = self.tls_settings();
+    tls.install()?;
+
+    if tls.is_insecure() {
+      eprintln!(
+        "warning: --insecure-tls is set, the certificates of https remotes will NOT be verified and \
+         anyone on the network path may tamper with what is fetched"
+      );
+    }
+    Ok(tls)
+  }
+}
+
+/// The options of the `init` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct InitArgs {
+  /// The url of the git repository holding the manifest.
+  #[clap(long, short = 'u')]
+  manifest_url: String,
+  /// The branch of the manifest repository to follow; its default branch when not provided.
+  #[clap(long, short = 'b')]
+  manifest_branch: Option<String>,
+  /// The path of the manifest file within the manifest repository.
+  #[clap(long, short = 'm', default_value = "default.xml")]
+  manifest_name: String,
+  /// The (comma separated) groups `sync` selects when not given `--groups`.
+  #[clap(long, short = 'g', value_delimiter = ',')]
+  groups: Vec<String>,
+  /// The destination to prepare; the current directory when not provided.
+  #[clap(long, short)]
+  destination: Option<std::path::PathBuf>,
+  /// A token offered as the password to https remotes that ask for credentials; see `execute`.
+  #[clap(long)]
+  token: Option<String>,
+  /// How remotes are reached.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format progress and results are printed in; `json` prints one event per line.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `status` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct StatusArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// The destination the manifest was executed into; defaults to the working directory.
+  #[clap(long, short)]
+  destination: Option<std::path::PathBuf>,
+  /// How manifests at http(s) urls are downloaded.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format the status of each project is printed in.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `changelog` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct ChangelogArgs {
+  /// The location (filesystem path) of the older manifest.
+  #[clap(long)]
+  from: String,
+  /// The location (filesystem path) of the newer manifest, or `-` to read it from stdin.
+  #[clap(long)]
+  to: String,
+  /// The destination holding checkouts of the projects; defaults to the working directory.
+  #[clap(long, short)]
+  destination: Option<std::path::PathBuf>,
+  /// How manifests at http(s) urls are downloaded.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format the changes of each project are printed in.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `mirror` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct MirrorArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// The directory the mirrors are created in.
+  #[clap(long, short)]
+  destination: std::path::PathBuf,
+  /// The number of threads to spawn for fetching.
+  #[clap(long, default_value = "3")]
+  threads: usize,
+  /// When provided, at most this many projects are fetched from the same host at once; see
+  /// `execute`.
+  #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+  host_limit: Option<u32>,
+  /// A token offered as the password to https remotes that ask for credentials; see `execute`.
+  #[clap(long)]
+  token: Option<String>,
+  /// How remotes are reached.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format progress and results are printed in; `json` prints one event per line.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The format projects are printed in by the `list` subcommand.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum ListFormat {
+  /// An aligned table, one project per row.
+  #[default]
+  Table,
+  /// A json array holding an object per project.
+  Json,
+}
+
+/// The options of the `list` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct ListArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// How manifests at http(s) urls are downloaded.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format projects are printed in.
+  #[clap(long, value_enum, default_value = "table")]
+  format: ListFormat,
+}
+
+/// The format subcommands print in.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum OutputFormat {
+  /// Human readable lines, one per diagnostic, project or message.
+  #[default]
+  Text,
+  /// One json object per diagnostic, project or event, on its own line, for scripts.
+  Json,
+}
+
+/// The options of the `validate` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct ValidateArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// How manifests at http(s) urls are downloaded.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format diagnostics are printed in.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options shared by the `execute` and `sync` subcommands.
+#[derive(clap::Args, Clone, Debug)]
+struct ExecuteArgs {
+  /// The number of threads to spawn for handling the cloning process.
+  #[clap(long, default_value = "3")]
+  threads: usize,
+  /// The location (filesystem path) of our xml manifest file, `-` to read it from stdin, or an
+  /// `http://` url to download it from. Files and stdin are streamed rather than read into memory,
+  /// so pipes and process substitution (`-m <(generate-manifest)`) work as well as regular files.
+  /// Only `sync` may leave it out, in a destination prepared by `init`.
+  #[clap(long, short)]
+  manifest: Option<String>,
+  /// The filesystem location we will consider as the root of our operation, where the `path`
+  /// values from the manifest will be relative to.
+  #[clap(long, short)]
+  destination: Option<String>,
+  /// When true, if `destination` exists, we will delete it.
+  #[clap(long, short = 'x', default_value = "false")]
+  overwrite: bool,
+  /// When provided with `-x`, an existing `destination` is moved into this directory (named after
+  /// it and the time, e.g. `build-20240102T030405Z`) rather than deleted. The directory must be on
+  /// the same filesystem as the destination, and outside of it.
+  #[clap(long, requires = "overwrite")]
+  backup: Option<std::path::PathBuf>,
+  /// The number of seconds a fetch may go without receiving data before a warning is logged.
+  #[clap(long, default_value = "30")]
+  stall_warning: u64,
+  /// When provided, fetches that go this many seconds without receiving data are aborted.
+  #[clap(long)]
+  stall_abort: Option<u64>,
+  /// When provided, projects that take longer than this many seconds to clone (or update) fail,
+  /// their fetch aborted, rather than holding up the execution.
+  #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+  timeout_per_project: Option<u64>,
+  /// When provided, projects not done within this many seconds of the start of the execution fail,
+  /// including those not started by then.
+  #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+  deadline: Option<u64>,
+  /// When provided, newline-delimited json lifecycle events are written here as they happen.
+  /// Accepts a file path, `fd:<number>` for an inherited file descriptor or `unix:<path>` for a
+  /// unix socket.
+  #[clap(long)]
+  event_stream: Option<String>,
+  /// When provided, every destructive filesystem operation (directory removal, creation and
+  /// renames) is appended to this file as a json record.
+  #[clap(long)]
+  audit_log: Option<std::path::PathBuf>,
+  /// When true, the temporary staging directory sources are cloned into is not removed, and the
+  /// staging path of each source is printed once complete.
+  #[clap(long, default_value = "false")]
+  keep_temp: bool,
+  /// When provided, a chrome://tracing compatible json file of worker activity (one track per
+  /// worker, one slice per phase) is written here once execution completes.
+  #[clap(long)]
+  chrome_trace: Option<std::path::PathBuf>,
+  /// When provided, a JUnit xml report with one test case per project (passed or failed, how long
+  /// it took and why it failed) is written here once execution completes, even if it failed.
+  #[clap(long)]
+  report_junit: Option<std::path::PathBuf>,
+  /// A directory where observations from previous runs (clone sizes and durations) are kept and
+  /// updated, rather than in `.repors/timings.json` within the destination. The sources that took
+  /// longest last time are scheduled first, and once every source has been seen before, how long
+  /// the execution will take is estimated.
+  #[clap(long)]
+  cache_dir: Option<std::path::PathBuf>,
+  /// When provided, the number of threads is chosen from the observations of previous runs (see
+  /// `cache-dir`), up to this maximum; `threads` is used when any source has not been seen before.
+  #[clap(long)]
+  auto_threads: Option<usize>,
+  /// When true, sources are dispatched to the workers as the manifest is parsed, rather than once
+  /// it has been read in full. Scheduling based on `cache-dir` and shared fetches of remotes used
+  /// by more than one project are not available in this mode, and manifests that remove (with
+  /// `<remove-project>`) projects they have already listed are rejected.
+  #[clap(long, default_value = "false")]
+  pipeline: bool,
+  /// When true, nothing is cloned; each source's revision is resolved against the references
+  /// advertised by its remote and printed.
+  #[clap(long, default_value = "false")]
+  dry_run: bool,
+  /// How many seconds the references advertised by a remote are remembered (in `cache-dir`, when
+  /// provided) before the remote is queried again.
+  #[clap(long, default_value = "300")]
+  refs_ttl: u64,
+  /// When true, git's automatic garbage collection (and background maintenance) is disabled in
+  /// every repository created, so later git commands never repack large layers.
+  #[clap(long, default_value = "false")]
+  no_auto_gc: bool,
+  /// The zlib compression level (0-9) git uses for objects later written to the repositories.
+  #[clap(long, value_parser = clap::value_parser!(u8).range(0..=9))]
+  compression: Option<u8>,
+  /// The number of threads each worker uses to write the working tree of a source, independent
+  /// of the number of workers.
+  #[clap(long, default_value = "1")]
+  checkout_threads: usize,
+  /// When provided, only projects in at least one of these (comma separated) groups are cloned.
+  /// Besides the groups listed by a project, every project is in `all`, `name:<name>` and
+  /// `path:<path>`, and those not in `notdefault` are in `default`.
+  #[clap(long, value_delimiter = ',')]
+  groups: Vec<String>,
+  /// Projects in any of these (comma separated) groups are not cloned, even if selected by
+  /// `groups`.
+  #[clap(long, value_delimiter = ',')]
+  exclude_groups: Vec<String>,
+  /// When provided, only the project with this name is cloned (along with those selected by any
+  /// other `project` or `path`). May be repeated.
+  #[clap(long = "project")]
+  projects: Vec<String>,
+  /// When provided, only projects whose path matches this glob (e.g. `layers/meta-*`) or lies
+  /// within it are cloned (along with those selected by any other `project` or `path`). May be
+  /// repeated.
+  #[clap(long = "path")]
+  paths: Vec<String>,
+  /// When provided, only this many commits of history are fetched for every project, overriding the
+  /// `clone-depth` of the manifest. Remotes on the local filesystem are always cloned in full.
+  #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+  depth: Option<u32>,
+  /// When provided, at most this many projects are cloned from the same remote host (e.g. a single
+  /// Gerrit server) at once, however many threads there are, so that the server does not throttle
+  /// us. Threads left idle by the limit take projects of other hosts. Projects on the local
+  /// filesystem are not limited.
+  #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+  host_limit: Option<u32>,
+  /// When true, the submodules of every project are (recursively) checked out, as though every
+  /// project of the manifest set `sync-s`.
+  #[clap(long, default_value = "false")]
+  fetch_submodules: bool,
+  /// A token (e.g. a GitHub or GitLab personal access token) offered as the password to https
+  /// remotes that ask for credentials. `GIT_ASKPASS`, `~/.netrc` and git's credential helpers are
+  /// consulted after it (or instead, when not provided); prefer those to keep the token out of
+  /// the process list.
+  #[clap(long)]
+  token: Option<String>,
+  /// How remotes are reached.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// Rewrites the urls projects are fetched from, replacing the `from` prefix with `to`, as in
+  /// `--map-remote https://github.com/=https://mirror.internal/github/`. May be repeated; when
+  /// several match, the longest `from` wins.
+  #[clap(long = "map-remote", value_name = "FROM=TO")]
+  map_remotes: Vec<String>,
+  /// A file of `from=to` remote mappings (see `map-remote`), one per line. Blank lines and lines
+  /// starting with `#` are ignored.
+  #[clap(long)]
+  remote_map: Option<std::path::PathBuf>,
+  /// When true, no progress bars are drawn while sources are cloned. Bars are only ever drawn when
+  /// stdout is a terminal.
+  #[clap(long, short, default_value = "false")]
+  quiet: bool,
+  /// When provided, a manifest pinning every project to the exact commit that was checked out is
+  /// written here once execution succeeds, for reproducing the same checkout later. Requires the
+  /// whole manifest up front, so cannot be combined with `pipeline`.
+  #[clap(long, conflicts_with_all = ["pipeline", "dry_run"])]
+  snapshot_out: Option<std::path::PathBuf>,
+  /// A directory holding a bare repository per remote, kept between runs. Remotes are fetched
+  /// there (only downloading what changed since the last run) and checkouts borrow their objects
+  /// from it, so it must be kept for as long as they are. Not used for shallow clones.
+  #[clap(long, conflicts_with = "pipeline")]
+  reference: Option<std::path::PathBuf>,
+  /// The directory sources are cloned into before being moved into the destination. Defaults to
+  /// a `.repors-tmp` directory inside the destination, keeping the move on the same filesystem.
+  #[clap(long)]
+  temp_dir: Option<std::path::PathBuf>,
+  /// When true, `sync` removes the checkouts of projects the manifest no longer lists once it
+  /// succeeds; see the `prune` subcommand.
+  #[clap(long, default_value = "false", conflicts_with_all = ["pipeline", "dry_run", "projects", "paths"])]
+  prune: bool,
+  /// When true, every checkout is re-opened once execution succeeds, failing unless its HEAD is
+  /// the commit that was checked out and its working tree is clean; see the `verify` subcommand.
+  #[clap(long, default_value = "false", conflicts_with = "dry_run")]
+  verify: bool,
+  /// When true, a bare repository holding every ref of its remote is created at the path of each
+  /// project instead of a working tree, e.g. for serving the layers onward with cgit. `copyfile`s
+  /// and `linkfile`s are skipped.
+  #[clap(long, default_value = "false", conflicts_with_all = ["verify", "fetch_submodules", "reference"])]
+  bare: bool,
+  /// When true, the `.git` directory of every project (and of its submodules) is removed once it
+  /// has been checked out, leaving only the files at its revision, e.g. for source archives.
+  #[clap(long, default_value = "false", conflicts_with_all = ["verify", "bare"])]
+  no_git: bool,
+  /// When true, checkouts holding uncommitted changes or unpushed commits are discarded by `-x`,
+  /// by `sync` and by `prune` like any other; without it, doing so is refused.
+  #[clap(long, default_value = "false")]
+  force: bool,
+  /// The format progress and results are printed in; `json` prints one event per line.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `verify` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct VerifyArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// The destination the manifest was executed into; defaults to the working directory.
+  #[clap(long, short)]
+  destination: Option<std::path::PathBuf>,
+  /// How manifests at http(s) urls are downloaded.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format mismatches are printed in.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `sbom` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct SbomArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// The destination the manifest was executed into; defaults to the working directory.
+  #[clap(long, short)]
+  destination: Option<std::path::PathBuf>,
+  /// The standard the bill of materials is written in.
+  #[clap(long, value_enum, default_value = "cyclonedx")]
+  standard: sbom::Standard,
+  /// Where the bill of materials is written; stdout when not provided.
+  #[clap(long, short)]
+  output: Option<std::path::PathBuf>,
+  /// How manifests at http(s) urls are downloaded.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format the bill of materials is printed in when written to stdout: indented, or on a
+  /// single line with `json`.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `archive` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct ArchiveArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// The destination the manifest was executed into; defaults to the working directory.
+  #[clap(long, short)]
+  destination: Option<std::path::PathBuf>,
+  /// Where the archive is written. Its extension chooses the compression: `.tar`, `.tar.gz` (by
+  /// `gzip`) or `.tar.zst` (by `zstd`).
+  #[clap(long, short)]
+  output: std::path::PathBuf,
+  /// Leaves the `.git` directory of every project out of the archive, keeping only the files at
+  /// the checked out revision.
+  #[clap(long)]
+  exclude_git: bool,
+  /// How manifests at http(s) urls are downloaded.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format results are printed in; `json` prints one object per line.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `prefetch` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct PrefetchArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// The directory holding a bare repository per remote; see the `reference` option of `execute`.
+  #[clap(long)]
+  reference: std::path::PathBuf,
+  /// The number of threads to spawn for fetching.
+  #[clap(long, default_value = "3")]
+  threads: usize,
+  /// When provided, at most this many remotes are fetched from the same host at once; see
+  /// `execute`.
+  #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+  host_limit: Option<u32>,
+  /// A token offered as the password to https remotes that ask for credentials; see `execute`.
+  #[clap(long)]
+  token: Option<String>,
+  /// How remotes are reached.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format progress and results are printed in; `json` prints one event per line.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `bundle create` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct BundleCreateArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// The destination the manifest was executed into; defaults to the working directory.
+  #[clap(long, short)]
+  destination: Option<std::path::PathBuf>,
+  /// The directory the bundles and the pinned manifest are written to.
+  #[clap(long, short)]
+  output: std::path::PathBuf,
+  /// How manifests at http(s) urls are downloaded.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format results are printed in; `json` prints one object per line.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `bundle apply` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct BundleApplyArgs {
+  /// The directory written by `bundle create`.
+  #[clap(long, short)]
+  input: std::path::PathBuf,
+  /// The destination the checkout is recreated in; defaults to the working directory.
+  #[clap(long, short)]
+  destination: Option<std::path::PathBuf>,
+  /// The number of threads to spawn for checking out.
+  #[clap(long, default_value = "3")]
+  threads: usize,
+  /// The format progress and results are printed in; `json` prints one event per line.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// The options of the `prune` subcommand.
+#[derive(clap::Args, Clone, Debug)]
+struct PruneArgs {
+  /// The location (filesystem path) of our xml manifest file, or `-` to read it from stdin.
+  #[clap(long, short)]
+  manifest: String,
+  /// The destination the manifest was executed into; defaults to the working directory.
+  #[clap(long, short)]
+  destination: Option<std::path::PathBuf>,
+  /// When true, the checkouts that would be removed are only listed.
+  #[clap(long, default_value = "false")]
+  dry_run: bool,
+  /// When true, checkouts holding uncommitted changes or unpushed commits are removed too.
+  #[clap(long, default_value = "false")]
+  force: bool,
+  /// When provided, every checkout removed (and every directory left empty by doing so) is
+  /// appended to this file as a json record; see `execute`.
+  #[clap(long)]
+  audit_log: Option<std::path::PathBuf>,
+  /// How manifests at http(s) urls are downloaded.
+  #[clap(flatten)]
+  transport: TransportArgs,
+  /// The format progress and results are printed in; `json` prints one event per line.
+  #[clap(long, value_enum, default_value = "text")]
+  format: OutputFormat,
+}
+
+/// How the sources of a manifest are handed to the worker pool.
+enum Sources {
+  /// The whole manifest was parsed before any work began.
+  Parsed(repors::Manifest),
+  /// Sources are dispatched as they are parsed.
+  Streamed(Box<dyn Iterator<Item = io::Result<repors::Source>>>),
+}
+
+impl Sources {
+  /// Yields every source, regardless of how they are being read.
+  fn into_iter(self) -> Box<dyn Iterator<Item = io::Result<repors::Source>>> {
+    match self {
+      Sources::Parsed(manifest) => Box::new(manifest.sources.into_iter().map(Ok)),
+      Sources::Streamed(reader) => reader,
+    }
+  }
+
+  /// Keeps only the sources that `selected` accepts; errors are always kept.
+  fn filter<F>(self, selected: F) -> Self
+  where
+    F: Fn(&repors::Source) -> bool + 'static,
+  {
+    match self {
+      Sources::Parsed(mut manifest) => {
+        manifest.sources.retain(|source| selected(source));
+        Sources::Parsed(manifest)
+      }
+      Sources::Streamed(reader) => Sources::Streamed(Box::new(
+        reader.filter(move |source| source.as_ref().map_or(true, &selected)),
+      )),
+    }
+  }
+}
+
+/// Keeps only the sources named by one of `projects`, or with a path matching one of the `paths`
+/// globs. When the whole manifest has been parsed, every project named must exist.
+fn select_projects(
+  sources: Sources,
+  projects: Vec<String>,
+  paths: Vec<String>,
+  format: OutputFormat,
+) -> io::Result<Sources> {
+  if let Sources::Parsed(manifest) = &sources {
+    let unknown = projects
+      .iter()
+      .find(|name| !manifest.sources.iter().any(|source| source.name == **name));
+    if let Some(name) = unknown {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("the manifest has no project named '{name}'"),
+      ));
+    }
+  }
+
+  let pathspec = git2::Pathspec::new(paths.iter()).map_err(|error| {
+    io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!("invalid path glob - {error}"),
+    )
+  })?;
+  let selected = sources.filter(move |source| {
+    let named = projects.contains(&source.name);
+    let matched = !paths.is_empty()
+      && pathspec.matches_path(
+        std::path::Path::new(&source.destination),
+        git2::PathspecFlags::DEFAULT,
+      );
+    named || matched
+  });
+
+  if let Sources::Parsed(manifest) = &selected {
+    say(
+      format,
+      format_args!(
+        "{} source(s) selected by project and path",
+        manifest.sources.len()
+      ),
+    );
+  }
+  Ok(selected)
+}
+
+/// Opens the manifest at `path` (or stdin, for `-`) for buffered, incremental reading. Includes are
+/// resolved relative to the manifest's directory, or the working directory for stdin. Manifests at
+/// http(s) urls are downloaded as `transport` says, once their host is found to be allowed.
+fn open_manifest(
+  path: &str,
+  transport: &TransportArgs,
+) -> io::Result<repors::ManifestReader<Box<dyn io::BufRead>>> {
+  if path == "-" {
+    let reader: Box<dyn io::BufRead> = Box::new(io::stdin().lock());
+    return Ok(repors::ManifestReader::new(reader).with_include_dir(std::env::current_dir()?));
+  }
+
+  if path.starts_with("http://") || path.starts_with("https://") {
+    transport.check_manifest_url(path)?;
+    let limit = repors::ManifestLimits::default().max_bytes;
+    let body = repors::download(path, limit, &transport.tls_settings()).map_err(|error| {
+      let url = repors::redact(path);
+      io::Error::new(
+        error.kind(),
+        format!("manifest '{url}' could not be downloaded - {error}"),
+      )
+    })?;
+    let reader: Box<dyn io::BufRead> = Box::new(io::Cursor::new(body));
+    return Ok(repors::ManifestReader::new(reader).with_origin(path.to_string()));
+  }
+
+  let unreadable = |error: io::Error| {
+    io::Error::new(
+      error.kind(),
+      format!("manifest file '{path}' could not be read - {error:?}"),
+    )
+  };
+  let file = std::fs::File::open(path).map_err(unreadable)?;
+  let reader: Box<dyn io::BufRead> = Box::new(io::BufReader::new(file));
+  repors::ManifestReader::new(reader)
+    .with_path(path)
+    .map_err(unreadable)
+}
+
+/// Resolves the revision of every source against the references advertised by its remote, printing
+/// the result. Fails if any revision could not be resolved.
+fn dry_run(sources: Sources, refs: &repors::RefCache, format: OutputFormat) -> io::Result<()> {
+  let mut unresolved = 0;
+
+  for source in sources.into_iter() {
+    let source = source?;
+    let origin = repors::redact(source.origin());
+
+    let resolved = refs.resolve(source.origin(), &source.revision);
+    let result = match &resolved {
+      Ok(Some(oid)) => Ok(*oid),
+      Ok(None) => Err("not advertised by the remote".to_string()),
+      Err(error) => Err(error.to_string()),
+    };
+    unresolved += usize::from(result.is_err());
+
+    match (format, result) {
+      (OutputFormat::Text, Ok(oid)) => {
+        println!("{}: {origin} @ {} ({oid})", source.destination, source.revision)
+      }
+      (OutputFormat::Text, Err(error)) => {
+        println!("{}: {origin} @ {} ({error})", source.destination, source.revision)
+      }
+      (OutputFormat::Json, result) => {
+        let event = serde_json::json!({
+          "path": source.destination,
+          "remote": origin,
+          "revision": source.revision,
+          "commit": result.as_ref().ok().map(ToString::to_string),
+          "error": result.err(),
+        });
+        println!("{event}");
+      }
+    }
+  }
+
+  match unresolved {
+    0 => Ok(()),
+    count => Err(io::Error::other(format!(
+      "{count} revision(s) could not be resolved"
+    ))),
+  }
 }
 
 /// The `repors` command line tool is meant to be somewhat of a replacement to the `repo` command
@@ -39,74 +970,2233 @@ struct CommandLine {
   /// The subcommand.
   #[clap(subcommand)]
   subcommand: Subcommand,
+  /// The format of log output written to stderr (log levels are controlled by `RUST_LOG`).
+  #[clap(long, value_enum, default_value = "text", global = true)]
+  log_format: LogFormat,
+}
+
+/// The token cancelled by our interrupt handler; see `cancel_on_interrupt`.
+static INTERRUPT: std::sync::OnceLock<repors::CancellationToken> = std::sync::OnceLock::new();
+
+/// Called on SIGINT (ctrl-c): cancels the execution and restores the default handler, so that a
+/// second interrupt terminates us immediately.
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+  if let Some(token) = INTERRUPT.get() {
+    token.cancel();
+  }
+
+  // SAFETY: `signal` is async-signal-safe.
+  unsafe {
+    libc::signal(libc::SIGINT, libc::SIG_DFL);
+  }
 }
 
-fn main() -> io::Result<()> {
-  let _ = env_logger::try_init();
+/// Returns a token that is cancelled when we are interrupted (ctrl-c), so that an interrupted
+/// execution cleans up its staging directory rather than leaving it behind.
+fn cancel_on_interrupt() -> repors::CancellationToken {
+  let token = INTERRUPT.get_or_init(repors::CancellationToken::default).clone();
+
+  // SAFETY: our handler only does async-signal-safe work: an atomic store, and `signal` itself.
+  unsafe {
+    libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+  }
+
+  token
+}
+
+fn main() -> std::process::ExitCode {
   let cli = CommandLine::parse();
+  init_logging(cli.log_format);
 
-  match cli.subcommand {
-    Subcommand::Execute {
-      threads,
-      manifest: manifest_path,
-      destination,
-      overwrite,
-    } => {
-      log::debug!("attempting to do repo stuff against manifest '{manifest_path}'");
-      let bytes = std::fs::read(&manifest_path).map_err(|error| {
-        io::Error::new(
-          error.kind(),
-          format!("manifest file '{manifest_path}' could not be read - {error:?}"),
+  let format = cli.subcommand.format();
+  match run(cli.subcommand) {
+    Ok(()) => std::process::ExitCode::SUCCESS,
+    Err(error) => {
+      if let OutputFormat::Json = format {
+        let event = serde_json::json!({ "event": "error", "message": repors::error_chain(&error) });
+        println!("{event}");
+      }
+      eprintln!("error: {}", repors::error_chain(&error));
+      std::process::ExitCode::FAILURE
+    }
+  }
+}
+
+/// Prints a progress message of a subcommand: as is, or as a `message` event when printing json so
+/// that stdout stays one json object per line.
+fn say(format: OutputFormat, message: std::fmt::Arguments<'_>) {
+  match format {
+    OutputFormat::Text => println!("{message}"),
+    OutputFormat::Json => println!(
+      "{}",
+      serde_json::json!({ "event": "message", "message": message.to_string() })
+    ),
+  }
+}
+
+impl Subcommand {
+  /// The format the subcommand prints in.
+  fn format(&self) -> OutputFormat {
+    match self {
+      Self::Execute(args) | Self::Sync(args) => args.format,
+      Self::Validate(args) => args.format,
+      Self::List(args) => match args.format {
+        ListFormat::Table => OutputFormat::Text,
+        ListFormat::Json => OutputFormat::Json,
+      },
+      Self::Status(args) => args.format,
+      Self::Changelog(args) => args.format,
+      Self::Mirror(args) => args.format,
+      Self::Init(args) => args.format,
+      Self::Verify(args) => args.format,
+      Self::Prune(args) => args.format,
+      Self::Sbom(args) => args.format,
+      Self::Archive(args) => args.format,
+      Self::Bundle(BundleAction::Create(args)) => args.format,
+      Self::Bundle(BundleAction::Apply(args)) => args.format,
+      Self::Prefetch(args) => args.format,
+    }
+  }
+}
+
+/// Executes the subcommand requested on the command line.
+fn run(subcommand: Subcommand) -> io::Result<()> {
+  match subcommand {
+    Subcommand::Execute(args) => execute(args, false),
+    Subcommand::Sync(args) => execute(args, true),
+    Subcommand::Validate(args) => validate(args),
+    Subcommand::List(args) => list(args),
+    Subcommand::Status(args) => status(args),
+    Subcommand::Changelog(args) => changelog(args),
+    Subcommand::Mirror(args) => mirror(args),
+    Subcommand::Init(args) => init(args),
+    Subcommand::Sbom(args) => write_sbom(args),
+    Subcommand::Archive(args) => write_archive(args),
+    Subcommand::Bundle(BundleAction::Create(args)) => create_bundles(args),
+    Subcommand::Bundle(BundleAction::Apply(args)) => apply_bundles(args),
+    Subcommand::Prefetch(args) => prefetch(args),
+    Subcommand::Verify(args) => {
+      let manifest = repors::Manifest::try_from(open_manifest(&args.manifest, &args.transport)?)?;
+      let destination = match args.destination {
+        Some(destination) => destination,
+        None => std::env::current_dir()?,
+      };
+      print_verification(&repors::verify(&destination, &manifest.sources), args.format)
+    }
+    Subcommand::Prune(args) => {
+      let manifest = repors::Manifest::try_from(open_manifest(&args.manifest, &args.transport)?)?;
+      let destination = match args.destination {
+        Some(destination) => destination,
+        None => std::env::current_dir()?,
+      };
+      let removal = Removal {
+        dry: args.dry_run,
+        force: args.force,
+        format: args.format,
+      };
+      let audit = open_audit_log(args.audit_log.as_deref())?;
+      prune(&destination, &manifest.sources, removal, &audit)
+    }
+  }
+}
+
+/// Opens the audit log at `path`, if any; without one nothing is recorded.
+fn open_audit_log(path: Option<&std::path::Path>) -> io::Result<repors::AuditLog> {
+  let Some(path) = path else {
+    return Ok(repors::AuditLog::default());
+  };
+  repors::AuditLog::open(path).map_err(|error| {
+    io::Error::new(
+      error.kind(),
+      format!("unable to open audit log '{}' - {error}", path.display()),
+    )
+  })
+}
+
+/// Runs the `sbom` subcommand, describing the checkout of every project of the manifest.
+fn write_sbom(args: SbomArgs) -> io::Result<()> {
+  let manifest = repors::Manifest::try_from(open_manifest(&args.manifest, &args.transport)?)?;
+  let destination = match args.destination {
+    Some(destination) => destination,
+    None => std::env::current_dir()?,
+  };
+
+  let statuses = repors::status(&destination, &manifest.sources);
+  let mut components = Vec::with_capacity(statuses.len());
+  let mut missing = Vec::new();
+  for (source, status) in manifest.sources.iter().zip(&statuses) {
+    match status.head {
+      Some(commit) => components.push(sbom::Component {
+        name: source.name.clone(),
+        origin: repors::redact(source.origin()).into_owned(),
+        revision: source.revision.clone(),
+        path: source.destination.clone(),
+        commit,
+      }),
+      None => missing.push(source.destination.as_str()),
+    }
+  }
+  if !missing.is_empty() {
+    return Err(io::Error::new(
+      io::ErrorKind::NotFound,
+      format!(
+        "{} project(s) have no checkout to describe - {}",
+        missing.len(),
+        missing.join(", ")
+      ),
+    ));
+  }
+
+  let document = sbom::document(
+    args.standard,
+    &args.manifest,
+    &components,
+    std::time::SystemTime::now(),
+  );
+  let text = match (&args.output, args.format) {
+    (None, OutputFormat::Json) => document.to_string(),
+    _ => serde_json::to_string_pretty(&document).map_err(io::Error::other)?,
+  };
+  match args.output {
+    Some(path) => std::fs::write(path, format!("{text}\n")),
+    None => {
+      println!("{text}");
+      Ok(())
+    }
+  }
+}
+
+/// Runs the `archive` subcommand. Entries are given the time named by `SOURCE_DATE_EPOCH`, or the
+/// epoch itself when it is not set.
+fn write_archive(args: ArchiveArgs) -> io::Result<()> {
+  let manifest = repors::Manifest::try_from(open_manifest(&args.manifest, &args.transport)?)?;
+  let destination = match args.destination {
+    Some(destination) => destination,
+    None => std::env::current_dir()?,
+  };
+  archive::Compression::from_path(&args.output)?;
+
+  let missing = manifest
+    .sources
+    .iter()
+    .filter(|source| !destination.join(source.relative_path()).is_dir())
+    .map(|source| source.destination.as_str())
+    .collect::<Vec<_>>();
+  if !missing.is_empty() {
+    return Err(io::Error::new(
+      io::ErrorKind::NotFound,
+      format!(
+        "{} project(s) have no checkout to archive - {}",
+        missing.len(),
+        missing.join(", ")
+      ),
+    ));
+  }
+
+  let mtime = match std::env::var("SOURCE_DATE_EPOCH") {
+    Ok(value) => value.trim().parse().map_err(|_| {
+      io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("SOURCE_DATE_EPOCH '{value}' is not a number of seconds"),
+      )
+    })?,
+    Err(_) => 0,
+  };
+  let paths = archive::paths(&destination, &manifest.sources, args.exclude_git)?;
+  archive::write(&destination, &paths, &args.output, mtime)?;
+  match args.format {
+    OutputFormat::Text => println!(
+      "archived {} entries of {} project(s) to '{}'",
+      paths.len(),
+      manifest.sources.len(),
+      args.output.display()
+    ),
+    OutputFormat::Json => {
+      let event = serde_json::json!({
+        "output": args.output.display().to_string(),
+        "projects": manifest.sources.len(),
+        "entries": paths.len(),
+      });
+      println!("{event}");
+    }
+  }
+  Ok(())
+}
+
+/// Runs the `bundle create` subcommand, bundling the checkout of every project of the manifest.
+fn create_bundles(args: BundleCreateArgs) -> io::Result<()> {
+  let mut manifest = repors::Manifest::try_from(open_manifest(&args.manifest, &args.transport)?)?;
+  let destination = match args.destination {
+    Some(destination) => destination,
+    None => std::env::current_dir()?,
+  };
+
+  let statuses = repors::status(&destination, &manifest.sources);
+  let missing = manifest
+    .sources
+    .iter()
+    .zip(&statuses)
+    .filter(|(_, status)| status.head.is_none())
+    .map(|(source, _)| source.destination.as_str())
+    .collect::<Vec<_>>();
+  if !missing.is_empty() {
+    return Err(io::Error::new(
+      io::ErrorKind::NotFound,
+      format!(
+        "{} project(s) have no checkout to bundle - {}",
+        missing.len(),
+        missing.join(", ")
+      ),
+    ));
+  }
+
+  std::fs::create_dir_all(&args.output)?;
+  for (source, status) in manifest.sources.iter_mut().zip(&statuses) {
+    let Some(commit) = status.head else {
+      continue;
+    };
+    let repo = git2::Repository::open(destination.join(source.relative_path()))
+      .map_err(|error| io::Error::other(format!("{} - {}", source.destination, error.message())))?;
+    let path = bundle::path(&args.output, source);
+    bundle::create(&repo, commit, &path)?;
+    match args.format {
+      OutputFormat::Text => println!(
+        "{}: bundled {commit} into '{}'",
+        source.destination,
+        path.display()
+      ),
+      OutputFormat::Json => {
+        let event = serde_json::json!({
+          "path": source.destination,
+          "commit": commit.to_string(),
+          "bundle": path.display().to_string(),
+        });
+        println!("{event}");
+      }
+    }
+    source.revision = commit.to_string();
+  }
+
+  let file = std::fs::File::create(args.output.join(bundle::MANIFEST))?;
+  manifest.to_writer(io::BufWriter::new(file))?;
+  say(
+    args.format,
+    format_args!(
+      "bundled {} project(s) into '{}'",
+      manifest.sources.len(),
+      args.output.display()
+    ),
+  );
+  Ok(())
+}
+
+/// Runs the `bundle apply` subcommand. Bundles are unpacked into repositories of a scratch
+/// directory, one per remote url, which the manifest's remotes are mapped to for the execution;
+/// the checkouts are then pointed back at the original urls.
+fn apply_bundles(args: BundleApplyArgs) -> io::Result<()> {
+  let destination = match args.destination {
+    Some(destination) => destination,
+    None => std::env::current_dir()?,
+  };
+  let manifest_path = args.input.join(bundle::MANIFEST);
+  let pinned = repors::Manifest::from_path(&manifest_path)?;
+
+  let scratch = std::env::temp_dir().join(format!("repors-bundles-{}", uuid::Uuid::new_v4()));
+  let mut remotes = repors::RemoteMap::default();
+  let mut repositories = std::collections::HashMap::new();
+  for (index, source) in pinned.sources.iter().enumerate() {
+    let count = repositories.len();
+    let repository = repositories
+      .entry(source.origin().to_string())
+      .or_insert_with(|| scratch.join(format!("{count}.git")))
+      .clone();
+    let tip = bundle::unbundle(
+      &bundle::path(&args.input, source),
+      &repository,
+      &format!("bundle-{index}"),
+    )?;
+    if tip.to_string() != source.revision {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+          "the bundle of '{}' holds {tip}, but the manifest pins {}",
+          source.destination, source.revision
+        ),
+      ));
+    }
+  }
+  for (origin, repository) in &repositories {
+    remotes = remotes.with_rule(origin.clone(), repository.display().to_string());
+  }
+
+  let reader = repors::ManifestReader::open(&manifest_path)?.with_remote_map(remotes);
+  let manifest = repors::Manifest::try_from(reader)?;
+  let options = repors::PoolOptions::new(destination.clone()).with_threads(args.threads);
+  let mut pool = repors::WorkerPool::from_options(options)?
+    .with_incremental(true)
+    .with_cancellation(cancel_on_interrupt());
+  pool = match args.format {
+    OutputFormat::Text => pool.with_observer(console::ConsoleObserver {
+      destination: destination.display().to_string(),
+      manifest: manifest_path.display().to_string(),
+      show_staging: false,
+    }),
+    OutputFormat::Json => pool.with_observer(events::EventStream::stdout()),
+  };
+  if matches!(args.format, OutputFormat::Text) && io::IsTerminal::is_terminal(&io::stdout()) {
+    pool = pool.with_observer(progress::ProgressBars::default());
+  }
+
+  let result = pool.execute(manifest);
+  let _ = std::fs::remove_dir_all(&scratch);
+  let report = result?;
+
+  for source in &pinned.sources {
+    let repo = git2::Repository::open(destination.join(source.relative_path()))
+      .and_then(|repo| repo.remote_set_url("origin", source.origin()));
+    if let Err(error) = repo {
+      log::warn!(
+        "unable to point '{}' at its remote - {}",
+        source.destination,
+        error.message()
+      );
+    }
+  }
+  let checkouts = report.sources.iter().map(|source| source.destination.as_str());
+  if let Err(error) = repors::record_projects(&destination, checkouts) {
+    log::warn!("unable to record the projects checked out - {error}");
+  }
+  Ok(())
+}
+
+/// Prints the mismatches of `verification` in `format`, failing if there are any.
+fn print_verification(verification: &repors::Verification, format: OutputFormat) -> io::Result<()> {
+  for failure in &verification.failures {
+    let mismatches = failure.mismatches.iter().map(ToString::to_string);
+    match format {
+      OutputFormat::Text => println!(
+        "{}: {}",
+        failure.destination,
+        mismatches.collect::<Vec<_>>().join(", ")
+      ),
+      OutputFormat::Json => {
+        let event = serde_json::json!({
+          "path": failure.destination,
+          "mismatches": mismatches.collect::<Vec<_>>(),
+        });
+        println!("{event}");
+      }
+    }
+  }
+
+  match verification.failures.len() {
+    0 => {
+      log::info!("{} checkout(s) verified", verification.checked);
+      Ok(())
+    }
+    count => Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!(
+        "{count} of {} checkout(s) failed verification",
+        verification.checked
+      ),
+    )),
+  }
+}
+
+/// How checkouts are removed by `prune`.
+#[derive(Clone, Copy)]
+struct Removal {
+  /// When true, checkouts are only listed.
+  dry: bool,
+  /// When true, checkouts are removed even if that loses local work.
+  force: bool,
+  /// The format what is (or would be) done is printed in.
+  format: OutputFormat,
+}
+
+/// Removes (or lists) the checkouts under `root` that `sources` no longer lists. Checkouts holding
+/// local work are kept, unless forced.
+fn prune(
+  root: &std::path::Path,
+  sources: &[repors::Source],
+  removal: Removal,
+  audit: &repors::AuditLog,
+) -> io::Result<()> {
+  let report = |orphan: &str, action: &str, reason: Option<&str>| match removal.format {
+    OutputFormat::Text => match reason {
+      Some(reason) => println!("{action} '{orphan}', which {reason} (provide --force to remove it)"),
+      None => println!("{action} '{orphan}'"),
+    },
+    OutputFormat::Json => {
+      let event = serde_json::json!({ "path": orphan, "action": action, "reason": reason });
+      println!("{event}");
+    }
+  };
+
+  let mut orphans = repors::orphans(root, sources)?;
+  if !removal.force {
+    orphans.retain(|orphan| match repors::unsaved_work(&root.join(orphan)) {
+      Some(work) => {
+        report(orphan, "keeping", Some(&work));
+        false
+      }
+      None => true,
+    });
+  }
+  if orphans.is_empty() {
+    say(removal.format, format_args!("no checkouts to prune"));
+    return Ok(());
+  }
+
+  let action = if removal.dry { "would remove" } else { "removing" };
+  for orphan in &orphans {
+    report(orphan, action, None);
+  }
+  if !removal.dry {
+    repors::prune(root, &orphans, audit)?;
+  }
+  Ok(())
+}
+
+/// Fails when any checkout under `root` that was recorded there, or that `sources` places there,
+/// holds local work.
+fn refuse_unsaved_work(
+  root: &std::path::Path,
+  sources: Option<&[repors::Source]>,
+  format: OutputFormat,
+) -> io::Result<()> {
+  let mut paths = repors::recorded_projects(root)?;
+  paths.extend(
+    sources
+      .unwrap_or_default()
+      .iter()
+      .map(|source| source.destination.clone()),
+  );
+  paths.sort();
+  paths.dedup();
+
+  let unsaved = paths
+    .iter()
+    .filter_map(|path| repors::unsaved_work(&root.join(path)).map(|work| (path, work)))
+    .inspect(|(path, work)| match format {
+      OutputFormat::Text => println!("'{path}' {work}"),
+      OutputFormat::Json => println!("{}", serde_json::json!({ "path": path, "local_work": work })),
+    })
+    .count();
+  match unsaved {
+    0 => Ok(()),
+    count => Err(io::Error::other(format!(
+      "{count} checkout(s) hold local work; provide --force to discard it"
+    ))),
+  }
+}
+
+/// Determines where `destination` is moved when it is backed up into `dir` at `time`, refusing a `dir`
+/// within the destination, since it would be moved along with it.
+fn backup_target(
+  destination: &std::path::Path,
+  dir: &std::path::Path,
+  time: std::time::SystemTime,
+) -> io::Result<std::path::PathBuf> {
+  let destination = destination.canonicalize()?;
+  // The backup directory may not exist yet, so its closest existing ancestor is resolved instead.
+  let absolute = std::path::absolute(dir)?;
+  let resolved = absolute
+    .ancestors()
+    .find_map(|ancestor| {
+      let rest = absolute.strip_prefix(ancestor).ok()?;
+      ancestor.canonicalize().ok().map(|ancestor| ancestor.join(rest))
+    })
+    .unwrap_or(absolute);
+  if resolved.starts_with(&destination) {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidInput,
+      format!(
+        "the backup directory '{}' is within '{}', which is being replaced",
+        dir.display(),
+        destination.display()
+      ),
+    ));
+  }
+
+  let name = destination
+    .file_name()
+    .map(|name| name.to_string_lossy().into_owned())
+    .unwrap_or_else(|| "destination".to_string());
+  Ok(dir.join(format!("{name}-{}", utc_stamp(time))))
+}
+
+/// Formats `time` as a compact UTC timestamp (`20240102T030405Z`), for naming backups.
+fn utc_stamp(time: std::time::SystemTime) -> String {
+  let seconds = time
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|elapsed| elapsed.as_secs())
+    .unwrap_or_default();
+  let (days, seconds) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+  // Converts days since the epoch to a civil date; see http://howardhinnant.github.io/date_algorithms.html
+  let shifted = days + 719_468;
+  let era = shifted.div_euclid(146_097);
+  let day_of_era = shifted.rem_euclid(146_097);
+  let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 {
+    month_index + 3
+  } else {
+    month_index - 9
+  };
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+  format!(
+    "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+    seconds / 3_600,
+    seconds % 3_600 / 60,
+    seconds % 60
+  )
+}
+
+/// Runs the `init` subcommand, cloning the manifest repository into the destination and recording
+/// what later syncs should use.
+fn init(args: InitArgs) -> io::Result<()> {
+  let root = match args.destination {
+    Some(destination) => destination,
+    None => std::env::current_dir()?,
+  };
+  let credentials = match args.token {
+    Some(token) => repors::Credentials::default().with_token(token),
+    None => repors::Credentials::default(),
+  };
+  args.transport.check_manifest_url(&args.manifest_url)?;
+
+  std::fs::create_dir_all(&root)?;
+  let config = workspace::Config {
+    manifest_url: args.manifest_url,
+    manifest_branch: args.manifest_branch.unwrap_or_default(),
+    manifest_name: args.manifest_name,
+    groups: args.groups,
+  };
+  let config = workspace::init(
+    &root,
+    config,
+    &credentials,
+    &args.transport.proxies()?,
+    &args.transport.tls()?,
+  )?;
+
+  match args.format {
+    OutputFormat::Text => println!(
+      "'{}' initialized with '{}' from '{}' ({}), run `repors sync` to check out its projects",
+      root.display(),
+      config.manifest_name,
+      repors::redact(&config.manifest_url),
+      config.manifest_branch
+    ),
+    OutputFormat::Json => {
+      let event = serde_json::json!({
+        "destination": root.display().to_string(),
+        "manifest_url": repors::redact(&config.manifest_url),
+        "manifest_branch": config.manifest_branch,
+        "manifest_name": config.manifest_name,
+        "groups": config.groups,
+      });
+      println!("{event}");
+    }
+  }
+  Ok(())
+}
+
+/// Runs the `mirror` subcommand, fetching every project of the manifest into a bare repository
+/// named after it. Projects checked out at several paths are mirrored once.
+fn mirror(args: MirrorArgs) -> io::Result<()> {
+  let reader =
+    open_manifest(&args.manifest, &args.transport)?.with_remote_policy(args.transport.remote_policy()?);
+  let mut manifest = repors::Manifest::try_from(reader)?;
+
+  let mut origins = std::collections::HashMap::new();
+  let mut mirrored = Vec::with_capacity(manifest.sources.len());
+  for mut source in std::mem::take(&mut manifest.sources) {
+    if source.name.split('/').any(|part| part == "..") {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("project name '{}' leaves the mirror directory", source.name),
+      ));
+    }
+
+    match origins.insert(source.name.clone(), source.origin().to_string()) {
+      Some(origin) if origin == source.origin() => continue,
+      Some(_) => {
+        return Err(io::Error::new(
+          io::ErrorKind::InvalidData,
+          format!("project '{}' is fetched from more than one remote", source.name),
+        ))
+      }
+      None => (),
+    }
+
+    source.destination = format!("{}.git", source.name.trim_matches('/'));
+    mirrored.push(source);
+  }
+  manifest.sources = mirrored;
+
+  let credentials = match args.token {
+    Some(token) => repors::Credentials::default().with_token(token),
+    None => repors::Credentials::default(),
+  };
+  let options = repors::PoolOptions::new(args.destination.clone()).with_threads(args.threads);
+  let mut pool = repors::WorkerPool::from_options(options)?
+    .with_bare_mirrors(true)
+    .with_incremental(true)
+    .with_host_limit(args.host_limit.map(|limit| limit as usize))
+    .with_credentials(credentials)
+    .with_proxies(args.transport.proxies()?)
+    .with_tls(args.transport.tls()?)
+    .with_cancellation(cancel_on_interrupt());
+  pool = match args.format {
+    OutputFormat::Text => pool.with_observer(console::ConsoleObserver {
+      destination: args.destination.display().to_string(),
+      manifest: args.manifest.clone(),
+      show_staging: false,
+    }),
+    OutputFormat::Json => pool.with_observer(events::EventStream::stdout()),
+  };
+  if matches!(args.format, OutputFormat::Text) && io::IsTerminal::is_terminal(&io::stdout()) {
+    pool = pool.with_observer(progress::ProgressBars::default());
+  }
+
+  pool.execute(manifest)?;
+  Ok(())
+}
+
+/// Runs the `prefetch` subcommand, fetching the remote of every project into the reference
+/// directory.
+fn prefetch(args: PrefetchArgs) -> io::Result<()> {
+  let reader =
+    open_manifest(&args.manifest, &args.transport)?.with_remote_policy(args.transport.remote_policy()?);
+  let manifest = repors::Manifest::try_from(reader)?;
+
+  let credentials = match args.token {
+    Some(token) => repors::Credentials::default().with_token(token),
+    None => repors::Credentials::default(),
+  };
+  let options = repors::PoolOptions::new(args.reference.clone()).with_threads(args.threads);
+  let mut pool = repors::WorkerPool::from_options(options)?
+    .with_reference_dir(args.reference.clone())
+    .with_host_limit(args.host_limit.map(|limit| limit as usize))
+    .with_credentials(credentials)
+    .with_proxies(args.transport.proxies()?)
+    .with_tls(args.transport.tls()?)
+    .with_cancellation(cancel_on_interrupt());
+  match args.format {
+    OutputFormat::Text if io::IsTerminal::is_terminal(&io::stdout()) => {
+      pool = pool.with_observer(progress::ProgressBars::default());
+    }
+    OutputFormat::Text => (),
+    OutputFormat::Json => pool = pool.with_observer(events::EventStream::stdout()),
+  }
+
+  let remotes = pool.prefetch(manifest)?;
+  say(
+    args.format,
+    format_args!(
+      "prefetched {remotes} remote(s) into '{}'",
+      args.reference.display()
+    ),
+  );
+  Ok(())
+}
+
+/// Runs the `changelog` subcommand, printing the commits made to every project that moved between
+/// two manifests, grouped by project.
+fn changelog(args: ChangelogArgs) -> io::Result<()> {
+  let old = repors::Manifest::try_from(open_manifest(&args.from, &args.transport)?)?;
+  let new = repors::Manifest::try_from(open_manifest(&args.to, &args.transport)?)?;
+  let destination = match args.destination {
+    Some(destination) => destination,
+    None => std::env::current_dir()?,
+  };
+  let short = |revision: &Option<String>| {
+    let revision = revision.as_deref().unwrap_or_default();
+    revision.get(..12).unwrap_or(revision).to_string()
+  };
+
+  let changes = repors::changelog(&destination, &old.sources, &new.sources);
+  for project in &changes {
+    if let OutputFormat::Json = args.format {
+      let (change, commits, problem) = match &project.change {
+        repors::ProjectChange::Added => ("added", Vec::new(), None),
+        repors::ProjectChange::Removed => ("removed", Vec::new(), None),
+        repors::ProjectChange::Updated(commits) => ("updated", commits.clone(), None),
+        repors::ProjectChange::Unknown(problem) => ("unknown", Vec::new(), Some(problem)),
+        _ => ("unknown", Vec::new(), None),
+      };
+      let commits = commits
+        .iter()
+        .map(|commit| {
+          serde_json::json!({
+            "id": commit.id.to_string(),
+            "summary": commit.summary,
+            "author": commit.author,
+            "time": commit.time,
+          })
+        })
+        .collect::<Vec<_>>();
+      let event = serde_json::json!({
+        "path": project.destination,
+        "from": project.old_revision,
+        "to": project.new_revision,
+        "change": change,
+        "commits": commits,
+        "problem": problem,
+      });
+      println!("{event}");
+      continue;
+    }
+
+    let (from, to) = (short(&project.old_revision), short(&project.new_revision));
+    match &project.change {
+      repors::ProjectChange::Added => println!("{}: added at {to}", project.destination),
+      repors::ProjectChange::Removed => println!("{}: removed (was at {from})", project.destination),
+      repors::ProjectChange::Updated(commits) => {
+        println!(
+          "{}: {from}..{to}, {} commit(s)",
+          project.destination,
+          commits.len()
+        );
+        for commit in commits {
+          let id = commit.id.to_string();
+          println!("  {} {} ({})", &id[..12], commit.summary, commit.author);
+        }
+      }
+      repors::ProjectChange::Unknown(problem) => {
+        println!(
+          "{}: {from}..{to}, unable to read commits - {problem}",
+          project.destination
         )
+      }
+      _ => println!("{}: {from}..{to}", project.destination),
+    }
+  }
+
+  let unknown = changes
+    .iter()
+    .filter(|project| matches!(project.change, repors::ProjectChange::Unknown(_)))
+    .count();
+  match unknown {
+    0 => Ok(()),
+    count => Err(io::Error::other(format!(
+      "the commits of {count} project(s) could not be read"
+    ))),
+  }
+}
+
+/// Runs the `status` subcommand, printing how every checkout compares to the manifest and failing
+/// if any is not up to date.
+fn status(args: StatusArgs) -> io::Result<()> {
+  let manifest = repors::Manifest::try_from(open_manifest(&args.manifest, &args.transport)?)?;
+  let destination = match args.destination {
+    Some(destination) => destination,
+    None => std::env::current_dir()?,
+  };
+  let short = |oid: Option<git2::Oid>| oid.map(|oid| oid.to_string()[..12].to_string());
+
+  let statuses = repors::status(&destination, &manifest.sources);
+  for project in &statuses {
+    match args.format {
+      OutputFormat::Json => {
+        let event = serde_json::json!({
+          "path": project.destination,
+          "revision": project.revision,
+          "present": project.present,
+          "head": project.head.map(|oid| oid.to_string()),
+          "expected": project.expected.map(|oid| oid.to_string()),
+          "dirty": project.dirty,
+          "up_to_date": project.up_to_date(),
+        });
+        println!("{event}");
+      }
+      OutputFormat::Text if !project.present => println!("{}: missing", project.destination),
+      OutputFormat::Text => {
+        let mut notes = Vec::new();
+        match (short(project.head), short(project.expected)) {
+          (head, expected) if head.is_some() && head == expected => (),
+          (head, None) => notes.push(format!(
+            "at {}, '{}' is not in the checkout",
+            head.as_deref().unwrap_or("no commit"),
+            project.revision
+          )),
+          (head, Some(expected)) => notes.push(format!(
+            "at {}, manifest wants {expected} ({})",
+            head.as_deref().unwrap_or("no commit"),
+            project.revision
+          )),
+        }
+        if project.dirty {
+          notes.push("has local changes".to_string());
+        }
+
+        if notes.is_empty() {
+          println!("{}: up to date", project.destination);
+        } else {
+          println!("{}: {}", project.destination, notes.join(", "));
+        }
+      }
+    }
+  }
+
+  match statuses.iter().filter(|project| !project.up_to_date()).count() {
+    0 => Ok(()),
+    count => Err(io::Error::other(format!(
+      "{count} of {} project(s) differ from the manifest",
+      statuses.len()
+    ))),
+  }
+}
+
+/// Runs the `list` subcommand, printing the name, path, remote url, revision and groups of every
+/// project.
+fn list(args: ListArgs) -> io::Result<()> {
+  let manifest = repors::Manifest::try_from(open_manifest(&args.manifest, &args.transport)?)?;
+  let mut stdout = io::stdout().lock();
+  // Scripts piping us into `head` and the like are done with us; that is not our failure.
+  let written = |result: io::Result<()>| match result {
+    Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+    other => other,
+  };
+
+  if let ListFormat::Json = args.format {
+    let projects = manifest
+      .sources
+      .iter()
+      .map(|source| {
+        serde_json::json!({
+          "name": source.name,
+          "path": source.destination,
+          "remote": repors::redact(source.origin()),
+          "revision": source.revision,
+          "groups": source.groups,
+        })
+      })
+      .collect::<Vec<_>>();
+    return written(writeln!(stdout, "{}", serde_json::Value::Array(projects)));
+  }
+
+  let rows = manifest
+    .sources
+    .iter()
+    .map(|source| {
+      [
+        source.name.clone(),
+        source.destination.clone(),
+        repors::redact(source.origin()).into_owned(),
+        source.revision.clone(),
+        source.groups.join(","),
+      ]
+    })
+    .collect::<Vec<_>>();
+
+  let header = ["NAME", "PATH", "REMOTE", "REVISION", "GROUPS"].map(str::to_string);
+  let mut widths = header.clone().map(|column| column.len());
+  for row in &rows {
+    for (width, column) in widths.iter_mut().zip(row) {
+      *width = (*width).max(column.len());
+    }
+  }
+
+  written(std::iter::once(&header).chain(&rows).try_for_each(|row| {
+    let line = row
+      .iter()
+      .zip(widths)
+      .map(|(column, width)| format!("{column:<width$}"))
+      .collect::<Vec<_>>()
+      .join("  ");
+    writeln!(stdout, "{}", line.trim_end())
+  }))
+}
+
+/// Runs the `validate` subcommand, printing every diagnostic and failing if any is an error.
+fn validate(args: ValidateArgs) -> io::Result<()> {
+  let diagnostics = repors::validate(open_manifest(&args.manifest, &args.transport)?);
+
+  for diagnostic in &diagnostics {
+    match args.format {
+      OutputFormat::Text => println!("{diagnostic}"),
+      OutputFormat::Json => {
+        let event = serde_json::json!({
+          "severity": diagnostic.severity.to_string(),
+          "project": diagnostic.project,
+          "message": diagnostic.message,
+        });
+        println!("{event}");
+      }
+    }
+  }
+
+  let errors = diagnostics
+    .iter()
+    .filter(|diagnostic| diagnostic.severity == repors::Severity::Error)
+    .count();
+  match errors {
+    0 => {
+      log::info!("manifest '{}' is valid", args.manifest);
+      Ok(())
+    }
+    count => Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("manifest '{}' has {count} error(s)", args.manifest),
+    )),
+  }
+}
+
+/// Runs the `execute` subcommand or, when `incremental` is true, the `sync` subcommand.
+fn execute(args: ExecuteArgs, incremental: bool) -> io::Result<()> {
+  let started = std::time::Instant::now();
+  let ExecuteArgs {
+    threads,
+    manifest: manifest_path,
+    destination,
+    overwrite,
+    backup,
+    stall_warning,
+    stall_abort,
+    timeout_per_project,
+    deadline,
+    event_stream,
+    audit_log,
+    keep_temp,
+    chrome_trace,
+    report_junit,
+    cache_dir,
+    auto_threads,
+    pipeline,
+    dry_run: dry,
+    refs_ttl,
+    no_auto_gc,
+    compression,
+    checkout_threads,
+    mut groups,
+    exclude_groups,
+    projects,
+    paths,
+    depth,
+    host_limit,
+    fetch_submodules,
+    token,
+    transport,
+    map_remotes,
+    remote_map,
+    quiet,
+    snapshot_out,
+    reference,
+    temp_dir,
+    prune: prune_orphans,
+    verify,
+    bare,
+    no_git,
+    force,
+    format,
+  } = args;
+
+  if prune_orphans && !incremental {
+    return Err(io::Error::other(
+      "--prune can only be used with sync, since execute starts from an empty destination",
+    ));
+  }
+
+  if no_git && incremental {
+    return Err(io::Error::other(
+      "--no-git can only be used with execute, since sync updates checkouts through their .git",
+    ));
+  }
+
+  let credentials = match token {
+    Some(token) => repors::Credentials::default().with_token(token),
+    None => repors::Credentials::default(),
+  };
+  let proxies = transport.proxies()?;
+  let tls = transport.tls()?;
+
+  // Manifests followed since `init` came from a repository that relative fetch urls refer to.
+  let (manifest_path, manifest_origin) = match manifest_path {
+    Some(path) => (path, None),
+    None if incremental => {
+      let root = match &destination {
+        Some(destination) => std::path::PathBuf::from(destination),
+        None => std::env::current_dir()?,
+      };
+      let config = workspace::Config::load(&root)?.ok_or_else(|| {
+        io::Error::other(format!(
+          "'{}' has not been initialized, provide --manifest or run `repors init` first",
+          root.display()
+        ))
       })?;
-      let cursor = std::io::Cursor::new(&bytes);
-      let manifest = repors::Manifest::from_reader(cursor)
-        .map_err(|error| io::Error::new(error.kind(), format!("failed parsing manifest - {error:?}")))?;
-      log::debug!("manifest loaded - '{manifest:?}'");
+      if groups.is_empty() {
+        groups = config.groups.clone();
+      }
+      say(
+        format,
+        format_args!(
+          "updating manifest repository (branch '{}')",
+          config.manifest_branch
+        ),
+      );
+      transport.check_manifest_url(&config.manifest_url)?;
+      let path = workspace::update(&root, &config, &credentials, &proxies, &tls)?;
+      (path.display().to_string(), Some(config.manifest_url))
+    }
+    None => return Err(io::Error::other("--manifest is required for execute")),
+  };
+
+  let audit = open_audit_log(audit_log.as_deref())?;
 
-      println!(
+  let mut remotes = match remote_map {
+    Some(path) => repors::RemoteMap::from_path(&path).map_err(|error| {
+      io::Error::new(
+        error.kind(),
+        format!("unable to read remote map '{}' - {error}", path.display()),
+      )
+    })?,
+    None => repors::RemoteMap::default(),
+  };
+  for rule in &map_remotes {
+    remotes = remotes.with_rule_str(rule)?;
+  }
+
+  log::debug!("attempting to do repo stuff against manifest '{manifest_path}'");
+  let mut reader = open_manifest(&manifest_path, &transport)?
+    .with_remote_map(remotes)
+    .with_remote_policy(transport.remote_policy()?);
+  if let Some(origin) = manifest_origin {
+    reader = reader.with_origin(origin);
+  }
+  let mut sources = if pipeline {
+    say(format, format_args!("streaming manifest, preparing destination"));
+    Sources::Streamed(Box::new(reader))
+  } else {
+    let manifest = repors::Manifest::try_from(reader).map_err(|error| {
+      let error = io::Error::from(error);
+      io::Error::new(error.kind(), format!("failed parsing manifest - {error}"))
+    })?;
+    log::debug!("manifest loaded - '{}'", repors::redact(&format!("{manifest:?}")));
+
+    say(
+      format,
+      format_args!(
         "successfully loaded manifest with {} source(s), preparing destination",
         manifest.sources.len()
+      ),
+    );
+    Sources::Parsed(manifest)
+  };
+
+  if !groups.is_empty() || !exclude_groups.is_empty() {
+    sources = sources.filter(move |source| {
+      (groups.is_empty() || source.in_any_group(&groups)) && !source.in_any_group(&exclude_groups)
+    });
+    if let Sources::Parsed(manifest) = &sources {
+      say(
+        format,
+        format_args!("{} source(s) selected by group", manifest.sources.len()),
+      );
+    }
+  }
+
+  if !projects.is_empty() || !paths.is_empty() {
+    sources = select_projects(sources, projects, paths, format)?;
+  }
+
+  if dry {
+    let refs_ttl = std::time::Duration::from_secs(refs_ttl);
+    let refs_path = cache_dir.as_ref().map(|dir| dir.join("refs.json"));
+    let refs = refs_path
+      .as_ref()
+      .map(|path| {
+        repors::RefCache::load(path, refs_ttl).unwrap_or_else(|error| {
+          log::warn!("ignoring unreadable ref cache '{}' - {error}", path.display());
+          repors::RefCache::new(refs_ttl)
+        })
+      })
+      .unwrap_or_else(|| repors::RefCache::new(refs_ttl))
+      .with_credentials(credentials)
+      .with_proxies(proxies)
+      .with_tls(tls);
+
+    let result = dry_run(sources, &refs, format);
+    if let Some(path) = refs_path {
+      if let Err(error) = refs.save(&path) {
+        log::warn!("unable to save ref cache to '{}' - {error}", path.display());
+      }
+    }
+    return result;
+  }
+
+  let destination = destination
+    .or(std::env::current_dir()?.to_str().map(str::to_string))
+    .ok_or_else(|| io::Error::other("unable to determine a destination directory for execution"))?;
+
+  // The timings are kept within the destination by default, so they are read before it is replaced.
+  let history_path = match cache_dir {
+    Some(dir) => dir.join("history.json"),
+    None => workspace::timings(std::path::Path::new(&destination)),
+  };
+  let mut history = repors::History::load(&history_path).unwrap_or_else(|error| {
+    log::warn!(
+      "ignoring unreadable history '{}' - {error}",
+      history_path.display()
+    );
+    repors::History::default()
+  });
+
+  match (overwrite, std::fs::metadata(&destination), backup) {
+    (true, _, _) if incremental => {
+      return Err(io::Error::other(
+        "-x cannot be used with sync, which updates the destination in place",
+      ));
+    }
+    (_, Err(_), _) => (),
+    (false, Ok(_), _) if incremental => say(
+      format,
+      format_args!("'{destination}' already exists, updating in place"),
+    ),
+    (false, Ok(_), _) => {
+      let message = format!("'{destination}' already exists, must provide -x to allow overwrite");
+      return Err(io::Error::other(message));
+    }
+    (true, Ok(_), Some(dir)) => {
+      let target = backup_target(destination.as_ref(), &dir, std::time::SystemTime::now())?;
+      say(
+        format,
+        format_args!(
+          "'{destination}' already exists, moving it to '{}'",
+          target.display()
+        ),
+      );
+      audit.create_dir_all(&dir)?;
+      audit.rename(destination.as_ref(), &target).map_err(|error| {
+        io::Error::new(
+          error.kind(),
+          format!(
+            "failed moving previous '{destination}' to '{}': {error}",
+            target.display()
+          ),
+        )
+      })?;
+    }
+    (true, Ok(_), None) => {
+      if !force {
+        let listed = match &sources {
+          Sources::Parsed(manifest) => Some(manifest.sources.as_slice()),
+          Sources::Streamed(_) => None,
+        };
+        refuse_unsaved_work(destination.as_ref(), listed, format)?;
+      }
+      say(format, format_args!("'{destination}' already exists, removing"));
+      audit.remove_dir_all(destination.as_ref()).map_err(|error| {
+        io::Error::new(
+          error.kind(),
+          format!("failed removing previous '{destination}': {error:?}"),
+        )
+      })?;
+    }
+  }
+
+  audit.create_dir_all(destination.as_ref())?;
+
+  let destination_path = std::path::PathBuf::from(&destination);
+
+  if let Sources::Parsed(manifest) = &mut sources {
+    history.schedule(&mut manifest.sources);
+  }
+
+  let threads = match (auto_threads, &sources) {
+    (Some(ceiling), Sources::Parsed(manifest)) => match history.parallelism(&manifest.sources, ceiling) {
+      Some(chosen) => {
+        log::info!("using {chosen} thread(s) based on previous runs");
+        chosen
+      }
+      None => {
+        log::info!("not every source has been seen before, using {threads} thread(s)");
+        threads
+      }
+    },
+    _ => threads,
+  };
+
+  // Checkouts updated in place only fetch what changed, which is not worth estimating.
+  let expected = match &sources {
+    Sources::Parsed(manifest) => manifest
+      .sources
+      .iter()
+      .map(|source| {
+        let marker = if bare { "HEAD" } else { ".git" };
+        let updated = incremental
+          && destination_path
+            .join(source.relative_path())
+            .join(marker)
+            .exists();
+        let expected = if updated {
+          Some(std::time::Duration::ZERO)
+        } else {
+          history
+            .observation(source.origin())
+            .map(|observation| observation.duration)
+        };
+        expected.map(|expected| (source.destination.clone(), expected))
+      })
+      .collect::<Option<std::collections::HashMap<_, _>>>(),
+    Sources::Streamed(_) => None,
+  };
+  if let (Some(expected), Sources::Parsed(manifest)) = (&expected, &sources) {
+    let cloned = manifest
+      .sources
+      .iter()
+      .filter(|source| !expected[&source.destination].is_zero())
+      .cloned()
+      .collect::<Vec<_>>();
+    if let Some(estimate) = history
+      .estimate(&cloned, threads)
+      .filter(|estimate| estimate.as_secs() > 0)
+    {
+      say(
+        format,
+        format_args!(
+          "expecting this to take about {} based on previous runs",
+          progress::duration(estimate)
+        ),
       );
+    }
+  }
+
+  say(
+    format,
+    format_args!("destination '{destination}' ready, creating worker pool..."),
+  );
+  let stall_policy = repors::StallPolicy::new(std::time::Duration::from_secs(stall_warning))
+    .with_abort_after(stall_abort.map(std::time::Duration::from_secs));
+  let mut options = repors::PoolOptions::new(destination_path.clone()).with_threads(threads);
+  if let Some(dir) = temp_dir {
+    options = options.with_temp_dir(dir);
+  }
+  let mut pool = repors::WorkerPool::from_options(options)?
+    .with_stall_policy(stall_policy)
+    .with_storage_policy(
+      repors::StoragePolicy::default()
+        .with_auto_gc(!no_auto_gc)
+        .with_compression(compression),
+    )
+    .with_audit_log(audit.clone())
+    .with_keep_temp(keep_temp)
+    .with_checkout_threads(checkout_threads)
+    .with_incremental(incremental)
+    .with_discard_local_work(force)
+    .with_clone_depth(depth)
+    .with_project_timeout(timeout_per_project.map(std::time::Duration::from_secs))
+    .with_deadline(deadline.map(|seconds| started + std::time::Duration::from_secs(seconds)))
+    .with_host_limit(host_limit.map(|limit| limit as usize))
+    .with_submodules(fetch_submodules)
+    .with_bare_mirrors(bare)
+    .with_strip_git(no_git)
+    .with_credentials(credentials)
+    .with_proxies(proxies)
+    .with_tls(tls)
+    .with_cancellation(cancel_on_interrupt());
+  pool = match format {
+    OutputFormat::Text => pool.with_observer(console::ConsoleObserver {
+      destination: destination.clone(),
+      manifest: manifest_path.clone(),
+      show_staging: keep_temp,
+    }),
+    OutputFormat::Json => pool.with_observer(events::EventStream::stdout()),
+  };
+
+  if let Some(target) = event_stream {
+    let stream = events::EventStream::open(&target).map_err(|error| {
+      io::Error::new(
+        error.kind(),
+        format!("unable to open event stream '{target}' - {error}"),
+      )
+    })?;
+    pool = pool.with_observer(stream);
+  }
+
+  if let Some(dir) = reference {
+    pool = pool.with_reference_dir(dir);
+  }
+
+  if !quiet && matches!(format, OutputFormat::Text) && io::IsTerminal::is_terminal(&io::stdout()) {
+    let bars = match expected {
+      Some(expected) => progress::ProgressBars::default().with_estimates(expected, threads),
+      None => progress::ProgressBars::default(),
+    };
+    pool = pool.with_observer(bars);
+  }
+
+  let tracer = chrome_trace.as_ref().map(|_| trace::TraceObserver::default());
+  if let Some(tracer) = tracer.as_ref() {
+    pool = pool.with_observer(tracer.clone());
+  }
+
+  let junit = report_junit.as_ref().map(|_| junit::JunitObserver::default());
+  if let Some(junit) = junit.as_ref() {
+    pool = pool.with_observer(junit.clone());
+  }
+
+  let snapshot = match (&snapshot_out, &sources) {
+    (Some(_), Sources::Parsed(manifest)) => Some(manifest.clone()),
+    _ => None,
+  };
+
+  let kept = match (prune_orphans, &sources) {
+    (true, Sources::Parsed(manifest)) => Some(manifest.sources.clone()),
+    _ => None,
+  };
+
+  let result = match sources {
+    Sources::Parsed(manifest) => pool.execute(manifest),
+    Sources::Streamed(reader) => pool.execute_streaming(reader),
+  };
+
+  if let Some((path, tracer)) = chrome_trace.zip(tracer) {
+    if let Err(error) = tracer.trace.write(&path) {
+      log::error!("unable to write chrome trace to '{}' - {error}", path.display());
+    }
+  }
+
+  if let Some((path, junit)) = report_junit.zip(junit) {
+    if let Err(error) = junit.write(&path, &manifest_path) {
+      log::error!("unable to write junit report to '{}' - {error}", path.display());
+    }
+  }
+
+  let report = result?;
+  let checkouts = report.sources.iter().map(|source| source.destination.as_str());
+  if let Err(error) = repors::record_projects(&destination_path, checkouts) {
+    log::warn!("unable to record the projects checked out - {error}");
+  }
+  if verify {
+    print_verification(&repors::verify_execution(&report), format)?;
+  }
+  if let Some(sources) = kept {
+    let removal = Removal {
+      dry: false,
+      force,
+      format,
+    };
+    prune(&destination_path, &sources, removal, &audit)?;
+  }
+
+  if let Some((path, manifest)) = snapshot_out.zip(snapshot) {
+    let written = std::fs::File::create(&path)
+      .and_then(|file| manifest.pinned(&report).to_writer(io::BufWriter::new(file)));
+    written.map_err(|error| {
+      io::Error::new(
+        error.kind(),
+        format!("unable to write snapshot to '{}' - {error}", path.display()),
+      )
+    })?;
+    say(
+      format,
+      format_args!("pinned manifest written to '{}'", path.display()),
+    );
+  }
+
+  history.record(&report);
+  if let Err(error) = history.save(&history_path) {
+    log::warn!("unable to save history to '{}' - {error}", history_path.display());
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io;
+
+  /// Creates a unique, empty directory for a test to work in.
+  fn scratch_dir(label: &str) -> std::path::PathBuf {
+    let mut path = std::env::temp_dir();
+    path.push(format!("repors-cli-test-{label}-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&path).expect("unable to create scratch dir");
+    path
+  }
+
+  /// Parses a manifest held in a string.
+  fn manifest(xml: &str) -> repors::Manifest {
+    repors::Manifest::from_reader(io::Cursor::new(xml.to_string())).expect("invalid test manifest")
+  }
+
+  /// An entry of a tar archive, as read back by `read_tar`.
+  #[derive(Debug)]
+  struct TarEntry {
+    /// The name of the entry, from its pax header when it has one.
+    name: String,
+    /// True when the entry was preceded by a pax header.
+    pax: bool,
+    /// The type flag of the entry.
+    kind: u8,
+    /// The permissions of the entry.
+    mode: u64,
+    /// The owner of the entry.
+    uid: u64,
+    /// The owner of the entry, by name.
+    uname: String,
+    /// The modification time of the entry.
+    mtime: u64,
+    /// The target of a symlink entry.
+    link: String,
+    /// The contents of a file entry.
+    contents: Vec<u8>,
+  }
 
-      let destination = destination
-        .or(std::env::current_dir()?.to_str().map(str::to_string))
-        .ok_or_else(|| {
-          io::Error::new(
-            io::ErrorKind::Other,
-            "unable to determine a destination directory for execution",
-          )
-        })?;
-
-      match (overwrite, std::fs::metadata(&destination)) {
-        (_, Err(_)) => (),
-        (false, Ok(_)) => {
-          let message = format!("'{destination}' already exists, must provide -x to allow overwrite");
-          return Err(std::io::Error::new(std::io::ErrorKind::Other, message));
+  /// Reads back the entries of the (uncompressed) tar archive `bytes`, checking the checksum of
+  /// every header along the way.
+  fn read_tar(bytes: &[u8]) -> Vec<TarEntry> {
+    let text = |field: &[u8]| {
+      let end = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+      String::from_utf8_lossy(&field[..end]).into_owned()
+    };
+    let octal = |field: &[u8]| {
+      let value = text(field);
+      u64::from_str_radix(value.trim(), 8).expect("invalid octal field")
+    };
+
+    let mut entries = Vec::new();
+    let mut pax = std::collections::HashMap::new();
+    let mut offset = 0;
+    loop {
+      let header = &bytes[offset..offset + 512];
+      if header.iter().all(|byte| *byte == 0) {
+        break;
+      }
+      let checksum = header
+        .iter()
+        .enumerate()
+        .map(|(index, byte)| match index {
+          148..156 => u64::from(b' '),
+          _ => u64::from(*byte),
+        })
+        .sum::<u64>();
+      assert_eq!(octal(&header[148..155]), checksum, "bad checksum at {offset}");
+
+      let size = octal(&header[124..136]) as usize;
+      let contents = bytes[offset + 512..offset + 512 + size].to_vec();
+      offset += 512 + size.div_ceil(512) * 512;
+
+      if header[156] == b'x' {
+        let mut records = contents.as_slice();
+        while !records.is_empty() {
+          let space = records
+            .iter()
+            .position(|byte| *byte == b' ')
+            .expect("bad pax record");
+          let length = std::str::from_utf8(&records[..space])
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+          let record = std::str::from_utf8(&records[space + 1..length - 1]).unwrap();
+          let (key, value) = record.split_once('=').expect("bad pax record");
+          pax.insert(key.to_string(), value.to_string());
+          records = &records[length..];
         }
-        (true, Ok(_)) => {
-          println!("'{destination}' already exists, removing");
-          std::fs::remove_dir_all(&destination).map_err(|error| {
-            io::Error::new(
-              error.kind(),
-              format!("failed removing previous '{destination}': {error:?}"),
-            )
-          })?;
+        continue;
+      }
+
+      entries.push(TarEntry {
+        pax: !pax.is_empty(),
+        name: pax.remove("path").unwrap_or_else(|| text(&header[0..100])),
+        link: pax.remove("linkpath").unwrap_or_else(|| text(&header[157..257])),
+        kind: header[156],
+        mode: octal(&header[100..108]),
+        uid: octal(&header[108..116]),
+        uname: text(&header[265..297]),
+        mtime: octal(&header[136..148]),
+        contents,
+      });
+      pax.clear();
+    }
+
+    assert!(bytes[offset..].iter().all(|byte| *byte == 0));
+    entries
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn archives_are_sorted_normalized_and_reproducible() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = scratch_dir("archive-root");
+    let output = scratch_dir("archive-output");
+    let project = root.join("layers/alpha");
+    let long_name = "n".repeat(120);
+    std::fs::create_dir_all(project.join(".git")).unwrap();
+    std::fs::write(project.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+    std::fs::write(project.join("README"), "alpha").unwrap();
+    std::fs::write(project.join(&long_name), "long").unwrap();
+    std::fs::write(project.join("run.sh"), "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(project.join("run.sh"), std::fs::Permissions::from_mode(0o700)).unwrap();
+    std::os::unix::fs::symlink("README", project.join("link")).unwrap();
+    let sources = manifest(
+      "<manifest><remote name=\"origin\" fetch=\"https://example.com\"/>\
+       <default remote=\"origin\" revision=\"main\"/>\
+       <project name=\"alpha\" path=\"layers/alpha\"/></manifest>",
+    )
+    .sources;
+
+    let paths = super::archive::paths(&root, &sources, false).expect("unable to list paths");
+    let first = output.join("first.tar");
+    super::archive::write(&root, &paths, &first, 1_700_000_000).expect("unable to archive");
+    let bytes = std::fs::read(&first).unwrap();
+    let entries = read_tar(&bytes);
+
+    let names = entries
+      .iter()
+      .map(|entry| entry.name.as_str())
+      .collect::<Vec<_>>();
+    let long_path = format!("layers/alpha/{long_name}");
+    assert_eq!(
+      names,
+      [
+        "layers/",
+        "layers/alpha/",
+        "layers/alpha/.git/",
+        "layers/alpha/.git/HEAD",
+        "layers/alpha/README",
+        "layers/alpha/link",
+        long_path.as_str(),
+        "layers/alpha/run.sh",
+      ]
+    );
+    for entry in &entries {
+      assert_eq!((entry.uid, entry.uname.as_str()), (0, "root"), "{}", entry.name);
+      assert_eq!(entry.mtime, 1_700_000_000, "{}", entry.name);
+      assert_eq!(entry.pax, entry.name == long_path, "{}", entry.name);
+    }
+    let entry = |name: &str| entries.iter().find(|entry| entry.name == name).unwrap();
+    assert_eq!(
+      (entry("layers/alpha/").kind, entry("layers/alpha/").mode),
+      (b'5', 0o755)
+    );
+    assert_eq!(
+      (
+        entry("layers/alpha/README").kind,
+        entry("layers/alpha/README").mode
+      ),
+      (b'0', 0o644)
+    );
+    assert_eq!(entry("layers/alpha/README").contents, b"alpha");
+    assert_eq!(entry("layers/alpha/run.sh").mode, 0o755);
+    assert_eq!(entry(&long_path).contents, b"long");
+    let link = entry("layers/alpha/link");
+    assert_eq!(
+      (link.kind, link.link.as_str(), link.mode),
+      (b'2', "README", 0o777)
+    );
+
+    // Nothing about when (or by whom) the files were written ends up in the archive.
+    let touched = std::fs::File::options()
+      .write(true)
+      .open(project.join("README"))
+      .unwrap();
+    touched
+      .set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(42))
+      .unwrap();
+    let second = output.join("second.tar");
+    super::archive::write(&root, &paths, &second, 1_700_000_000).expect("unable to archive");
+    assert_eq!(std::fs::read(&second).unwrap(), bytes);
+
+    let without_git = super::archive::paths(&root, &sources, true).expect("unable to list paths");
+    assert!(without_git
+      .iter()
+      .all(|path| !path.ends_with(".git") && !path.starts_with("layers/alpha/.git")));
+    assert_eq!(without_git.len(), paths.len() - 2);
+
+    let _ = std::fs::remove_dir_all(root);
+    let _ = std::fs::remove_dir_all(output);
+  }
+
+  #[test]
+  fn archive_compression_follows_the_extension() {
+    use super::archive::Compression;
+
+    let cases = [
+      ("layers.tar", Some(Compression::None)),
+      ("layers.tar.gz", Some(Compression::Gzip)),
+      ("layers.TGZ", Some(Compression::Gzip)),
+      ("layers.tar.zst", Some(Compression::Zstd)),
+      ("layers.zip", None),
+    ];
+    for (name, expected) in cases {
+      assert_eq!(
+        Compression::from_path(std::path::Path::new(name)).ok(),
+        expected,
+        "{name}"
+      );
+    }
+  }
+
+  /// The time `seconds` after the epoch.
+  fn at(seconds: u64) -> std::time::SystemTime {
+    std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds)
+  }
+
+  #[test]
+  fn utc_stamps_handle_leap_days_and_centuries() {
+    assert_eq!(super::utc_stamp(at(0)), "19700101T000000Z");
+    assert_eq!(super::utc_stamp(at(951_782_400)), "20000229T000000Z");
+    assert_eq!(super::utc_stamp(at(951_868_799)), "20000229T235959Z");
+    // 2100 is not a leap year, so March follows the 28th of February.
+    assert_eq!(super::utc_stamp(at(4_107_542_399)), "21000228T235959Z");
+    assert_eq!(super::utc_stamp(at(4_107_542_400 + 3_723)), "21000301T010203Z");
+  }
+
+  #[test]
+  fn backups_within_the_destination_are_refused() {
+    let scratch = scratch_dir("backup");
+    let destination = scratch.join("build");
+    std::fs::create_dir_all(destination.join("layers")).unwrap();
+
+    for dir in [
+      destination.join("backups"),
+      destination.join("layers/../old"),
+      destination.clone(),
+    ] {
+      let error = super::backup_target(&destination, &dir, at(0)).expect_err("backup should be refused");
+      assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{}", dir.display());
+    }
+
+    let target = super::backup_target(&destination, &scratch.join("backups"), at(0)).expect("backup refused");
+    assert_eq!(target, scratch.join("backups/build-19700101T000000Z"));
+
+    let _ = std::fs::remove_dir_all(scratch);
+  }
+
+  /// Commits `contents` as `file` on top of HEAD of the repository at `path`, creating it if needed.
+  fn commit(path: &std::path::Path, file: &str, contents: &str) -> git2::Oid {
+    let repo = git2::Repository::open(path)
+      .or_else(|_| git2::Repository::init(path))
+      .expect("unable to open upstream");
+    std::fs::write(path.join(file), contents).expect("unable to write upstream file");
+    let mut index = repo.index().expect("unable to open index");
+    index
+      .add_path(std::path::Path::new(file))
+      .expect("unable to stage file");
+    index.write().expect("unable to write index");
+    let tree = repo
+      .find_tree(index.write_tree().expect("unable to write tree"))
+      .expect("unable to find tree");
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let signature = git2::Signature::now("repors", "repors@example.com").expect("bad signature");
+    repo
+      .commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        file,
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+      )
+      .expect("unable to commit")
+  }
+
+  #[test]
+  fn bundles_recreate_a_checkout_without_its_remote() {
+    let scratch = scratch_dir("bundle");
+    let upstreams = scratch.join("upstreams");
+    commit(&upstreams.join("alpha"), "README", "alpha");
+    let alpha = commit(&upstreams.join("alpha"), "NOTES", "more");
+    let beta = commit(&upstreams.join("beta"), "README", "beta");
+    let manifest_path = scratch.join("manifest.xml");
+    let xml = format!(
+      "<manifest><remote name=\"local\" fetch=\"file://{}\"/><default remote=\"local\"/>\
+       <project name=\"alpha\" path=\"layers/alpha\" revision=\"{alpha}\"/>\
+       <project name=\"beta\" path=\"layers/beta\" revision=\"{beta}\"/></manifest>",
+      upstreams.display()
+    );
+    std::fs::write(&manifest_path, xml).unwrap();
+
+    let synced = scratch.join("synced");
+    let options = repors::PoolOptions::new(synced.clone()).with_threads(1);
+    repors::WorkerPool::from_options(options)
+      .and_then(|pool| pool.execute(manifest(&std::fs::read_to_string(&manifest_path).unwrap())))
+      .expect("unable to sync");
+
+    let bundles = scratch.join("bundles");
+    super::create_bundles(super::BundleCreateArgs {
+      manifest: manifest_path.display().to_string(),
+      destination: Some(synced.clone()),
+      output: bundles.clone(),
+      transport: super::TransportArgs::default(),
+      format: super::OutputFormat::Json,
+    })
+    .expect("unable to create bundles");
+    assert!(bundles.join("layers/alpha.bundle").is_file());
+    assert!(bundles.join(super::bundle::MANIFEST).is_file());
+
+    // The bundles alone must be enough to recreate the checkout.
+    std::fs::remove_dir_all(&upstreams).unwrap();
+    let applied = scratch.join("applied");
+    super::apply_bundles(super::BundleApplyArgs {
+      input: bundles,
+      destination: Some(applied.clone()),
+      threads: 1,
+      format: super::OutputFormat::Json,
+    })
+    .expect("unable to apply bundles");
+
+    for (path, expected) in [("layers/alpha", alpha), ("layers/beta", beta)] {
+      let head = |root: &std::path::Path| {
+        let repo = git2::Repository::open(root.join(path)).expect("missing checkout");
+        let head = repo
+          .head()
+          .expect("missing head")
+          .peel_to_commit()
+          .expect("bad head")
+          .id();
+        head
+      };
+      assert_eq!(head(&synced), expected, "{path}");
+      assert_eq!(head(&applied), expected, "{path}");
+    }
+    let remote = git2::Repository::open(applied.join("layers/alpha"))
+      .and_then(|repo| Ok(repo.find_remote("origin")?.url().map(str::to_string)))
+      .expect("missing remote");
+    assert_eq!(remote, Some(format!("file://{}/alpha", upstreams.display())));
+    assert!(applied.join("layers/alpha/NOTES").is_file());
+
+    let _ = std::fs::remove_dir_all(scratch);
+  }
+
+  #[test]
+  fn json_log_records_carry_their_fields() {
+    let fields = [("source", "layers/alpha"), ("phase", "clone"), ("attempt", "2")];
+    let record = log::Record::builder()
+      .level(log::Level::Warn)
+      .target("repors::execution")
+      .args(format_args!("fetch stalled"))
+      .key_values(&fields)
+      .build();
+    let event = super::json_record(&record, "2024-01-02T03:04:05Z".to_string());
+    assert_eq!(
+      event,
+      serde_json::json!({
+        "timestamp": "2024-01-02T03:04:05Z",
+        "level": "WARN",
+        "target": "repors::execution",
+        "source": "layers/alpha",
+        "phase": "clone",
+        "message": "fetch stalled",
+        "attempt": "2",
+      })
+    );
+
+    // Records logged outside of any source still have (empty) source and phase fields.
+    let record = log::Record::builder().args(format_args!("starting")).build();
+    let event = super::json_record(&record, String::new());
+    assert_eq!(event["source"], serde_json::Value::Null);
+    assert_eq!(event["phase"], serde_json::Value::Null);
+    assert_eq!(event["level"], "INFO");
+  }
+
+  /// Executes a manifest listing each of `projects` (at `layers/<name>`) into a scratch directory,
+  /// notifying `observer`. Only `alpha` has an upstream; any other project fails to clone. Returns
+  /// the scratch directory, the commit of `alpha` and the outcome of the execution.
+  fn execute_with<O>(
+    label: &str,
+    projects: &[&str],
+    observer: O,
+  ) -> (
+    std::path::PathBuf,
+    git2::Oid,
+    Result<repors::ExecutionReport, repors::Error>,
+  )
+  where
+    O: repors::ProgressObserver + 'static,
+  {
+    let scratch = scratch_dir(label);
+    let upstreams = scratch.join("upstreams");
+    let alpha = commit(&upstreams.join("alpha"), "README", "alpha");
+    let mut xml = format!(
+      "<manifest><remote name=\"local\" fetch=\"file://{}\"/><default remote=\"local\" revision=\"{alpha}\"/>",
+      upstreams.display()
+    );
+    for name in projects {
+      xml.push_str(&format!("<project name=\"{name}\" path=\"layers/{name}\"/>"));
+    }
+    xml.push_str("</manifest>");
+
+    let options = repors::PoolOptions::new(scratch.join("destination")).with_threads(1);
+    let outcome = repors::WorkerPool::from_options(options)
+      .expect("unable to create pool")
+      .with_observer(observer)
+      .execute(manifest(&xml));
+    (scratch, alpha, outcome)
+  }
+
+  #[test]
+  fn event_streams_are_newline_delimited_json() {
+    let scratch = scratch_dir("events");
+    let path = scratch.join("events.ndjson");
+    let stream =
+      super::events::EventStream::open(&path.display().to_string()).expect("unable to open stream");
+    let (destination, alpha, outcome) = execute_with("events-execute", &["alpha"], stream);
+    outcome.expect("execution failed");
+
+    let events = std::fs::read_to_string(&path)
+      .unwrap()
+      .lines()
+      .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("invalid event line"))
+      .collect::<Vec<_>>();
+    for event in &events {
+      assert!(event["timestamp_ms"].is_u64(), "{event}");
+    }
+    let named = |name: &str| {
+      events
+        .iter()
+        .find(|event| event["event"] == name)
+        .unwrap_or_else(|| panic!("no {name} event"))
+    };
+
+    assert_eq!(
+      events.first().map(|event| &event["event"]),
+      Some(&"execution_started".into())
+    );
+    assert_eq!(
+      events.last().map(|event| &event["event"]),
+      Some(&"execution_finished".into())
+    );
+    assert_eq!(named("execution_started")["sources"], 1);
+    for name in ["job_queued", "job_started", "checkout_finished", "job_finished"] {
+      assert_eq!(named(name)["source"], "layers/alpha", "{name}");
+      assert!(
+        named(name)["origin"].as_str().unwrap().ends_with("/alpha"),
+        "{name}"
+      );
+    }
+    assert_eq!(named("checkout_finished")["commit"], alpha.to_string());
+    assert_eq!(named("job_finished")["success"], true);
+    assert!(named("job_finished").get("error").is_none());
+    assert!(named("placement_finished")["destination"]
+      .as_str()
+      .unwrap()
+      .ends_with("layers/alpha"));
+
+    let finished = named("execution_finished");
+    let source = &finished["sources"][0];
+    assert_eq!(source["source"], "layers/alpha");
+    assert_eq!(source["commit"], alpha.to_string());
+    assert_eq!(source["outcome"], "cloned");
+    assert!(source["received_bytes"].is_u64());
+    for phase in ["clone", "resolve", "checkout", "place"] {
+      assert!(source["timings_ms"][phase].is_u64(), "{phase}");
+    }
+    assert_eq!(
+      finished["counts"],
+      serde_json::json!({ "cloned": 1, "updated": 0, "cached": 0, "skipped": 0 })
+    );
+
+    let _ = std::fs::remove_dir_all(scratch);
+    let _ = std::fs::remove_dir_all(destination);
+  }
+
+  #[test]
+  fn chrome_traces_hold_a_slice_per_phase() {
+    let observer = super::trace::TraceObserver::default();
+    let (scratch, _, outcome) = execute_with("trace", &["alpha"], observer.clone());
+    outcome.expect("execution failed");
+    let path = scratch.join("trace.json");
+    observer.trace.write(&path).expect("unable to write trace");
+
+    let document = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&path).unwrap())
+      .expect("invalid trace");
+    assert_eq!(document["displayTimeUnit"], "ms");
+    let events = document["traceEvents"].as_array().expect("missing trace events");
+
+    let tracks = events
+      .iter()
+      .filter(|event| event["ph"] == "M")
+      .map(|event| {
+        assert_eq!(event["name"], "thread_name");
+        assert!(event["args"]["name"].is_string());
+        event["tid"].as_u64().expect("missing track id")
+      })
+      .collect::<Vec<_>>();
+    let slices = events
+      .iter()
+      .filter(|event| event["ph"] == "X")
+      .collect::<Vec<_>>();
+    assert_eq!(slices.len() + tracks.len(), events.len());
+    for slice in &slices {
+      assert_eq!((&slice["cat"], &slice["pid"]), (&"phase".into(), &1.into()));
+      assert!(slice["ts"].is_u64() && slice["dur"].is_u64(), "{slice}");
+      assert!(tracks.contains(&slice["tid"].as_u64().unwrap()), "{slice}");
+    }
+
+    let phases = |source: &str| {
+      slices
+        .iter()
+        .filter(|slice| {
+          slice["args"]["source"]
+            .as_str()
+            .is_some_and(|name| name.ends_with(source))
+        })
+        .map(|slice| slice["name"].as_str().unwrap())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(phases("layers/alpha"), ["clone", "resolve", "checkout", "place"]);
+
+    let _ = std::fs::remove_dir_all(scratch);
+  }
+
+  /// The elements of the xml document `text`, in document order, each with its attributes.
+  fn xml_elements(text: &str) -> Vec<(String, std::collections::HashMap<String, String>)> {
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut elements = Vec::new();
+    loop {
+      match reader.read_event().expect("invalid xml") {
+        quick_xml::events::Event::Start(element) | quick_xml::events::Event::Empty(element) => {
+          let attributes = element
+            .attributes()
+            .map(|attribute| {
+              let attribute = attribute.expect("invalid attribute");
+              let value = attribute.unescape_value().expect("invalid attribute value");
+              (
+                String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                value.into_owned(),
+              )
+            })
+            .collect();
+          elements.push((
+            String::from_utf8_lossy(element.name().as_ref()).into_owned(),
+            attributes,
+          ));
         }
+        quick_xml::events::Event::Eof => break,
+        _ => (),
       }
+    }
+    elements
+  }
+
+  #[test]
+  fn junit_reports_hold_a_case_per_project() {
+    let observer = super::junit::JunitObserver::default();
+    let (scratch, _, outcome) = execute_with("junit", &["alpha", "missing"], observer.clone());
+    assert!(outcome.is_err(), "the missing project should fail");
+    let path = scratch.join("junit.xml");
+    observer
+      .write(&path, "manifest.xml")
+      .expect("unable to write report");
+
+    let elements = xml_elements(&std::fs::read_to_string(&path).unwrap());
+    let names = elements.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+      names,
+      ["testsuites", "testsuite", "testcase", "testcase", "failure"]
+    );
+    for (_, suite) in &elements[..2] {
+      assert_eq!(suite["name"], "manifest.xml");
+      assert_eq!(suite["tests"], "2");
+      assert_eq!(suite["failures"], "1");
+      assert_eq!(suite["errors"], "0");
+      assert_eq!(suite["skipped"], "0");
+      assert!(suite["time"].parse::<f64>().is_ok());
+    }
+
+    let (_, alpha) = &elements[2];
+    assert_eq!(
+      (alpha["classname"].as_str(), alpha["name"].as_str()),
+      ("alpha", "layers/alpha")
+    );
+    assert!(alpha["time"].parse::<f64>().is_ok());
+    let (_, missing) = &elements[3];
+    assert_eq!(
+      (missing["classname"].as_str(), missing["name"].as_str()),
+      ("missing", "layers/missing")
+    );
+    let (_, failure) = &elements[4];
+    assert!(
+      failure["message"].contains("layers/missing"),
+      "{}",
+      failure["message"]
+    );
+
+    let _ = std::fs::remove_dir_all(scratch);
+  }
+
+  /// The projects of a checkout, as described in bills of materials.
+  fn components() -> Vec<super::sbom::Component> {
+    let component = |name: &str, origin: &str, commit: &str| super::sbom::Component {
+      name: name.to_string(),
+      origin: origin.to_string(),
+      revision: "main".to_string(),
+      path: format!("layers/{name}"),
+      commit: git2::Oid::from_str(commit).unwrap(),
+    };
+    vec![
+      component(
+        "alpha",
+        "https://example.com/alpha",
+        "1111111111111111111111111111111111111111",
+      ),
+      component(
+        "beta",
+        "/srv/git/beta",
+        "2222222222222222222222222222222222222222",
+      ),
+    ]
+  }
+
+  #[test]
+  fn cyclonedx_documents_list_every_project() {
+    let created = at(1_704_164_645);
+    let document = super::sbom::document(
+      super::sbom::Standard::Cyclonedx,
+      "default.xml",
+      &components(),
+      created,
+    );
+    let document = serde_json::from_str::<serde_json::Value>(&document.to_string()).expect("invalid sbom");
 
-      std::fs::create_dir_all(&destination)?;
+    assert_eq!(
+      (&document["bomFormat"], &document["specVersion"]),
+      (&"CycloneDX".into(), &"1.5".into())
+    );
+    assert!(document["serialNumber"]
+      .as_str()
+      .unwrap()
+      .starts_with("urn:uuid:"));
+    assert_eq!(document["version"], 1);
+    assert_eq!(document["metadata"]["timestamp"], "2024-01-02T03:04:05Z");
+    assert_eq!(document["metadata"]["tools"]["components"][0]["name"], "repors");
+    assert_eq!(document["metadata"]["component"]["name"], "default.xml");
 
-      let destination_path = std::path::PathBuf::from(&destination);
+    let alpha = &document["components"][0];
+    assert_eq!(document["components"].as_array().map(Vec::len), Some(2));
+    assert_eq!(alpha["type"], "library");
+    assert_eq!(alpha["bom-ref"], "layers/alpha");
+    assert_eq!(alpha["name"], "alpha");
+    assert_eq!(alpha["version"], "1111111111111111111111111111111111111111");
+    assert_eq!(alpha["externalReferences"][0]["url"], "https://example.com/alpha");
+    assert_eq!(alpha["pedigree"]["commits"][0]["uid"], alpha["version"]);
+    assert_eq!(
+      alpha["properties"],
+      serde_json::json!([
+        { "name": "repors:path", "value": "layers/alpha" },
+        { "name": "repors:revision", "value": "main" },
+      ])
+    );
+    assert_eq!(
+      document["dependencies"],
+      serde_json::json!([{ "ref": "manifest", "dependsOn": ["layers/alpha", "layers/beta"] }])
+    );
+  }
+
+  #[test]
+  fn spdx_documents_list_every_project() {
+    let created = at(1_704_164_645);
+    let document = super::sbom::document(super::sbom::Standard::Spdx, "default.xml", &components(), created);
+    let document = serde_json::from_str::<serde_json::Value>(&document.to_string()).expect("invalid sbom");
 
-      println!("destination '{destination}' ready, creating worker pool...");
-      let pool = repors::WorkerPool::create(threads, destination_path.clone())?;
+    assert_eq!(document["spdxVersion"], "SPDX-2.3");
+    assert_eq!(document["dataLicense"], "CC0-1.0");
+    assert_eq!(document["SPDXID"], "SPDXRef-DOCUMENT");
+    assert_eq!(document["name"], "default.xml");
+    assert!(document["documentNamespace"]
+      .as_str()
+      .unwrap()
+      .starts_with("urn:uuid:"));
+    assert_eq!(document["creationInfo"]["created"], "2024-01-02T03:04:05Z");
 
-      println!("populating '{destination}' from '{manifest_path}', please wait...");
-      pool.execute(manifest)?;
-      println!("success!");
+    let packages = document["packages"].as_array().expect("missing packages");
+    assert_eq!(packages.len(), 2);
+    assert_eq!(packages[0]["SPDXID"], "SPDXRef-Project-1");
+    assert_eq!(packages[0]["name"], "alpha");
+    assert_eq!(
+      packages[0]["versionInfo"],
+      "1111111111111111111111111111111111111111"
+    );
+    assert_eq!(
+      packages[0]["downloadLocation"],
+      "git+https://example.com/alpha@1111111111111111111111111111111111111111"
+    );
+    // Local paths are not locations SPDX can name.
+    assert_eq!(packages[1]["downloadLocation"], "NOASSERTION");
+    for package in packages {
+      assert_eq!(package["filesAnalyzed"], false);
+      assert_eq!(package["licenseConcluded"], "NOASSERTION");
     }
+    assert_eq!(
+      document["relationships"][1],
+      serde_json::json!({
+        "spdxElementId": "SPDXRef-DOCUMENT",
+        "relationshipType": "DESCRIBES",
+        "relatedSpdxElement": "SPDXRef-Project-2",
+      })
+    );
   }
 
-  Ok(())
+  #[test]
+  fn downloaded_manifests_are_checked_against_the_remote_policy() {
+    let refusal = |url: &str, transport: &super::TransportArgs| match super::open_manifest(url, transport) {
+      Ok(_) => panic!("'{url}' was downloaded"),
+      Err(error) => error,
+    };
+
+    // Nothing listens on port 1, so the download would fail if it were attempted.
+    let error = refusal("http://127.0.0.1:1/default.xml", &super::TransportArgs::default());
+    assert_eq!(error.kind(), io::ErrorKind::PermissionDenied, "{error}");
+    let transport = super::TransportArgs {
+      allowed_hosts: vec!["git.internal".to_string()],
+      ..Default::default()
+    };
+    let error = refusal("https://127.0.0.1:1/default.xml", &transport);
+    assert_eq!(error.kind(), io::ErrorKind::PermissionDenied, "{error}");
+    assert!(error.to_string().contains("refusing the manifest at"), "{error}");
+
+    let transport = super::TransportArgs {
+      allowed_hosts: vec!["127.0.0.1".to_string()],
+      allow_insecure_remotes: true,
+      ..Default::default()
+    };
+    let error = refusal("http://127.0.0.1:1/default.xml", &transport);
+    assert!(error.to_string().contains("could not be downloaded"), "{error}");
+  }
+
+  #[test]
+  fn relative_fetch_urls_of_downloaded_manifests_are_resolved_against_their_url() {
+    use std::io::{BufRead, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("unable to listen");
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+      let (stream, _) = listener.accept().expect("unable to accept");
+      let mut reader = io::BufReader::new(stream);
+      // The headers of the request end with an empty line.
+      let mut line = String::from("GET");
+      while !line.trim().is_empty() {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+      }
+      let body =
+        "<manifest><remote name=\"origin\" fetch=\".\"/><default remote=\"origin\" revision=\"main\"/>\
+                  <project name=\"alpha\" path=\"alpha\"/></manifest>";
+      let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+      reader.get_mut().write_all(response.as_bytes()).unwrap();
+    });
+
+    let transport = super::TransportArgs {
+      allow_insecure_remotes: true,
+      ..Default::default()
+    };
+    let url = format!("http://127.0.0.1:{port}/manifests/default.xml");
+    let reader = super::open_manifest(&url, &transport).expect("unable to download");
+    let manifest = repors::Manifest::try_from(reader).expect("invalid manifest");
+    server.join().unwrap();
+    assert_eq!(
+      manifest.sources[0].origin(),
+      format!("http://127.0.0.1:{port}/manifests/alpha")
+    );
+  }
 }
@@ -0,0 +1,260 @@
+//! The live progress bars drawn to a terminal while sources are being cloned.
+
+use std::io::Write;
+
+/// How often the bars are redrawn for transfer progress, at most.
+const REDRAW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// The width, in characters, of each bar.
+const BAR_WIDTH: usize = 24;
+
+/// What we know about a source a worker is busy with.
+struct Active {
+  /// The manifest path of the source.
+  destination: String,
+  /// The phase of work the source is in, once known.
+  phase: Option<repors::Phase>,
+  /// The latest transfer statistics of the source, once any have been received.
+  progress: Option<repors::TransferProgress>,
+  /// When the worker started on the source.
+  started: std::time::Instant,
+}
+
+impl Active {
+  /// The line drawn for this source.
+  fn line(&self) -> String {
+    let progress = self.progress.filter(|progress| progress.total_objects > 0);
+
+    match (self.phase, progress) {
+      (None | Some(repors::Phase::Clone), Some(progress)) => {
+        let filled = (progress.received_objects * BAR_WIDTH / progress.total_objects).min(BAR_WIDTH);
+        format!(
+          "  {:<40} [{}{}] {:>3}% {}",
+          self.destination,
+          "#".repeat(filled),
+          "-".repeat(BAR_WIDTH - filled),
+          progress.received_objects * 100 / progress.total_objects,
+          bytes(progress.received_bytes)
+        )
+      }
+      (None | Some(repors::Phase::Clone), None) => {
+        format!("  {:<40} waiting for the remote", self.destination)
+      }
+      (Some(phase), _) => format!("  {:<40} {phase}", self.destination),
+    }
+  }
+}
+
+/// Formats a number of bytes for humans.
+fn bytes(amount: usize) -> String {
+  let units = ["B", "KiB", "MiB", "GiB"];
+  let mut value = amount as f64;
+  let mut unit = 0;
+  while value >= 1024.0 && unit < units.len() - 1 {
+    value /= 1024.0;
+    unit += 1;
+  }
+
+  match unit {
+    0 => format!("{amount} B"),
+    unit => format!("{value:.1} {}", units[unit]),
+  }
+}
+
+/// Formats a duration for humans, to the second.
+pub fn duration(elapsed: std::time::Duration) -> String {
+  let seconds = elapsed.as_secs();
+  match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+    (0, 0, seconds) => format!("{seconds}s"),
+    (0, minutes, seconds) => format!("{minutes}m {seconds}s"),
+    (hours, minutes, _) => format!("{hours}h {minutes}m"),
+  }
+}
+
+/// How long every source is expected to take, used to estimate how long is left.
+struct Estimates {
+  /// The expected duration of each source, by manifest path.
+  expected: std::collections::HashMap<String, std::time::Duration>,
+  /// The number of workers sharing the work.
+  threads: usize,
+  /// The sources queued but not yet started.
+  waiting: std::collections::HashSet<String>,
+}
+
+/// The state of the bars, shared by every worker.
+#[derive(Default)]
+struct Bars {
+  /// How long sources are expected to take, if known.
+  estimates: Option<Estimates>,
+  /// The number of sources queued so far.
+  queued: usize,
+  /// The number of sources that have finished, successfully or not.
+  finished: usize,
+  /// The sources the workers are busy with, in the order they were started.
+  active: Vec<Active>,
+  /// How many lines were drawn last time; they are drawn over next time.
+  drawn: usize,
+  /// When the bars were last drawn.
+  last_drawn: Option<std::time::Instant>,
+}
+
+impl Bars {
+  /// Roughly how long is left: the work expected of the sources not yet finished, shared by every
+  /// worker.
+  fn remaining(&self) -> Option<std::time::Duration> {
+    let estimates = self.estimates.as_ref()?;
+    let waiting = estimates
+      .waiting
+      .iter()
+      .map(|destination| estimates.expected.get(destination).copied())
+      .sum::<Option<std::time::Duration>>()?;
+    let active = self
+      .active
+      .iter()
+      .map(|active| {
+        let expected = estimates.expected.get(&active.destination)?;
+        Some(expected.saturating_sub(active.started.elapsed()))
+      })
+      .sum::<Option<std::time::Duration>>()?;
+    Some((waiting + active) / estimates.threads.max(1) as u32)
+  }
+
+  /// Redraws the bars in place of those drawn last time. Once nothing is active, the bars are
+  /// cleared entirely.
+  fn draw(&mut self) {
+    let mut lines = Vec::with_capacity(self.active.len() + 1);
+    if !self.active.is_empty() {
+      let header = format!("[{}/{}] cloning", self.finished, self.queued);
+      lines.push(match self.remaining() {
+        Some(remaining) => format!("{header}, about {} left", duration(remaining)),
+        None => header,
+      });
+      lines.extend(self.active.iter().map(Active::line));
+    }
+
+    let mut out = String::new();
+    if self.drawn > 0 {
+      out.push_str(&format!("\x1b[{}A", self.drawn));
+    }
+    for line in &lines {
+      out.push_str("\x1b[2K");
+      out.push_str(line);
+      out.push('\n');
+    }
+
+    // Whatever is left of a taller drawing is cleared, and the cursor returned to below ours.
+    let stale = self.drawn.saturating_sub(lines.len());
+    out.push_str(&"\x1b[2K\n".repeat(stale));
+    if stale > 0 {
+      out.push_str(&format!("\x1b[{stale}A"));
+    }
+
+    self.drawn = lines.len();
+    self.last_drawn = Some(std::time::Instant::now());
+
+    let mut stdout = std::io::stdout().lock();
+    if let Err(error) = stdout.write_all(out.as_bytes()).and_then(|()| stdout.flush()) {
+      log::debug!("unable to draw progress - {error}");
+    }
+  }
+}
+
+/// Draws a bar per source being cloned to stdout, showing how much of it has been received. Meant
+/// for terminals only, since the bars are redrawn in place with ANSI escapes.
+#[derive(Default)]
+pub struct ProgressBars(std::sync::Mutex<Bars>);
+
+impl ProgressBars {
+  /// Shows how long is left, given how long each source (by manifest path) is `expected` to take
+  /// on one of `threads` workers.
+  pub fn with_estimates(
+    self,
+    expected: std::collections::HashMap<String, std::time::Duration>,
+    threads: usize,
+  ) -> Self {
+    if let Ok(mut bars) = self.0.lock() {
+      bars.estimates = Some(Estimates {
+        expected,
+        threads,
+        waiting: Default::default(),
+      });
+    }
+    self
+  }
+
+  /// Applies `change` to the bars and redraws them if `force` is set or they are due.
+  fn update<F>(&self, force: bool, change: F)
+  where
+    F: FnOnce(&mut Bars),
+  {
+    let Ok(mut bars) = self.0.lock() else {
+      return;
+    };
+
+    change(&mut bars);
+    let due = bars
+      .last_drawn
+      .is_none_or(|last| last.elapsed() >= REDRAW_INTERVAL);
+    if force || due {
+      bars.draw();
+    }
+  }
+}
+
+impl repors::ProgressObserver for ProgressBars {
+  fn job_queued(&self, source: &repors::Source) {
+    self.update(false, |bars| {
+      bars.queued += 1;
+      if let Some(estimates) = bars.estimates.as_mut() {
+        estimates.waiting.insert(source.destination.clone());
+      }
+    });
+  }
+
+  fn job_started(&self, source: &repors::Source) {
+    self.update(true, |bars| {
+      if let Some(estimates) = bars.estimates.as_mut() {
+        estimates.waiting.remove(&source.destination);
+      }
+      bars.active.push(Active {
+        destination: source.destination.clone(),
+        phase: None,
+        progress: None,
+        started: std::time::Instant::now(),
+      })
+    });
+  }
+
+  fn phase_started(&self, source: &repors::Source, phase: repors::Phase) {
+    self.update(true, |bars| {
+      let active = bars
+        .active
+        .iter_mut()
+        .find(|active| active.destination == source.destination);
+      if let Some(active) = active {
+        active.phase = Some(phase);
+      }
+    });
+  }
+
+  fn job_progress(&self, source: &repors::Source, progress: &repors::TransferProgress) {
+    self.update(false, |bars| {
+      let active = bars
+        .active
+        .iter_mut()
+        .find(|active| active.destination == source.destination);
+      if let Some(active) = active {
+        active.progress = Some(*progress);
+      }
+    });
+  }
+
+  fn job_finished(&self, source: &repors::Source, _result: Result<(), &repors::SourceError>) {
+    self.update(true, |bars| {
+      bars
+        .active
+        .retain(|active| active.destination != source.destination);
+      bars.finished += 1;
+    });
+  }
+}
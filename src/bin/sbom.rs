@@ -0,0 +1,128 @@
+//! Software bills of materials describing an assembled checkout: every project, the url it was
+//! fetched from, the commit checked out and where, in one of the standard json formats auditors
+//! ask for.
+
+/// The standards a bill of materials can be written in.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+pub enum Standard {
+  /// A CycloneDX 1.5 json document.
+  #[default]
+  Cyclonedx,
+  /// An SPDX 2.3 json document.
+  Spdx,
+}
+
+/// A project of the checkout.
+pub struct Component {
+  /// The name of the project in the manifest.
+  pub name: String,
+  /// The url the project was fetched from, credentials removed.
+  pub origin: String,
+  /// The revision of the project in the manifest.
+  pub revision: String,
+  /// The path of the project's checkout, relative to the destination.
+  pub path: String,
+  /// The commit checked out.
+  pub commit: git2::Oid,
+}
+
+/// The bill of materials of `components`, the projects of the manifest named `name`, written in
+/// `standard` as of `created`.
+pub fn document(
+  standard: Standard,
+  name: &str,
+  components: &[Component],
+  created: std::time::SystemTime,
+) -> serde_json::Value {
+  let timestamp = timestamp(created);
+  let serial = uuid::Uuid::new_v4();
+
+  match standard {
+    Standard::Cyclonedx => serde_json::json!({
+      "bomFormat": "CycloneDX",
+      "specVersion": "1.5",
+      "serialNumber": format!("urn:uuid:{serial}"),
+      "version": 1,
+      "metadata": {
+        "timestamp": timestamp,
+        "tools": {
+          "components": [
+            { "type": "application", "name": "repors", "version": env!("CARGO_PKG_VERSION") },
+          ],
+        },
+        "component": { "type": "application", "bom-ref": "manifest", "name": name },
+      },
+      "components": components.iter().map(|component| serde_json::json!({
+        "type": "library",
+        "bom-ref": component.path,
+        "name": component.name,
+        "version": component.commit.to_string(),
+        "externalReferences": [{ "type": "vcs", "url": component.origin }],
+        "pedigree": {
+          "commits": [{ "uid": component.commit.to_string(), "url": component.origin }],
+        },
+        "properties": [
+          { "name": "repors:path", "value": component.path },
+          { "name": "repors:revision", "value": component.revision },
+        ],
+      })).collect::<Vec<_>>(),
+      "dependencies": [{
+        "ref": "manifest",
+        "dependsOn": components.iter().map(|component| &component.path).collect::<Vec<_>>(),
+      }],
+    }),
+    Standard::Spdx => {
+      let id = |index: usize| format!("SPDXRef-Project-{}", index + 1);
+      serde_json::json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": name,
+        "documentNamespace": format!("urn:uuid:{serial}"),
+        "creationInfo": {
+          "created": timestamp,
+          "creators": [format!("Tool: repors-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": components.iter().enumerate().map(|(index, component)| {
+          // SPDX names git locations `git+<url>@<commit>`; local paths are no location at all.
+          let location = if component.origin.contains("://") {
+            format!("git+{}@{}", component.origin, component.commit)
+          } else {
+            "NOASSERTION".to_string()
+          };
+          serde_json::json!({
+            "SPDXID": id(index),
+            "name": component.name,
+            "versionInfo": component.commit.to_string(),
+            "downloadLocation": location,
+            "filesAnalyzed": false,
+            "licenseConcluded": "NOASSERTION",
+            "licenseDeclared": "NOASSERTION",
+            "copyrightText": "NOASSERTION",
+            "comment": format!("revision '{}' checked out at '{}'", component.revision, component.path),
+          })
+        }).collect::<Vec<_>>(),
+        "relationships": (0..components.len()).map(|index| serde_json::json!({
+          "spdxElementId": "SPDXRef-DOCUMENT",
+          "relationshipType": "DESCRIBES",
+          "relatedSpdxElement": id(index),
+        })).collect::<Vec<_>>(),
+      })
+    }
+  }
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp (`2024-01-02T03:04:05Z`).
+fn timestamp(time: std::time::SystemTime) -> String {
+  // Reuses the compact form of `utc_stamp`, whose layout is fixed: `20240102T030405Z`.
+  let stamp = super::utc_stamp(time);
+  format!(
+    "{}-{}-{}T{}:{}:{}Z",
+    &stamp[0..4],
+    &stamp[4..6],
+    &stamp[6..8],
+    &stamp[9..11],
+    &stamp[11..13],
+    &stamp[13..15]
+  )
+}
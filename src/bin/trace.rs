@@ -0,0 +1,124 @@
+//! An observer that records worker activity in the chrome trace event format, which can be loaded
+//! by `chrome://tracing` (or perfetto) to visualize scheduling gaps between phases.
+
+use std::io;
+
+/// The events recorded so far, along with the track (thread) ids we have handed out.
+#[derive(Default)]
+struct Inner {
+  /// The recorded trace events.
+  events: Vec<serde_json::Value>,
+  /// Thread names mapped to the track id used for them.
+  tracks: std::collections::HashMap<String, usize>,
+}
+
+impl Inner {
+  /// Returns the track id of the calling thread, registering a metadata event naming the track the
+  /// first time a thread is seen.
+  fn track(&mut self) -> usize {
+    let name = std::thread::current().name().unwrap_or("unnamed").to_string();
+
+    if let Some(id) = self.tracks.get(&name) {
+      return *id;
+    }
+
+    let id = self.tracks.len();
+    self.events.push(serde_json::json!({
+      "name": "thread_name",
+      "ph": "M",
+      "pid": 1,
+      "tid": id,
+      "args": { "name": name },
+    }));
+    self.tracks.insert(name, id);
+    id
+  }
+}
+
+/// Records one slice per phase of work, on one track per thread. Clones share the same recording.
+#[derive(Clone)]
+pub struct ChromeTrace {
+  /// When recording started; all timestamps are relative to this.
+  epoch: std::time::Instant,
+  /// The shared recording.
+  inner: std::sync::Arc<std::sync::Mutex<Inner>>,
+}
+
+impl Default for ChromeTrace {
+  fn default() -> Self {
+    Self {
+      epoch: std::time::Instant::now(),
+      inner: Default::default(),
+    }
+  }
+}
+
+impl ChromeTrace {
+  /// Records a complete slice that ended just now and lasted `elapsed`.
+  fn slice(&self, name: String, source: &str, elapsed: std::time::Duration) {
+    let end = self.epoch.elapsed();
+    let start = end.saturating_sub(elapsed);
+
+    let Ok(mut inner) = self.inner.lock() else {
+      return;
+    };
+    let tid = inner.track();
+    inner.events.push(serde_json::json!({
+      "name": name,
+      "cat": "phase",
+      "ph": "X",
+      "ts": start.as_micros() as u64,
+      "dur": elapsed.as_micros() as u64,
+      "pid": 1,
+      "tid": tid,
+      "args": { "source": source },
+    }));
+  }
+
+  /// Writes everything recorded so far to `path`.
+  pub fn write(&self, path: &std::path::Path) -> io::Result<()> {
+    let inner = self
+      .inner
+      .lock()
+      .map_err(|error| io::Error::other(error.to_string()))?;
+    let document = serde_json::json!({ "traceEvents": inner.events, "displayTimeUnit": "ms" });
+    std::fs::write(path, document.to_string())
+  }
+}
+
+/// Records the phases reported by our workers into a `ChromeTrace`.
+#[derive(Clone, Default)]
+pub struct TraceObserver {
+  /// The recording.
+  pub trace: ChromeTrace,
+  /// Placements are not reported as phases, so we time them ourselves between the start and finish
+  /// events; several may be in flight at once.
+  placements:
+    std::sync::Arc<std::sync::Mutex<std::collections::HashMap<std::path::PathBuf, std::time::Instant>>>,
+}
+
+impl repors::ProgressObserver for TraceObserver {
+  fn phase_finished(&self, source: &repors::Source, phase: repors::Phase, elapsed: std::time::Duration) {
+    self.trace.slice(phase.to_string(), &source.destination, elapsed);
+  }
+
+  fn placement_started(&self, destination: &std::path::Path) {
+    if let Ok(mut placements) = self.placements.lock() {
+      placements.insert(destination.to_path_buf(), std::time::Instant::now());
+    }
+  }
+
+  fn placement_finished(&self, destination: &std::path::Path) {
+    let started = self
+      .placements
+      .lock()
+      .ok()
+      .and_then(|mut placements| placements.remove(destination));
+    if let Some(started) = started {
+      let destination = destination.display().to_string();
+      self
+        .trace
+        .slice("place".to_string(), &destination, started.elapsed());
+    }
+  }
+}
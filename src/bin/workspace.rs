@@ -0,0 +1,216 @@
+//! The state `init` records in the `.repors` directory of a destination, so that later `sync` runs
+//! know which manifest to use without being told again.
+
+use std::io;
+
+/// The directory, within a destination, holding our state.
+const STATE_DIR: &str = ".repors";
+
+/// The name of the remote the manifest repository is fetched from.
+const ORIGIN: &str = "origin";
+
+/// What `init` was asked for.
+pub struct Config {
+  /// The url of the repository holding the manifest.
+  pub manifest_url: String,
+  /// The branch of the manifest repository that is followed.
+  pub manifest_branch: String,
+  /// The path of the manifest file within the manifest repository.
+  pub manifest_name: String,
+  /// The groups selected when `sync` is not given any.
+  pub groups: Vec<String>,
+}
+
+impl Config {
+  /// Where the configuration of the destination `root` is kept.
+  fn path(root: &std::path::Path) -> std::path::PathBuf {
+    root.join(STATE_DIR).join("config")
+  }
+
+  /// Reads the configuration of the destination `root`, if it has been initialized.
+  pub fn load(root: &std::path::Path) -> io::Result<Option<Self>> {
+    let contents = match std::fs::read_to_string(Self::path(root)) {
+      Ok(contents) => contents,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+      Err(error) => return Err(error),
+    };
+
+    let invalid = |field: &str| {
+      io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("'{}' has no valid {field}", Self::path(root).display()),
+      )
+    };
+    let value = serde_json::from_str::<serde_json::Value>(&contents)?;
+    let text = |field: &str| {
+      value[field]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| invalid(field))
+    };
+
+    Ok(Some(Self {
+      manifest_url: text("manifest_url")?,
+      manifest_branch: text("manifest_branch")?,
+      manifest_name: text("manifest_name")?,
+      groups: value["groups"]
+        .as_array()
+        .map(|groups| {
+          groups
+            .iter()
+            .filter_map(|group| group.as_str().map(str::to_string))
+            .collect()
+        })
+        .unwrap_or_default(),
+    }))
+  }
+
+  /// Writes the configuration of the destination `root`.
+  fn save(&self, root: &std::path::Path) -> io::Result<()> {
+    let value = serde_json::json!({
+      "manifest_url": self.manifest_url,
+      "manifest_branch": self.manifest_branch,
+      "manifest_name": self.manifest_name,
+      "groups": self.groups,
+    });
+    std::fs::write(Self::path(root), format!("{value:#}\n"))
+  }
+
+  /// The checkout of the manifest repository within the destination `root`.
+  fn checkout(root: &std::path::Path) -> std::path::PathBuf {
+    root.join(STATE_DIR).join("manifests")
+  }
+
+  /// The manifest file within the destination `root`.
+  pub fn manifest(&self, root: &std::path::Path) -> std::path::PathBuf {
+    Self::checkout(root).join(&self.manifest_name)
+  }
+}
+
+/// Where the clone durations observed by previous executions into the destination `root` are kept,
+/// unless a cache directory is given.
+pub fn timings(root: &std::path::Path) -> std::path::PathBuf {
+  root.join(STATE_DIR).join("timings.json")
+}
+
+/// Fetch options offering `credentials` to the manifest remote at `url`, reached through the proxy
+/// `proxies` choose for it and verified as `tls` says.
+fn fetch_options<'a>(
+  credentials: &'a repors::Credentials,
+  proxies: &'a repors::Proxies,
+  tls: &repors::Tls,
+  url: &str,
+) -> git2::FetchOptions<'a> {
+  let mut callbacks = git2::RemoteCallbacks::new();
+  callbacks.credentials(credentials.callback());
+  tls.apply(&mut callbacks);
+  let mut options = git2::FetchOptions::new();
+  options.remote_callbacks(callbacks);
+  if let Some(proxy) = proxies.proxy_for(url) {
+    let mut proxy_options = git2::ProxyOptions::new();
+    proxy_options.url(proxy);
+    options.proxy_options(proxy_options);
+  }
+  options
+}
+
+/// Wraps a libgit2 failure of the manifest repository with some context.
+fn manifest_error(error: git2::Error) -> io::Error {
+  io::Error::other(format!(
+    "unable to update the manifest repository - {}",
+    error.message()
+  ))
+}
+
+/// Initializes the destination `root`: the manifest repository of `config` is cloned (or, when
+/// already initialized, fetched) into the `.repors` directory, and `config` is recorded there. When
+/// `config` names no branch, the default branch of the manifest repository is followed.
+pub fn init(
+  root: &std::path::Path,
+  mut config: Config,
+  credentials: &repors::Credentials,
+  proxies: &repors::Proxies,
+  tls: &repors::Tls,
+) -> io::Result<Config> {
+  let checkout = Config::checkout(root);
+  std::fs::create_dir_all(root.join(STATE_DIR))?;
+
+  if !checkout.exists() {
+    let mut builder = git2::build::RepoBuilder::new();
+    builder.fetch_options(fetch_options(credentials, proxies, tls, &config.manifest_url));
+    if !config.manifest_branch.is_empty() {
+      builder.branch(&config.manifest_branch);
+    }
+    let repo = builder
+      .clone(&config.manifest_url, &checkout)
+      .map_err(manifest_error)?;
+    if config.manifest_branch.is_empty() {
+      let head = repo.head().map_err(manifest_error)?;
+      config.manifest_branch = head.shorthand().unwrap_or("master").to_string();
+    }
+  } else if config.manifest_branch.is_empty() {
+    let previous = Config::load(root)?.ok_or_else(|| {
+      io::Error::other("the manifest repository exists without a configuration; name the branch to follow")
+    })?;
+    config.manifest_branch = previous.manifest_branch;
+  }
+
+  let repo = git2::Repository::open(&checkout).map_err(manifest_error)?;
+  repo
+    .remote_set_url(ORIGIN, &config.manifest_url)
+    .map_err(manifest_error)?;
+  update(root, &config, credentials, proxies, tls)?;
+  config.save(root)?;
+  Ok(config)
+}
+
+/// Brings the checkout of the manifest repository within the destination `root` to the tip of the
+/// branch of `config`, returning the path of the manifest file.
+pub fn update(
+  root: &std::path::Path,
+  config: &Config,
+  credentials: &repors::Credentials,
+  proxies: &repors::Proxies,
+  tls: &repors::Tls,
+) -> io::Result<std::path::PathBuf> {
+  let repo = git2::Repository::open(Config::checkout(root)).map_err(manifest_error)?;
+  let mut remote = repo.find_remote(ORIGIN).map_err(manifest_error)?;
+  let refspec = format!(
+    "+refs/heads/{0}:refs/remotes/{ORIGIN}/{0}",
+    config.manifest_branch
+  );
+  remote
+    .fetch(
+      &[refspec],
+      Some(&mut fetch_options(
+        credentials,
+        proxies,
+        tls,
+        &config.manifest_url,
+      )),
+      None,
+    )
+    .map_err(manifest_error)?;
+
+  let tip = repo
+    .find_reference(&format!("refs/remotes/{ORIGIN}/{}", config.manifest_branch))
+    .and_then(|reference| reference.peel_to_commit())
+    .map_err(manifest_error)?;
+  repo.set_head_detached(tip.id()).map_err(manifest_error)?;
+  repo
+    .reset(tip.as_object(), git2::ResetType::Hard, None)
+    .map_err(manifest_error)?;
+
+  let manifest = config.manifest(root);
+  if !manifest.is_file() {
+    return Err(io::Error::new(
+      io::ErrorKind::NotFound,
+      format!(
+        "the manifest repository has no '{}' on '{}'",
+        config.manifest_name, config.manifest_branch
+      ),
+    ));
+  }
+
+  Ok(manifest)
+}
@@ -0,0 +1,19 @@
+use std::sync::atomic;
+
+/// Cancels an execution from another thread (e.g. a signal handler). Clones share the same state,
+/// so the pool can be handed one while the caller keeps another to `cancel` with.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(std::sync::Arc<atomic::AtomicBool>);
+
+impl CancellationToken {
+  /// Requests cancellation. Only an atomic store is performed, so this is safe to call from a
+  /// signal handler.
+  pub fn cancel(&self) {
+    self.0.store(true, atomic::Ordering::SeqCst);
+  }
+
+  /// True once `cancel` has been called on this token or any of its clones.
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(atomic::Ordering::SeqCst)
+  }
+}
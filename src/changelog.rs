@@ -0,0 +1,126 @@
+/// A single commit made to a project between two manifests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LogEntry {
+  /// The id of the commit.
+  pub id: git2::Oid,
+  /// The first line of the commit message.
+  pub summary: String,
+  /// The name of the author of the commit.
+  pub author: String,
+  /// When the commit was authored, in seconds since the unix epoch.
+  pub time: i64,
+}
+
+/// What happened to a project between two manifests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProjectChange {
+  /// The project is only listed by the newer manifest.
+  Added,
+  /// The project is only listed by the older manifest.
+  Removed,
+  /// The project moved to another revision; these are the commits that brought it there, newest
+  /// first. Empty when the project moved backwards.
+  Updated(Vec<LogEntry>),
+  /// The project moved to another revision, but its commits could not be read from the checkout
+  /// (e.g. it is missing, or has not fetched one of the revisions); this describes why.
+  Unknown(String),
+}
+
+/// How a single project changed between two manifests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProjectChangelog {
+  /// The manifest path of the project.
+  pub destination: String,
+  /// The revision of the project in the older manifest, if listed there.
+  pub old_revision: Option<String>,
+  /// The revision of the project in the newer manifest, if listed there.
+  pub new_revision: Option<String>,
+  /// What happened to the project.
+  pub change: ProjectChange,
+}
+
+/// Compares two (usually pinned) manifests project by project, matching projects by path, and
+/// reads the commits between the old and new revision of every project that moved from its
+/// checkout under `root`; nothing is fetched. Projects at the same revision in both are left out.
+/// Projects are ordered as in `new`, followed by those that were removed.
+pub fn changelog(
+  root: &std::path::Path,
+  old: &[crate::Source],
+  new: &[crate::Source],
+) -> Vec<ProjectChangelog> {
+  let previous = old
+    .iter()
+    .map(|source| (source.destination.as_str(), source))
+    .collect::<std::collections::HashMap<_, _>>();
+  let current = new
+    .iter()
+    .map(|source| source.destination.as_str())
+    .collect::<std::collections::HashSet<_>>();
+
+  let changed = new.iter().filter_map(|source| {
+    let before = previous.get(source.destination.as_str());
+    let change = match before {
+      None => ProjectChange::Added,
+      Some(before) if before.revision == source.revision => return None,
+      Some(before) => project_log(root, source, &before.revision),
+    };
+
+    Some(ProjectChangelog {
+      destination: source.destination.clone(),
+      old_revision: before.map(|before| before.revision.clone()),
+      new_revision: Some(source.revision.clone()),
+      change,
+    })
+  });
+
+  let removed = old
+    .iter()
+    .filter(|source| !current.contains(source.destination.as_str()))
+    .map(|source| ProjectChangelog {
+      destination: source.destination.clone(),
+      old_revision: Some(source.revision.clone()),
+      new_revision: None,
+      change: ProjectChange::Removed,
+    });
+
+  changed.chain(removed).collect()
+}
+
+/// The commits reachable from the revision of `source` but not from `old`, read from its checkout
+/// under `root`.
+fn project_log(root: &std::path::Path, source: &crate::Source, old: &str) -> ProjectChange {
+  let path = root.join(source.relative_path());
+  let walked = git2::Repository::open(&path).and_then(|repo| {
+    let from = crate::revision::resolve(&repo, old)?.id();
+    let to = crate::revision::resolve(&repo, &source.revision)?.id();
+
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+    walk.push(to)?;
+    walk.hide(from)?;
+
+    walk
+      .map(|id| {
+        let commit = repo.find_commit(id?)?;
+        let author = commit.author();
+        Ok(LogEntry {
+          id: commit.id(),
+          summary: commit.summary().unwrap_or_default().to_string(),
+          author: author.name().unwrap_or_default().to_string(),
+          time: author.when().seconds(),
+        })
+      })
+      .collect::<Result<Vec<_>, git2::Error>>()
+  });
+
+  match walked {
+    Ok(commits) => ProjectChange::Updated(commits),
+    Err(error) => {
+      log::warn!(source = source.destination.as_str(); "unable to read changes - {}", error.message());
+      ProjectChange::Unknown(error.message().to_string())
+    }
+  }
+}
@@ -0,0 +1,151 @@
+/// The git file mode of submodule entries, which have nothing to write to the working tree.
+const GITLINK_MODE: u32 = 0o160000;
+
+/// A path to check out, along with the id of its tree if it is a directory.
+type Unit = (String, Option<git2::Oid>);
+
+/// Checks out `tree` into the working directory of `repo` using `threads` threads. libgit2 only
+/// materializes files on a single thread, so the tree is split into units (files and directories)
+/// that are spread across several independent checkouts, none of which touch the index; the index
+/// is then written once, from the tree, with the stat information of the files we wrote. Trees that
+/// cannot be split fall back to a regular checkout.
+pub(crate) fn parallel(
+  repo: &git2::Repository,
+  tree: &git2::Tree<'_>,
+  threads: usize,
+) -> Result<(), git2::Error> {
+  let units = split(repo, tree, threads * 4)?;
+  if units.len() < 2 {
+    let mut options = git2::build::CheckoutBuilder::new();
+    options.force();
+    return repo.checkout_tree(tree.as_object(), Some(&mut options));
+  }
+
+  let mut groups = vec![Vec::new(); threads];
+  for (index, unit) in units.into_iter().enumerate() {
+    groups[index % threads].push(unit);
+  }
+
+  let git_dir = repo.path();
+  let tree_id = tree.id();
+
+  std::thread::scope(|scope| {
+    let handles = groups
+      .into_iter()
+      .filter(|group| !group.is_empty())
+      .map(|group| {
+        scope.spawn(move || {
+          let repo = git2::Repository::open(git_dir)?;
+          let tree = repo.find_tree(tree_id)?;
+          let mut options = git2::build::CheckoutBuilder::new();
+          options.force().update_index(false);
+          for path in &group {
+            options.path(path.as_str());
+          }
+          repo.checkout_tree(tree.as_object(), Some(&mut options))
+        })
+      })
+      .collect::<Vec<_>>();
+
+    handles.into_iter().try_for_each(|handle| {
+      handle
+        .join()
+        .unwrap_or_else(|_| Err(git2::Error::from_str("checkout thread panicked")))
+    })
+  })?;
+
+  let mut index = repo.index()?;
+  index.read_tree(tree)?;
+
+  if let Some(workdir) = repo.workdir() {
+    let mut entries = index.iter().collect::<Vec<git2::IndexEntry>>();
+    for entry in entries.iter_mut().filter(|entry| entry.mode != GITLINK_MODE) {
+      let path = String::from_utf8_lossy(&entry.path).into_owned();
+      if let Ok(metadata) = std::fs::symlink_metadata(workdir.join(path)) {
+        refresh_stat(entry, &metadata);
+      }
+    }
+    for entry in &entries {
+      index.add(entry)?;
+    }
+  }
+
+  index.write()
+}
+
+/// Splits `tree` into at least `target` units of work where possible, descending a level into
+/// every directory until there are enough. Directories with entries whose names are not utf-8 (or
+/// would be read as a pathspec pattern) are kept whole, since we could not name their children.
+fn split(repo: &git2::Repository, tree: &git2::Tree<'_>, target: usize) -> Result<Vec<String>, git2::Error> {
+  let mut units = children("", tree)?.unwrap_or_default();
+
+  while units.len() < target {
+    let mut expanded = Vec::with_capacity(units.len());
+    let mut descended = false;
+
+    for (path, directory) in units {
+      let Some(directory) = directory else {
+        expanded.push((path, None));
+        continue;
+      };
+
+      let subtree = repo.find_tree(directory)?;
+      match children(&path, &subtree)? {
+        Some(inner) => {
+          descended = true;
+          expanded.extend(inner);
+        }
+        None => expanded.push((path, None)),
+      }
+    }
+
+    units = expanded;
+    if !descended {
+      break;
+    }
+  }
+
+  Ok(units.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Lists the entries of `tree` (which lives at `prefix`) along with the ids of those that are
+/// directories, or `None` if any of them cannot be named.
+fn children(prefix: &str, tree: &git2::Tree<'_>) -> Result<Option<Vec<Unit>>, git2::Error> {
+  let mut out = Vec::with_capacity(tree.len());
+
+  for entry in tree.iter() {
+    let Some(name) = entry.name().filter(|name| !name.contains(['*', '?', '[', '\\'])) else {
+      return Ok(None);
+    };
+    let path = match prefix {
+      "" => name.to_string(),
+      prefix => format!("{prefix}/{name}"),
+    };
+    let directory = (entry.kind() == Some(git2::ObjectType::Tree)).then(|| entry.id());
+    out.push((path, directory));
+  }
+
+  Ok(Some(out))
+}
+
+/// Copies the stat information git keeps in the index from `metadata`, so that git does not need to
+/// re-hash the file to know it is unchanged.
+#[cfg(unix)]
+fn refresh_stat(entry: &mut git2::IndexEntry, metadata: &std::fs::Metadata) {
+  use std::os::unix::fs::MetadataExt;
+
+  entry.ctime = git2::IndexTime::new(metadata.ctime() as i32, metadata.ctime_nsec() as u32);
+  entry.mtime = git2::IndexTime::new(metadata.mtime() as i32, metadata.mtime_nsec() as u32);
+  entry.dev = metadata.dev() as u32;
+  entry.ino = metadata.ino() as u32;
+  entry.uid = metadata.uid();
+  entry.gid = metadata.gid();
+  entry.file_size = metadata.size() as u32;
+}
+
+/// Copies the stat information git keeps in the index from `metadata`, so that git does not need to
+/// re-hash the file to know it is unchanged.
+#[cfg(not(unix))]
+fn refresh_stat(entry: &mut git2::IndexEntry, metadata: &std::fs::Metadata) {
+  entry.file_size = metadata.len() as u32;
+}
@@ -0,0 +1,198 @@
+/// The places a username and password may come from, in the order they are tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Lookup {
+  /// The token we were configured with.
+  Token,
+  /// The program named by `GIT_ASKPASS`.
+  AskPass,
+  /// The user's netrc file.
+  Netrc,
+  /// The credential helpers configured in the user's git config.
+  Helper,
+}
+
+/// Every `Lookup`, in the order they are tried.
+const LOOKUPS: [Lookup; 4] = [Lookup::Token, Lookup::AskPass, Lookup::Netrc, Lookup::Helper];
+
+/// The username offered alongside a token when the url does not name one; GitHub and GitLab both
+/// accept any username with a personal access token.
+const TOKEN_USERNAME: &str = "x-access-token";
+
+/// Answers the credential requests of remotes. Https remotes are offered, in order: the token (if
+/// any), what the program named by `GIT_ASKPASS` answers, the matching entry of the netrc file and
+/// finally whatever git's credential helpers (from the user's git config) provide; each is only
+/// offered once per connection. Ssh remotes are offered the keys held by the ssh agent.
+#[derive(Clone, Default)]
+pub struct Credentials {
+  /// A token (e.g. a personal access token) offered as the password to https remotes.
+  token: Option<String>,
+  /// The netrc file consulted, when not the one in the home directory.
+  netrc: Option<std::path::PathBuf>,
+}
+
+impl std::fmt::Debug for Credentials {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    formatter
+      .debug_struct("Credentials")
+      .field("token", &self.token.as_ref().map(|_| crate::redact::MASK))
+      .field("netrc", &self.netrc)
+      .finish()
+  }
+}
+
+impl Credentials {
+  /// Offers `token` as the password to https remotes, before anything else is tried.
+  pub fn with_token(mut self, token: String) -> Self {
+    self.token = Some(token);
+    self
+  }
+
+  /// Consults the netrc file at `path` rather than the one in the home directory.
+  pub fn with_netrc(mut self, path: std::path::PathBuf) -> Self {
+    self.netrc = Some(path);
+    self
+  }
+
+  /// Creates the credential callback of a single connection, for `git2::RemoteCallbacks::credentials`.
+  pub fn callback(
+    &self,
+  ) -> impl FnMut(&str, Option<&str>, git2::CredentialType) -> Result<git2::Cred, git2::Error> + '_ {
+    let mut lookups = LOOKUPS.iter();
+    let mut offered_agent = false;
+
+    move |url, username, allowed| {
+      if allowed.contains(git2::CredentialType::SSH_KEY) && !offered_agent {
+        offered_agent = true;
+        return git2::Cred::ssh_key_from_agent(username.unwrap_or("git"));
+      }
+
+      if allowed.contains(git2::CredentialType::USERNAME) {
+        return git2::Cred::username(username.unwrap_or("git"));
+      }
+
+      if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+        for lookup in lookups.by_ref() {
+          log::debug!(
+            "looking up credentials for '{}' ({lookup:?})",
+            crate::redact::redact(url)
+          );
+          if *lookup == Lookup::Helper {
+            let helped = git2::Config::open_default()
+              .and_then(|config| git2::Cred::credential_helper(&config, url, username));
+            if helped.is_ok() {
+              return helped;
+            }
+            continue;
+          }
+
+          if let Some((username, password)) = self.userpass(*lookup, url, username) {
+            return git2::Cred::userpass_plaintext(&username, &password);
+          }
+        }
+      }
+
+      Err(git2::Error::from_str(
+        "no (further) credentials available; tried the token, GIT_ASKPASS, netrc and git credential helpers",
+      ))
+    }
+  }
+
+  /// The username and password `lookup` provides for `url`, if any. Credential helpers are not
+  /// consulted here, since libgit2 hands us their result as an opaque credential.
+  pub(crate) fn userpass(
+    &self,
+    lookup: Lookup,
+    url: &str,
+    username: Option<&str>,
+  ) -> Option<(String, String)> {
+    match lookup {
+      Lookup::Token => self.token.as_ref().map(|token| {
+        let username = username.unwrap_or(TOKEN_USERNAME).to_string();
+        (username, token.clone())
+      }),
+      Lookup::AskPass => {
+        let program = std::env::var_os("GIT_ASKPASS").filter(|program| !program.is_empty())?;
+        let username = match username {
+          Some(username) => username.to_string(),
+          None => ask(&program, &format!("Username for '{url}': "))?,
+        };
+        let password = ask(&program, &format!("Password for '{url}': "))?;
+        Some((username, password))
+      }
+      Lookup::Netrc => {
+        let path = self.netrc.clone().or_else(default_netrc)?;
+        let contents = std::fs::read_to_string(path).ok()?;
+        netrc_entry(&contents, crate::throttle::host(url)?)
+          .filter(|(login, _)| username.is_none_or(|name| name == login))
+      }
+      Lookup::Helper => None,
+    }
+  }
+}
+
+/// Runs the askpass `program` with `prompt`, returning the first line it prints.
+fn ask(program: &std::ffi::OsStr, prompt: &str) -> Option<String> {
+  let output = std::process::Command::new(program)
+    .arg(prompt)
+    .stdin(std::process::Stdio::null())
+    .output()
+    .map_err(|error| log::warn!("unable to run GIT_ASKPASS - {error}"))
+    .ok()?;
+
+  if !output.status.success() {
+    log::warn!("GIT_ASKPASS exited with {}", output.status);
+    return None;
+  }
+
+  let answer = String::from_utf8(output.stdout).ok()?;
+  answer.lines().next().map(str::to_string)
+}
+
+/// The netrc file in the home directory: `.netrc`, or `_netrc` on windows.
+fn default_netrc() -> Option<std::path::PathBuf> {
+  let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+  let name = if cfg!(windows) { "_netrc" } else { ".netrc" };
+  Some(std::path::PathBuf::from(home).join(name))
+}
+
+/// Finds the login and password of the `machine` entry for `host` in the netrc `contents`, falling
+/// back to the `default` entry.
+pub(crate) fn netrc_entry(contents: &str, host: &str) -> Option<(String, String)> {
+  let mut tokens = contents.split_whitespace();
+  let mut fallback = None;
+  let mut current: Option<(bool, Option<String>, Option<String>)> = None;
+  let mut entries = Vec::new();
+
+  while let Some(token) = tokens.next() {
+    match token {
+      "machine" => {
+        entries.extend(current.take());
+        let machine = tokens.next();
+        current = Some((machine == Some(host), None, None));
+      }
+      "default" => {
+        entries.extend(current.take());
+        fallback = Some(entries.len());
+        current = Some((false, None, None));
+      }
+      "login" => {
+        if let Some((_, login, _)) = current.as_mut() {
+          *login = tokens.next().map(str::to_string);
+        }
+      }
+      "password" => {
+        if let Some((_, _, password)) = current.as_mut() {
+          *password = tokens.next().map(str::to_string);
+        }
+      }
+      _ => (),
+    }
+  }
+  entries.extend(current);
+
+  let position = entries.iter().position(|(matched, _, _)| *matched).or(fallback)?;
+  match entries.swap_remove(position) {
+    (_, Some(login), Some(password)) => Some((login, password)),
+    _ => None,
+  }
+}
@@ -0,0 +1,534 @@
+use std::io;
+use std::io::{BufRead, Read, Write};
+
+/// How many redirects are followed before giving up.
+const MAX_REDIRECTS: usize = 5;
+
+/// How long connecting to (and then each read from) the server may take.
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// The most bytes of status line and headers we are willing to read.
+const MAX_HEAD_BYTES: u64 = 64 * 1024;
+
+/// The parts of an `http://` or `https://` url we need to make a request.
+struct Target {
+  /// True for `https://` urls, which are requested over tls.
+  secure: bool,
+  /// The host (and port, if any) as written in the url, for the `Host` header.
+  authority: String,
+  /// The host and port to connect to.
+  address: (String, u16),
+  /// The path and query of the url.
+  path: String,
+}
+
+impl Target {
+  /// Parses `url`, which must be an `http://` or `https://` url without credentials.
+  fn parse(url: &str) -> io::Result<Self> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+
+    let (scheme, rest) = url
+      .split_once("://")
+      .ok_or_else(|| invalid("only http(s) urls can be downloaded"))?;
+    let secure = match scheme.to_ascii_lowercase().as_str() {
+      "http" => false,
+      "https" => true,
+      _ => return Err(invalid("only http(s) urls can be downloaded")),
+    };
+
+    let (authority, path) = match rest.find(['/', '?']) {
+      Some(index) if rest[index..].starts_with('?') => (&rest[..index], format!("/{}", &rest[index..])),
+      Some(index) => (&rest[..index], rest[index..].to_string()),
+      None => (rest, "/".to_string()),
+    };
+    let path = path.split('#').next().unwrap_or("/").to_string();
+    if authority.contains('@') {
+      return Err(invalid("credentials in manifest urls are not supported"));
+    }
+
+    let (host, port) = match authority.rsplit_once(':') {
+      Some((host, port)) if !port.contains(']') => {
+        let port = port
+          .parse()
+          .map_err(|_| invalid("the port of the url is not a number"))?;
+        (host, port)
+      }
+      _ => (authority, if secure { 443 } else { 80 }),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+      return Err(invalid("the url has no host"));
+    }
+
+    Ok(Self {
+      secure,
+      authority: authority.to_string(),
+      address: (host.to_string(), port),
+      path,
+    })
+  }
+
+  /// The scheme of the url.
+  fn scheme(&self) -> &'static str {
+    if self.secure {
+      "https"
+    } else {
+      "http"
+    }
+  }
+
+  /// Resolves the `Location` of a redirect against this target.
+  fn redirect(&self, location: &str) -> String {
+    if location.starts_with('/') {
+      format!("{}://{}{location}", self.scheme(), self.authority)
+    } else {
+      location.to_string()
+    }
+  }
+}
+
+/// Downloads the body of the `http://` or `https://` url `url`, following redirects, failing if it
+/// is larger than `limit` bytes or the server does not answer with success. Meant for manifests kept
+/// on web servers (e.g. the raw files of a forge). The certificates of https servers are verified as
+/// `tls` says, through the openssl libgit2 is built with; redirects from https to http are refused.
+pub fn download(url: &str, limit: u64, tls: &crate::tls::Tls) -> io::Result<Vec<u8>> {
+  let mut url = url.to_string();
+  let mut secure = false;
+
+  for _ in 0..=MAX_REDIRECTS {
+    let target = Target::parse(&url)?;
+    if secure && !target.secure {
+      return Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("refusing a redirect from https to '{}'", crate::redact(&url)),
+      ));
+    }
+    secure = target.secure;
+    match get(&target, limit, tls)? {
+      Response::Body(body) => return Ok(body),
+      Response::Redirect(location) => {
+        log::debug!(
+          "'{}' redirected to '{}'",
+          crate::redact(&url),
+          crate::redact(&location)
+        );
+        url = target.redirect(&location);
+      }
+    }
+  }
+
+  Err(io::Error::other(format!(
+    "'{}' redirected more than {MAX_REDIRECTS} times",
+    crate::redact(&url)
+  )))
+}
+
+/// What the server answered a request with.
+enum Response {
+  /// The body of a successful response.
+  Body(Vec<u8>),
+  /// The location of a redirect.
+  Redirect(String),
+}
+
+/// Makes a single `GET` request of `target`.
+fn get(target: &Target, limit: u64, tls: &crate::tls::Tls) -> io::Result<Response> {
+  let addresses = std::net::ToSocketAddrs::to_socket_addrs(&(target.address.0.as_str(), target.address.1))?;
+  let mut connected = Err(io::Error::new(
+    io::ErrorKind::NotFound,
+    "the host has no addresses",
+  ));
+  for address in addresses {
+    connected = std::net::TcpStream::connect_timeout(&address, TIMEOUT);
+    if connected.is_ok() {
+      break;
+    }
+  }
+  let stream = connected?;
+  stream.set_read_timeout(Some(TIMEOUT))?;
+  stream.set_write_timeout(Some(TIMEOUT))?;
+  if target.secure {
+    let stream = secure::TlsStream::connect(stream, &target.address.0, tls)?;
+    return exchange(stream, target, limit);
+  }
+  exchange(stream, target, limit)
+}
+
+/// Sends the `GET` request of `target` over `stream` and reads the response.
+fn exchange<S>(mut stream: S, target: &Target, limit: u64) -> io::Result<Response>
+where
+  S: Read + Write,
+{
+  write!(
+    stream,
+    "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: repors/{}\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+    target.path,
+    target.authority,
+    env!("CARGO_PKG_VERSION")
+  )?;
+
+  let mut reader = io::BufReader::new(stream);
+  let mut head = (&mut reader).take(MAX_HEAD_BYTES);
+  let mut status = String::new();
+  head.read_line(&mut status)?;
+  let code = status
+    .split_whitespace()
+    .nth(1)
+    .and_then(|code| code.parse::<u16>().ok())
+    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed http response"))?;
+
+  let mut headers = std::collections::HashMap::new();
+  loop {
+    let mut line = String::new();
+    if head.read_line(&mut line)? == 0 {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "http response headers are too long",
+      ));
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = line.split_once(':') {
+      headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+  }
+
+  match code {
+    200..=299 => (),
+    301 | 302 | 303 | 307 | 308 => {
+      let location = headers
+        .remove("location")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "redirect without a location"))?;
+      return Ok(Response::Redirect(location));
+    }
+    404 | 410 => {
+      return Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("server answered '{}'", status.trim()),
+      ))
+    }
+    _ => return Err(io::Error::other(format!("server answered '{}'", status.trim()))),
+  }
+
+  let too_large = || {
+    io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("download is larger than {limit} bytes"),
+    )
+  };
+  let chunked = headers
+    .get("transfer-encoding")
+    .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+  let mut body = Vec::new();
+
+  if chunked {
+    loop {
+      let mut size = String::new();
+      reader.read_line(&mut size)?;
+      let size = size.split(';').next().unwrap_or_default().trim();
+      let size = u64::from_str_radix(size, 16)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed chunked response"))?;
+      if size == 0 {
+        break;
+      }
+      if body.len() as u64 + size > limit {
+        return Err(too_large());
+      }
+      let read = (&mut reader).take(size).read_to_end(&mut body)?;
+      if read as u64 != size {
+        return Err(io::Error::new(
+          io::ErrorKind::UnexpectedEof,
+          "response ended early",
+        ));
+      }
+      reader.read_line(&mut String::new())?;
+    }
+    return Ok(Response::Body(body));
+  }
+
+  let length = headers
+    .get("content-length")
+    .and_then(|length| length.parse::<u64>().ok());
+  if length.is_some_and(|length| length > limit) {
+    return Err(too_large());
+  }
+  reader
+    .take(length.unwrap_or(limit.saturating_add(1)))
+    .read_to_end(&mut body)?;
+  match length {
+    Some(length) if (body.len() as u64) < length => Err(io::Error::new(
+      io::ErrorKind::UnexpectedEof,
+      "response ended early",
+    )),
+    _ if body.len() as u64 > limit => Err(too_large()),
+    _ => Ok(Response::Body(body)),
+  }
+}
+
+/// The tls client of https downloads, built on the openssl libgit2 links against.
+#[cfg(unix)]
+mod secure {
+  use std::ffi::{c_int, CStr, CString};
+  use std::io;
+
+  /// The reason openssl gives when a peer closes the connection without a `close_notify`, which
+  /// many web servers do once they have sent a `Connection: close` response.
+  const UNEXPECTED_EOF: c_int = 294;
+
+  /// A tls connection over a tcp stream.
+  pub(super) struct TlsStream {
+    /// The connection, which borrows the socket of `tcp`.
+    ssl: *mut openssl_sys::SSL,
+    /// The context the connection was made with.
+    ctx: *mut openssl_sys::SSL_CTX,
+    /// The socket, closed once the connection is freed.
+    _tcp: std::net::TcpStream,
+  }
+
+  impl TlsStream {
+    /// Makes a tls connection to `host` over `tcp`, verifying its certificate as `tls` says.
+    pub(super) fn connect(tcp: std::net::TcpStream, host: &str, tls: &crate::tls::Tls) -> io::Result<Self> {
+      openssl_sys::init();
+      // SAFETY: the context is checked for null before use, and freed by `Drop` (or right away when
+      // it cannot be configured).
+      let ctx = unsafe { openssl_sys::SSL_CTX_new(openssl_sys::TLS_client_method()) };
+      if ctx.is_null() {
+        return Err(error("unable to set up tls"));
+      }
+      // SAFETY: `ctx` is valid and not yet shared.
+      let ssl = unsafe { configure(ctx, tls) }.map(|()| unsafe { openssl_sys::SSL_new(ctx) });
+      let ssl = match ssl {
+        Ok(ssl) if !ssl.is_null() => ssl,
+        result => {
+          // SAFETY: nothing else refers to the context.
+          unsafe { openssl_sys::SSL_CTX_free(ctx) };
+          return Err(result.err().unwrap_or_else(|| error("unable to set up tls")));
+        }
+      };
+      let stream = Self { ssl, ctx, _tcp: tcp };
+
+      let name =
+        CString::new(host).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid host"))?;
+      let address = host.parse::<std::net::IpAddr>().ok();
+      // SAFETY: `ssl` is valid; openssl copies the names and the socket outlives the connection,
+      // which does not close it.
+      unsafe {
+        let param = openssl_sys::SSL_get0_param(stream.ssl);
+        let checked = match address {
+          Some(std::net::IpAddr::V4(address)) => {
+            openssl_sys::X509_VERIFY_PARAM_set1_ip(param, address.octets().as_ptr(), 4)
+          }
+          Some(std::net::IpAddr::V6(address)) => {
+            openssl_sys::X509_VERIFY_PARAM_set1_ip(param, address.octets().as_ptr(), 16)
+          }
+          None => {
+            openssl_sys::SSL_set_tlsext_host_name(stream.ssl, name.as_ptr() as *mut _);
+            openssl_sys::X509_VERIFY_PARAM_set1_host(param, name.as_ptr(), host.len())
+          }
+        };
+        if checked != 1 {
+          return Err(error("unable to set the host to verify"));
+        }
+
+        let bio = openssl_sys::BIO_new_socket(std::os::unix::io::AsRawFd::as_raw_fd(&stream._tcp), 0);
+        if bio.is_null() {
+          return Err(error("unable to set up tls"));
+        }
+        openssl_sys::SSL_set_bio(stream.ssl, bio, bio);
+
+        if openssl_sys::SSL_connect(stream.ssl) != 1 {
+          let verified = openssl_sys::SSL_get_verify_result(stream.ssl);
+          if verified != std::ffi::c_long::from(openssl_sys::X509_V_OK) {
+            let reason = CStr::from_ptr(openssl_sys::X509_verify_cert_error_string(verified));
+            openssl_sys::ERR_clear_error();
+            return Err(io::Error::new(
+              io::ErrorKind::PermissionDenied,
+              format!(
+                "the tls certificate of '{host}' is not trusted - {}",
+                reason.to_string_lossy()
+              ),
+            ));
+          }
+          return Err(error(&format!("unable to make a tls connection to '{host}'")));
+        }
+      }
+
+      Ok(stream)
+    }
+
+    /// Maps the result `code` of a read or write to an error, or to the end of the stream (`None`).
+    fn failure(&self, code: c_int) -> Option<io::Error> {
+      let last = io::Error::last_os_error();
+      // SAFETY: `ssl` is valid for the life of the stream, and the error queue is that of this
+      // thread.
+      let (kind, queued) = unsafe {
+        (
+          openssl_sys::SSL_get_error(self.ssl, code),
+          openssl_sys::ERR_get_error(),
+        )
+      };
+      match kind {
+        openssl_sys::SSL_ERROR_ZERO_RETURN => None,
+        openssl_sys::SSL_ERROR_SYSCALL if queued == 0 => match last.raw_os_error() {
+          None | Some(0) => None,
+          Some(_) => Some(last),
+        },
+        openssl_sys::SSL_ERROR_SSL if openssl_sys::ERR_GET_REASON(queued) == UNEXPECTED_EOF => {
+          // SAFETY: clearing the error queue of this thread.
+          unsafe { openssl_sys::ERR_clear_error() };
+          None
+        }
+        _ => Some(describe("tls connection failed", queued)),
+      }
+    }
+  }
+
+  impl io::Read for TlsStream {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+      let length = buffer.len().min(c_int::MAX as usize) as c_int;
+      // SAFETY: `ssl` is valid and `buffer` holds at least `length` bytes.
+      let read = unsafe { openssl_sys::SSL_read(self.ssl, buffer.as_mut_ptr().cast(), length) };
+      if read > 0 {
+        return Ok(read as usize);
+      }
+      match self.failure(read) {
+        Some(error) => Err(error),
+        None => Ok(0),
+      }
+    }
+  }
+
+  impl io::Write for TlsStream {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+      let length = buffer.len().min(c_int::MAX as usize) as c_int;
+      // SAFETY: `ssl` is valid and `buffer` holds at least `length` bytes.
+      let written = unsafe { openssl_sys::SSL_write(self.ssl, buffer.as_ptr().cast(), length) };
+      if written > 0 {
+        return Ok(written as usize);
+      }
+      Err(
+        self
+          .failure(written)
+          .unwrap_or_else(|| io::Error::new(io::ErrorKind::WriteZero, "tls connection closed")),
+      )
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  impl Drop for TlsStream {
+    fn drop(&mut self) {
+      // SAFETY: both were made in `connect` and nothing else refers to them; the connection (and
+      // its socket bio) is freed before its context.
+      unsafe {
+        openssl_sys::SSL_free(self.ssl);
+        openssl_sys::SSL_CTX_free(self.ctx);
+      }
+    }
+  }
+
+  /// Sets how the certificates of servers are verified on `ctx`.
+  ///
+  /// # Safety
+  ///
+  /// `ctx` must be a valid context no connection has been made with yet.
+  unsafe fn configure(ctx: *mut openssl_sys::SSL_CTX, tls: &crate::tls::Tls) -> io::Result<()> {
+    if tls.is_insecure() {
+      log::warn!("not verifying the tls certificates of downloads");
+      openssl_sys::SSL_CTX_set_verify(ctx, openssl_sys::SSL_VERIFY_NONE, None);
+      return Ok(());
+    }
+    openssl_sys::SSL_CTX_set_verify(ctx, openssl_sys::SSL_VERIFY_PEER, None);
+
+    if let Some(path) = tls.ca_bundle() {
+      let file = path_string(path)?;
+      if openssl_sys::SSL_CTX_load_verify_locations(ctx, file.as_ptr(), std::ptr::null()) != 1 {
+        return Err(error(&format!(
+          "unable to use certificate authority bundle '{}'",
+          path.display()
+        )));
+      }
+      return Ok(());
+    }
+
+    // The defaults openssl was built with may not exist (e.g. when it is vendored), so the usual
+    // locations of the system are probed too.
+    openssl_sys::SSL_CTX_set_default_verify_paths(ctx);
+    let probed = openssl_probe::probe();
+    let file = probed.cert_file.as_deref().map(path_string).transpose()?;
+    let dir = probed.cert_dir.as_deref().map(path_string).transpose()?;
+    if file.is_some() || dir.is_some() {
+      let pointer = |value: &Option<CString>| value.as_ref().map_or(std::ptr::null(), |value| value.as_ptr());
+      openssl_sys::SSL_CTX_load_verify_locations(ctx, pointer(&file), pointer(&dir));
+    }
+    openssl_sys::ERR_clear_error();
+    Ok(())
+  }
+
+  /// `path` as a string for openssl.
+  fn path_string(path: &std::path::Path) -> io::Result<CString> {
+    CString::new(std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()))
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid path"))
+  }
+
+  /// An error of `message` and the reason of the last openssl error.
+  fn error(message: &str) -> io::Error {
+    // SAFETY: reading the error queue of this thread.
+    describe(message, unsafe { openssl_sys::ERR_get_error() })
+  }
+
+  /// An error of `message` and the reason of the openssl error `code`; the rest of the error queue
+  /// is cleared.
+  fn describe(message: &str, code: std::ffi::c_ulong) -> io::Error {
+    // SAFETY: openssl returns static strings, or null for unknown reasons; the error queue is that
+    // of this thread.
+    let reason = unsafe {
+      let reason = openssl_sys::ERR_reason_error_string(code);
+      openssl_sys::ERR_clear_error();
+      (code != 0 && !reason.is_null()).then(|| CStr::from_ptr(reason).to_string_lossy().into_owned())
+    };
+    match reason {
+      Some(reason) => io::Error::other(format!("{message} - {reason}")),
+      None => io::Error::other(message.to_string()),
+    }
+  }
+}
+
+/// Without openssl to build on, https downloads are not supported.
+#[cfg(not(unix))]
+mod secure {
+  use std::io;
+
+  /// Stands in for the tls connections of unix.
+  pub(super) struct TlsStream(std::convert::Infallible);
+
+  impl TlsStream {
+    /// Fails, since no tls client is built in.
+    pub(super) fn connect(_: std::net::TcpStream, _: &str, _: &crate::tls::Tls) -> io::Result<Self> {
+      Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "https urls are not supported on this platform; download the manifest first",
+      ))
+    }
+  }
+
+  impl io::Read for TlsStream {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+      match self.0 {}
+    }
+  }
+
+  impl io::Write for TlsStream {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+      match self.0 {}
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      match self.0 {}
+    }
+  }
+}
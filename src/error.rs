@@ -0,0 +1,323 @@
+use crate::redact;
+use std::io;
+
+/// A broad classification of the git failures we run into, used to tell users whether they should
+/// be looking at their credentials, their manifest, or their network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GitErrorKind {
+  /// The remote host name could not be resolved.
+  Dns,
+  /// The remote rejected our credentials (or we had none to offer).
+  Auth,
+  /// The tls handshake or certificate verification failed.
+  Tls,
+  /// The remote took too long to respond.
+  Timeout,
+  /// The remote repository does not exist.
+  RepositoryNotFound,
+  /// The repository was cloned, but the revision requested by the manifest is not in it.
+  RevisionNotFound,
+  /// Some other failure while talking to the remote.
+  Network,
+  /// Anything we were unable to classify.
+  Other,
+}
+
+impl GitErrorKind {
+  /// A short, human readable suggestion of what the user should look into.
+  pub fn hint(&self) -> &'static str {
+    match self {
+      Self::Dns => "unable to resolve the remote host; check the remote url and your dns/network",
+      Self::Auth => "the remote denied access; check your credentials",
+      Self::Tls => "tls error talking to the remote; check certificates and the remote url",
+      Self::Timeout => "timed out talking to the remote; check your network",
+      Self::RepositoryNotFound => {
+        "the remote repository was not found; check the manifest remote and project name"
+      }
+      Self::RevisionNotFound => "the revision was not found in the repository; check the manifest revision",
+      Self::Network => "network error talking to the remote",
+      Self::Other => "git error",
+    }
+  }
+
+  /// Determines the kind of a `git2` error from its code, class and message.
+  fn classify(error: &git2::Error) -> Self {
+    let message = error.message().to_lowercase();
+    let mentions = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+
+    match (error.code(), error.class()) {
+      (git2::ErrorCode::Auth, _) => Self::Auth,
+      (git2::ErrorCode::Certificate, _) | (_, git2::ErrorClass::Ssl) => Self::Tls,
+      (git2::ErrorCode::Timeout, _) => Self::Timeout,
+      (
+        git2::ErrorCode::NotFound | git2::ErrorCode::Ambiguous,
+        git2::ErrorClass::Odb | git2::ErrorClass::Reference | git2::ErrorClass::Object,
+      ) => Self::RevisionNotFound,
+      _ if mentions(&[
+        "failed to resolve address",
+        "could not resolve",
+        "name or service not known",
+      ]) =>
+      {
+        Self::Dns
+      }
+      _ if mentions(&["401", "403", "authentication", "permission denied"]) => Self::Auth,
+      _ if mentions(&["certificate", "ssl", "tls"]) => Self::Tls,
+      _ if mentions(&["timed out", "timeout"]) => Self::Timeout,
+      _ if mentions(&["404", "repository not found", "not found"]) => Self::RepositoryNotFound,
+      (_, git2::ErrorClass::Net | git2::ErrorClass::Http | git2::ErrorClass::Ssh) => Self::Network,
+      _ => Self::Other,
+    }
+  }
+}
+
+/// A classified failure coming from `git2`.
+#[derive(Debug)]
+pub struct GitError {
+  /// Our classification of the error.
+  kind: GitErrorKind,
+  /// The original error.
+  inner: git2::Error,
+}
+
+impl GitError {
+  /// Our classification of this error.
+  pub fn kind(&self) -> GitErrorKind {
+    self.kind
+  }
+}
+
+impl From<git2::Error> for GitError {
+  fn from(inner: git2::Error) -> Self {
+    Self {
+      kind: GitErrorKind::classify(&inner),
+      inner,
+    }
+  }
+}
+
+impl std::fmt::Display for GitError {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(formatter, "{}", self.kind.hint())
+  }
+}
+
+impl std::error::Error for GitError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    Some(&self.inner)
+  }
+}
+
+/// The time limit a source ran out of; failures for that reason have one of these in their `source`
+/// chain, with an `io::ErrorKind::TimedOut` kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TimeLimitExceeded {
+  /// The source took longer than the given limit on each source.
+  Project(std::time::Duration),
+  /// The deadline of the whole execution passed.
+  Deadline,
+}
+
+impl std::fmt::Display for TimeLimitExceeded {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Project(limit) => write!(formatter, "took longer than the {limit:?} allowed per project"),
+      Self::Deadline => write!(formatter, "the deadline of the execution passed"),
+    }
+  }
+}
+
+impl std::error::Error for TimeLimitExceeded {}
+
+/// The phases of work performed for every source; used to give our errors context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Phase {
+  /// Preparing the staging directory.
+  Prepare,
+  /// Cloning the repository from its remote.
+  Clone,
+  /// Resolving the manifest revision to a commit.
+  Resolve,
+  /// Checking out the resolved commit.
+  Checkout,
+  /// Moving the checkout into its final destination.
+  Place,
+}
+
+impl std::fmt::Display for Phase {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      Self::Prepare => "prepare",
+      Self::Clone => "clone",
+      Self::Resolve => "resolve",
+      Self::Checkout => "checkout",
+      Self::Place => "place",
+    };
+    write!(formatter, "{name}")
+  }
+}
+
+/// A failure of some phase of work for a single source. The underlying cause is available through
+/// `std::error::Error::source`.
+#[derive(Debug)]
+pub struct SourceError {
+  /// The manifest path of the source that failed.
+  destination: String,
+  /// The (redacted) remote url of the source that failed.
+  origin: String,
+  /// The phase that failed.
+  phase: Phase,
+  /// What went wrong.
+  inner: io::Error,
+}
+
+impl SourceError {
+  /// Wraps `inner` with the context of the source and phase it happened during.
+  pub(crate) fn new(destination: &str, origin: &str, phase: Phase, inner: io::Error) -> Self {
+    Self {
+      destination: destination.to_string(),
+      origin: redact::redact(origin).into_owned(),
+      phase,
+      inner,
+    }
+  }
+
+  /// The manifest path of the source that failed.
+  pub fn destination(&self) -> &str {
+    &self.destination
+  }
+
+  /// The (redacted) remote url of the source that failed.
+  pub fn origin(&self) -> &str {
+    &self.origin
+  }
+
+  /// The phase that failed.
+  pub fn phase(&self) -> Phase {
+    self.phase
+  }
+}
+
+impl std::fmt::Display for SourceError {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      formatter,
+      "{} failed for '{}' ({})",
+      self.phase, self.destination, self.origin
+    )
+  }
+}
+
+impl std::error::Error for SourceError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    Some(&self.inner)
+  }
+}
+
+impl From<SourceError> for io::Error {
+  fn from(error: SourceError) -> Self {
+    io::Error::new(error.inner.kind(), error)
+  }
+}
+
+/// Everything that can go wrong parsing a manifest or executing a pool, classified so that callers
+/// can tell a broken manifest from a failing remote from a full disk. Wherever an `io::Error` is
+/// more convenient (e.g. in a `main` returning `io::Result`), every variant converts into one with
+/// a fitting `io::ErrorKind`, from which the `Error` can be recovered with `get_ref` and
+/// `downcast_ref`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+  /// The manifest could not be read, or is invalid.
+  Parse(io::Error),
+  /// Sources could not be cloned (or updated) and checked out. Every failed source is listed, with
+  /// the phase it failed in; failures git is to blame for have a `GitError` in their `source`
+  /// chain.
+  Git(Vec<SourceError>),
+  /// A checkout could not be moved into its destination, or the files and links named by the
+  /// manifest could not be created.
+  Placement(io::Error),
+  /// The workers of the pool could not be started, or were lost.
+  Worker(io::Error),
+  /// The execution was stopped by its `CancellationToken`.
+  Cancelled,
+  /// Any other filesystem failure, e.g. preparing the destination.
+  Io(io::Error),
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Parse(error) | Self::Placement(error) | Self::Worker(error) | Self::Io(error) => {
+        write!(formatter, "{error}")
+      }
+      Self::Git(failures) => {
+        write!(formatter, "{} source(s) failed:", failures.len())?;
+        failures
+          .iter()
+          .try_for_each(|failure| write!(formatter, "\n  - {}", error_chain(failure)))
+      }
+      Self::Cancelled => write!(formatter, "execution was cancelled"),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Parse(error) | Self::Placement(error) | Self::Worker(error) | Self::Io(error) => error.source(),
+      Self::Git(_) | Self::Cancelled => None,
+    }
+  }
+}
+
+impl From<io::Error> for Error {
+  fn from(error: io::Error) -> Self {
+    Self::Io(error)
+  }
+}
+
+impl From<Error> for io::Error {
+  fn from(error: Error) -> Self {
+    let kind = match &error {
+      Error::Parse(inner) | Error::Placement(inner) | Error::Worker(inner) | Error::Io(inner) => inner.kind(),
+      Error::Git(failures) => match failures.as_slice() {
+        [only] => only.inner.kind(),
+        _ => io::ErrorKind::Other,
+      },
+      Error::Cancelled => io::ErrorKind::Interrupted,
+    };
+
+    io::Error::new(kind, error)
+  }
+}
+
+/// Renders an error along with every error in its `source` chain, separated by `": "`.
+pub fn error_chain(error: &(dyn std::error::Error + 'static)) -> String {
+  let mut rendered = error.to_string();
+  let mut current = error.source();
+
+  while let Some(cause) = current {
+    rendered.push_str(": ");
+    rendered.push_str(&cause.to_string());
+    current = cause.source();
+  }
+
+  redact::redact(&rendered).into_owned()
+}
+
+impl From<GitError> for io::Error {
+  fn from(error: GitError) -> Self {
+    let kind = match error.kind {
+      GitErrorKind::Auth => io::ErrorKind::PermissionDenied,
+      GitErrorKind::Timeout => io::ErrorKind::TimedOut,
+      GitErrorKind::RepositoryNotFound | GitErrorKind::RevisionNotFound => io::ErrorKind::NotFound,
+      _ => io::ErrorKind::Other,
+    };
+
+    io::Error::new(kind, error)
+  }
+}
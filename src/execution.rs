@@ -1,6 +1,70 @@
-use crate::{manifest, tree};
+use crate::observer::ProgressObserver;
+use crate::{
+  audit, cancel, checkout, credentials, error, instrument, manifest, mirror, observer, proxy, redact, report,
+  revision, sparse, status, storage, throttle, tls, tree, watchdog,
+};
 use std::io;
 
+/// How often we will log the transfer progress of an in-flight clone at the debug level.
+const PROGRESS_LOG_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// The refspec of bare mirrors: every ref of the remote, as it is named there.
+const MIRROR_REFSPEC: &str = "+refs/*:refs/*";
+
+/// The refspecs of clones: every branch of the remote (as a remote-tracking branch) and every tag.
+const CLONE_REFSPECS: [&str; 2] = ["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"];
+
+/// This is what a worker sends back to the pool after successfully preparing a source.
+struct Staged {
+  /// Where the source should ultimately be placed.
+  destination: std::path::PathBuf,
+  /// Where the worker left the checkout, or `None` if it was updated in place and there is nothing
+  /// to place.
+  temp: Option<std::path::PathBuf>,
+  /// What we know about the source so far; placement timing is filled in by the pool.
+  report: report::SourceReport,
+}
+
+/// The configuration shared by every job of a single execution.
+struct JobContext {
+  /// The observers that will be notified of progress on our jobs.
+  observer: observer::Observers,
+  /// How stalled fetches should be handled, if at all.
+  stall: Option<watchdog::StallPolicy>,
+  /// Where destructive filesystem operations are recorded.
+  audit: audit::AuditLog,
+  /// The shared fetches of origins used by more than one source.
+  mirrors: mirror::Mirrors,
+  /// How objects are stored in the repositories we create.
+  storage: storage::StoragePolicy,
+  /// The number of threads used to write the working tree of each (non-sparse) source.
+  checkout_threads: usize,
+  /// When true, sources with an existing checkout at their destination are updated in place.
+  incremental: bool,
+  /// When provided, overrides the `clone-depth` of every source.
+  clone_depth: Option<u32>,
+  /// When true, the submodules of every source are checked out, regardless of its own `sync-s`.
+  submodules: bool,
+  /// When true, sources are mirrored into bare repositories rather than checked out.
+  bare_mirrors: bool,
+  /// When true, the `.git` of every checkout is removed once it has been placed.
+  strip_git: bool,
+  /// When true, checkouts updated in place are reset even if that loses local work.
+  discard_local_work: bool,
+  /// Limits how many jobs work on sources of the same remote host at once.
+  hosts: std::sync::Arc<throttle::HostThrottle<Job>>,
+  /// How long each job, and the execution as a whole, may take.
+  limits: watchdog::TimeLimits,
+  /// The credentials offered to remotes that ask for them.
+  credentials: credentials::Credentials,
+  /// The proxies remotes are fetched through.
+  proxies: proxy::Proxies,
+  /// How the certificates of https remotes are verified.
+  tls: tls::Tls,
+  /// Checked by workers between (and during) clones, and by the pool between placements.
+  cancel: cancel::CancellationToken,
+}
+
 /// During the execution subcommand, we will send instances of this types into background workers
 /// where they will perform their work.
 enum Job {
@@ -8,314 +72,1742 @@ enum Job {
   /// where it can tell us where the repo was cloned and where we should put it.
   Cloner {
     /// The sender of locations.
-    results: std::sync::mpsc::Sender<io::Result<(std::path::PathBuf, std::path::PathBuf)>>,
+    results: std::sync::mpsc::Sender<JobResult>,
     /// The layer we should clone.
     source: manifest::Source,
+    /// The span covering all work done for this source.
+    span: instrument::SourceSpan,
+    /// The configuration shared by all jobs of this execution.
+    context: std::sync::Arc<JobContext>,
+  },
+  /// Moves a staged checkout into its final destination. These are only sent once every clone is
+  /// complete, a level of the layer tree at a time.
+  Place {
+    /// Where the checkout should be placed.
+    destination: std::path::PathBuf,
+    /// Where the checkout currently is.
+    temp: std::path::PathBuf,
+    /// The span covering all work done for the source, if we have one.
+    span: Option<instrument::SourceSpan>,
+    /// The configuration shared by all jobs of this execution.
+    context: std::sync::Arc<JobContext>,
+    /// Where the outcome of the placement is sent.
+    results: std::sync::mpsc::Sender<Placed>,
+  },
+  /// Fetches the remote of a source into its mirror, without checking anything out; see
+  /// `WorkerPool::prefetch`.
+  Prefetch {
+    /// A source fetched from the remote.
+    source: manifest::Source,
+    /// The configuration shared by all jobs of this execution.
+    context: std::sync::Arc<JobContext>,
+    /// Where the outcome of the fetch is sent.
+    results: std::sync::mpsc::Sender<Result<(), error::SourceError>>,
   },
   /// This variant is used to signal termination.
   Terminate,
 }
 
-/// Our threadpool is based on this type, which is used to communicate from the threads we spawn
-/// back up into the main thread.
-#[derive(Debug)]
-enum WorkerEvent {
-  /// We will immediately send this type when the thread starts executing.
-  Online(String, std::sync::mpsc::Sender<Job>),
-  /// This variant is send when a worker finishes a job.
-  Idle(String),
+/// This is what a worker sends back to the pool after attempting a placement.
+struct Placed {
+  /// The destination that was being placed.
+  destination: std::path::PathBuf,
+  /// How long the placement took, if it succeeded.
+  elapsed: io::Result<std::time::Duration>,
 }
 
-/// This is the handle we will use in our pool to communicate with our spawned threads.
-struct WorkerHandle {
-  /// This is the pipe going into the spawned thread, where we will send it clone jobs.
-  jobs: std::sync::mpsc::Sender<Job>,
-  /// This is the thread's join handle so we can clean up nicely when we are done.
-  handle: std::thread::JoinHandle<()>,
+/// The receiving half of our job queue, shared by every worker. A worker only holds the lock while
+/// waiting for its next job, so whichever worker frees up first picks up the next job. Workers are
+/// never waited on individually: a job that panics is reported as the failure of its source, and
+/// should every worker be gone, sending to the queue fails rather than blocking.
+type JobQueue = std::sync::Arc<std::sync::Mutex<std::sync::mpsc::Receiver<Job>>>;
+
+/// What a worker sends back for every source it clones (or updates).
+type JobResult = Result<Staged, error::SourceError>;
+
+/// The options a `WorkerPool` is created from. Options are added here (with defaults) rather than
+/// as new parameters of `WorkerPool::create`, so that code using this type keeps compiling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PoolOptions {
+  /// The number of worker threads to spawn.
+  pub threads: usize,
+  /// The directory that the destinations of sources are relative to.
+  pub destination: std::path::PathBuf,
+  /// The directory sources are cloned into before being moved into `destination`. Defaults to a
+  /// `.repors-tmp` directory inside `destination`, so that moving them is a rename on one device.
+  pub temp_dir: Option<std::path::PathBuf>,
 }
 
+impl PoolOptions {
+  /// Options for a pool placing sources under `destination`, with three workers.
+  pub fn new(destination: std::path::PathBuf) -> Self {
+    Self {
+      threads: 3,
+      destination,
+      temp_dir: None,
+    }
+  }
+
+  /// Sets the number of worker threads to spawn.
+  pub fn with_threads(mut self, threads: usize) -> Self {
+    self.threads = threads;
+    self
+  }
+
+  /// Clones sources into (a directory created within) `dir` rather than into the destination.
+  pub fn with_temp_dir(mut self, dir: std::path::PathBuf) -> Self {
+    self.temp_dir = Some(dir);
+    self
+  }
+}
+
+/// The name of the directory, inside the destination, that sources are cloned into by default.
+const DEFAULT_TEMP_DIR: &str = ".repors-tmp";
+
 /// This is a container of threads.
+///
+/// The pool is deliberately thread based: every clone, fetch and checkout is a blocking libgit2
+/// call, so an async runtime would only hand each job to a pool of blocking threads of its own. Large
+/// manifests of small repositories are served by raising `PoolOptions::with_threads` instead, with
+/// `with_cancellation` for cooperative cancellation.
 pub struct WorkerPool {
-  /// For every worker, will will want to keep a unique id
-  workers: std::collections::HashMap<String, WorkerHandle>,
-  /// This is used for synchronizing state between the workers themselves and our pool.
-  events: std::sync::mpsc::Receiver<WorkerEvent>,
+  /// The configuration provided to every job we dispatch.
+  context: JobContext,
+  /// The root directory that the destinations of our sources are relative to.
+  root: std::path::PathBuf,
+  /// The directory our workers clone into, before the results are moved into `root`.
+  staging: std::path::PathBuf,
+  /// When true, the staging directory is left in place once we are done.
+  keep_temp: bool,
+  /// The default directory (inside `root`) holding our staging directory, removed with it when
+  /// nothing else is left in it. `None` when the staging directory was placed elsewhere.
+  default_temp: Option<std::path::PathBuf>,
+  /// The persistent directory remotes are fetched into (and borrowed from), if any.
+  reference: Option<std::path::PathBuf>,
+  /// For every worker, will will want to keep a unique id and its join handle so we can clean up
+  /// nicely when we are done.
+  workers: std::collections::HashMap<String, std::thread::JoinHandle<()>>,
+  /// The sending half of the queue our workers pull jobs from.
+  jobs: std::sync::mpsc::Sender<Job>,
   /// This is the channel we will clone senders for, providing them to the jobs passed to our
   /// workers. After sending all layers, we receive on the other half, creating our tree from the
   /// items received.
-  #[allow(clippy::type_complexity)]
   results: (
-    std::sync::mpsc::Sender<io::Result<(std::path::PathBuf, std::path::PathBuf)>>,
-    std::sync::mpsc::Receiver<io::Result<(std::path::PathBuf, std::path::PathBuf)>>,
+    std::sync::mpsc::Sender<JobResult>,
+    std::sync::mpsc::Receiver<JobResult>,
   ),
 }
 
-impl WorkerPool {
-  /// This method will attempt to spawn `amount` number of threads, registering themselves with the
-  /// returned pool which can then be used to `execute` against some manifest.
-  pub fn create(amount: usize, destination: std::path::PathBuf) -> io::Result<Self> {
-    let mut workers = std::collections::HashMap::new();
-    let (event_sender, events) = std::sync::mpsc::channel();
+/// Fetches the remote of `source` into its mirror (see `WorkerPool::prefetch`), notifying our
+/// observers as a job of its own.
+fn prefetch_one(
+  source: &manifest::Source,
+  context: &JobContext,
+  permit: Option<throttle::HostPermit<Job>>,
+) -> Result<(), error::SourceError> {
+  context.observer.job_started(source);
+  let expiry = context.limits.expiry(std::time::Instant::now());
+  let received = std::cell::Cell::new(0);
+  let fail = |phase, error| error::SourceError::new(&source.destination, source.origin(), phase, error);
 
-    std::fs::create_dir_all(&destination)?;
+  let fetched = match (
+    expiry.filter(watchdog::Expiry::passed),
+    context.mirrors.get(source.origin()),
+  ) {
+    (Some(expired), _) => Err(fail(error::Phase::Prepare, expired.error())),
+    (None, Some(mirror)) => {
+      log::debug!(source = source.destination.as_str(), phase = "clone"; "prefetching '{}'", redact::redact(source.origin()));
+      fetch_with(source, context, expiry, &received, |options| {
+        mirror.fetch(source.origin(), &context.storage, options)
+      })
+      .map_err(|error| fail(error::Phase::Clone, error))
+    }
+    (None, None) => Ok(()),
+  };
+  drop(permit);
+  context
+    .observer
+    .job_finished(source, fetched.as_ref().map(|_| ()));
+  fetched
+}
 
-    let mut temp_path = std::env::temp_dir();
-    temp_path.push(format!("repors-{}", uuid::Uuid::new_v4()));
+/// Applies the read timeout of network operations implied by the stall policy and time limits of
+/// `context`, if any. Our callbacks are not called while a read from the remote blocks, so hung
+/// remotes are only noticed through the timeout of reads. The timeout is global to the process;
+/// the one it replaced is returned, for `restore_server_timeout` once the execution is over.
+fn set_server_timeout(context: &JobContext) -> Option<i32> {
+  let stall = context.stall.and_then(|policy| policy.abort_after);
+  let abort_after = stall.into_iter().chain(context.limits.longest_wait()).min()?;
+  let millis = i32::try_from(abort_after.as_millis()).unwrap_or(i32::MAX).max(1);
 
-    for i in 0..amount {
-      let es = event_sender.clone();
-      let dp = destination.clone();
-      let tp = temp_path.clone();
+  // SAFETY: these read and modify a libgit2 global without synchronization; our workers are all
+  // idle at this point, so none of them can be in the middle of a libgit2 network operation.
+  let previous = match unsafe { git2::opts::get_server_timeout_in_milliseconds() } {
+    Ok(previous) => previous,
+    Err(error) => {
+      log::warn!("unable to read the server timeout, not applying {abort_after:?} - {error}");
+      return None;
+    }
+  };
+  if let Err(error) = unsafe { git2::opts::set_server_timeout_in_milliseconds(millis) } {
+    log::warn!("unable to apply server timeout of {abort_after:?} - {error}");
+    return None;
+  }
+  Some(previous)
+}
 
-      let handle = std::thread::spawn(move || {
-        let id = uuid::Uuid::new_v4().to_string();
-        let (job_sender, job_receiver) = std::sync::mpsc::channel();
+/// Puts back the server timeout `set_server_timeout` replaced, if it replaced one, so that it does
+/// not outlive the execution that needed it.
+fn restore_server_timeout(previous: Option<i32>) {
+  let Some(previous) = previous else {
+    return;
+  };
+  // SAFETY: as in `set_server_timeout`, our workers have all stopped by now.
+  if let Err(error) = unsafe { git2::opts::set_server_timeout_in_milliseconds(previous) } {
+    log::warn!("unable to restore server timeout of {previous}ms - {error}");
+  }
+}
 
-        if let Err(error) = es.send(WorkerEvent::Online(id.clone(), job_sender)) {
-          log::error!("unable to notify worker pool of readiness - {error:?}");
-          return;
-        }
+/// Admits `job` to run now, returning it with the permit it holds for the host of its source (if
+/// limited), or sets it aside until a job of the same host finishes (see `HostThrottle`). Jobs that
+/// cannot be admitted fail their source.
+fn admit(job: Job) -> Option<(Job, Option<throttle::HostPermit<Job>>)> {
+  let (origin, hosts) = match &job {
+    Job::Cloner { source, context, .. } | Job::Prefetch { source, context, .. } => {
+      (source.origin().to_string(), context.hosts.clone())
+    }
+    Job::Place { .. } | Job::Terminate => return Some((job, None)),
+  };
 
-        while let Ok(job) = job_receiver.recv() {
-          let Job::Cloner {
-            results: sender,
-            source,
-          } = job
-          else {
-            break;
-          };
+  match hosts.admit(&origin, job) {
+    Ok(throttle::Admission::Admitted(job, permit)) => Some((job, permit)),
+    Ok(throttle::Admission::Deferred) => None,
+    Err((job, error)) => {
+      job.fail(error);
+      None
+    }
+  }
+}
 
-          log::debug!("thread[{i}] doing job");
+impl Job {
+  /// Reports `error` as the failure of the source of this job, without running it.
+  fn fail(self, error: io::Error) {
+    let failure = |source: &manifest::Source| {
+      error::SourceError::new(&source.destination, source.origin(), error::Phase::Prepare, error)
+    };
+    let sent = match self {
+      Job::Cloner { results, source, .. } => results.send(Err(failure(&source))).is_ok(),
+      Job::Prefetch { results, source, .. } => results.send(Err(failure(&source))).is_ok(),
+      Job::Place { .. } | Job::Terminate => true,
+    };
+    if !sent {
+      log::error!("unable to send job failure");
+    }
+  }
+}
 
-          let mut source_path = dp.clone();
-          source_path.push(&source.destination);
+/// Tells every one of `workers` to stop, and waits for them to. Workers outlive failed jobs, so
+/// this happens however an execution ended, and to the workers started so far when a pool cannot
+/// start them all.
+fn stop_workers(
+  jobs: &std::sync::mpsc::Sender<Job>,
+  workers: &mut std::collections::HashMap<String, std::thread::JoinHandle<()>>,
+) {
+  for _ in 0..workers.len() {
+    if let Err(error) = jobs.send(Job::Terminate) {
+      log::warn!("unable to terminate worker: {error:?}");
+    }
+  }
 
-          let mut temp_dest = tp.clone();
-          temp_dest.push(uuid::Uuid::new_v4().to_string());
+  for (id, handle) in workers.drain() {
+    if let Err(error) = handle.join() {
+      log::error!("worker handle '{id}' did not close successfully: {error:?}");
+    }
+  }
+}
 
-          let origin = source.origin.clone();
+/// The error of work stopped by a `CancellationToken`.
+fn cancelled() -> io::Error {
+  io::Error::new(io::ErrorKind::Interrupted, "execution was cancelled")
+}
 
-          if let Err(error) = std::fs::create_dir_all(&temp_dest) {
-            log::warn!("failed preparing temp dir - {error:?}");
+/// Runs `work` (a clone or fetch of `source`) under the watchdog, with fetch options that report
+/// transfer progress to our observers and keep `received` up to date with the bytes received.
+fn fetch_with<F, T>(
+  source: &manifest::Source,
+  context: &JobContext,
+  expiry: Option<watchdog::Expiry>,
+  received: &std::cell::Cell<u64>,
+  work: F,
+) -> io::Result<T>
+where
+  F: FnOnce(git2::FetchOptions<'_>) -> Result<T, git2::Error>,
+{
+  watchdog::watch(context.stall.as_ref(), &source.destination, |heartbeat| {
+    let mut callbacks = git2::RemoteCallbacks::new();
+    let mut last_logged = std::time::Instant::now();
+    callbacks.transfer_progress(|stats| {
+      let progress = observer::TransferProgress::from(&stats);
+      heartbeat.beat(Some(progress.received_bytes));
+      received.set(progress.received_bytes as u64);
 
-            if let Err(error) = sender.send(Err(error)) {
-              log::warn!("worker failed to notify pool of error during execution - {error:?}");
-            }
+      let complete = progress.total_objects > 0 && progress.indexed_objects == progress.total_objects;
+      if last_logged.elapsed() >= PROGRESS_LOG_INTERVAL || complete {
+        log::debug!(
+          source = source.destination.as_str(), phase = "clone";
+          "received {}/{} objects ({} bytes), indexed {}",
+          progress.received_objects,
+          progress.total_objects,
+          progress.received_bytes,
+          progress.indexed_objects
+        );
+        last_logged = std::time::Instant::now();
+      }
 
-            return;
-          }
+      context.observer.job_progress(source, &progress);
+      heartbeat.alive() && !context.cancel.is_cancelled() && !expiry.is_some_and(|expiry| expiry.passed())
+    });
+    callbacks.credentials(context.credentials.callback());
+    context.tls.apply(&mut callbacks);
+    callbacks.sideband_progress(|_| {
+      heartbeat.beat(None);
+      heartbeat.alive() && !context.cancel.is_cancelled() && !expiry.is_some_and(|expiry| expiry.passed())
+    });
+    let mut fetch_options = git2::FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    fetch_options.proxy_options(context.proxies.options(source.origin()));
 
-          log::debug!("starting to clone '{source:?}' into '{temp_dest:?}'");
+    work(fetch_options).map_err(|error| {
+      log::warn!(source = source.destination.as_str(), phase = "clone"; "failed cloning - {}", redact::redact(error.message()));
 
-          let mut builder = git2::build::RepoBuilder::new();
+      if context.cancel.is_cancelled() {
+        return cancelled();
+      }
 
-          let clone_result = builder.clone(&origin, &temp_dest);
+      if let Some(expiry) = expiry.filter(watchdog::Expiry::passed) {
+        return expiry.error();
+      }
 
-          let repo = match clone_result {
-            Err(error) => {
-              log::warn!("failed cloning - {error:?}");
-              let wrapped_err = io::Error::new(io::ErrorKind::Other, error.to_string());
+      if heartbeat.aborted() {
+        return io::Error::new(
+          io::ErrorKind::TimedOut,
+          format!("fetch stalled - {}", redact::redact(error.message())),
+        );
+      }
 
-              if let Err(error) = sender.send(Err(wrapped_err)) {
-                log::warn!("worker failed to notify pool of error during execution - {error:?}");
-              }
+      io::Error::from(error::GitError::from(error))
+    })
+  })
+}
 
-              return;
-            }
-            Ok(repo) => repo,
-          };
+/// Clones just `depth` commits of the history of `source` into `temp_dest`, leaving the checkout to
+/// our caller. Full commit ids are fetched directly; other revisions are looked for within `depth`
+/// of the branches and tags of the remote.
+fn shallow_clone(
+  source: &manifest::Source,
+  depth: u32,
+  temp_dest: &std::path::Path,
+  mut options: git2::FetchOptions<'_>,
+) -> Result<git2::Repository, git2::Error> {
+  let repo = git2::Repository::init(temp_dest)?;
+  let mut remote = repo.remote("origin", source.origin())?;
+  options.depth(i32::try_from(depth).unwrap_or(i32::MAX));
 
-          log::debug!("clone complete in '{temp_dest:?}'");
+  let refspecs = if revision::is_commit_id(&source.revision) {
+    vec![source.revision.as_str()]
+  } else {
+    CLONE_REFSPECS.to_vec()
+  };
+  remote.fetch(&refspecs, Some(&mut options), None)?;
+  drop(remote);
 
-          let commit = match repo.find_commit_by_prefix(&source.revision) {
-            Ok(c) => c,
-            Err(error) => {
-              let wrapped_err = io::Error::new(io::ErrorKind::Other, error.to_string());
+  Ok(repo)
+}
 
-              if let Err(error) = sender.send(Err(wrapped_err)) {
-                log::warn!("worker failed to notify pool of error during execution - {error:?}");
-              }
+/// Clones `source` into `temp_dest` by fetching only the commit, branch or tag its revision names
+/// (see `revision::refspecs`), rather than every branch of the remote, which saves transferring the
+/// history of release branches the source does not use. When the revision turns out to be none of
+/// them, or the remote refuses to serve a commit that no ref points at (servers without
+/// `allow-reachable-sha1-in-want`), every branch and tag is fetched after all. Nothing is checked
+/// out.
+fn narrow_clone(
+  source: &manifest::Source,
+  refspecs: &[String],
+  temp_dest: &std::path::Path,
+  mut options: git2::FetchOptions<'_>,
+) -> Result<git2::Repository, git2::Error> {
+  let repo = git2::Repository::init(temp_dest)?;
+  let mut remote = repo.remote("origin", source.origin())?;
+  let fetched = match remote.fetch(refspecs, Some(&mut options), None) {
+    // Fetches aborted by our callbacks (cancelled, stalled or out of time) are not retried.
+    Err(error) if error.code() == git2::ErrorCode::User => return Err(error),
+    Err(error) => {
+      log::debug!(source = source.destination.as_str(), phase = "clone"; "unable to fetch only '{}' - {}", source.revision, redact::redact(error.message()));
+      false
+    }
+    Ok(()) => revision::resolve(&repo, &source.revision).is_ok(),
+  };
 
-              return;
-            }
-          };
+  if !fetched {
+    log::debug!(source = source.destination.as_str(), phase = "clone"; "fetching every branch for '{}'", source.revision);
+    remote.fetch(&CLONE_REFSPECS, Some(&mut options), None)?;
+  }
+  drop(remote);
 
-          log::debug!("pointing '{}' to {commit:?}", source.origin);
-          let oid = commit.as_object().id();
+  Ok(repo)
+}
 
-          if let Err(error) = repo.set_head_detached(oid) {
-            let wrapped_err = io::Error::new(io::ErrorKind::Other, error.to_string());
+/// Whether `origin` is on the local filesystem, which libgit2 cannot fetch shallowly from.
+fn is_local(origin: &str) -> bool {
+  origin.starts_with("file://") || std::path::Path::new(origin).exists()
+}
 
-            if let Err(error) = sender.send(Err(wrapped_err)) {
-              log::warn!("worker failed to notify pool of error during execution - {error:?}");
-            }
+/// Initializes and checks out every submodule of `repo` (a checkout of `source`) at the commit
+/// recorded for it, recursing into the submodules of submodules. The bytes fetched are added to
+/// `received`.
+fn update_submodules(
+  repo: &git2::Repository,
+  source: &manifest::Source,
+  context: &JobContext,
+  expiry: Option<watchdog::Expiry>,
+  received: &std::cell::Cell<u64>,
+) -> io::Result<()> {
+  let git_error = |error| io::Error::from(error::GitError::from(error));
+
+  for mut submodule in repo.submodules().map_err(git_error)? {
+    log::debug!(source = source.destination.as_str(), phase = "checkout"; "updating submodule '{}'", submodule.path().display());
+    let fetched = std::cell::Cell::new(0);
+    let updated = fetch_with(source, context, expiry, &fetched, |options| {
+      let mut update = git2::SubmoduleUpdateOptions::new();
+      update.fetch(options);
+      submodule.update(true, Some(&mut update))?;
+      submodule.open()
+    });
+    received.set(received.get() + fetched.get());
+
+    let nested = updated.map_err(|error| {
+      let path = submodule.path().display();
+      io::Error::new(
+        error.kind(),
+        format!("unable to update submodule '{path}' - {error}"),
+      )
+    })?;
+    update_submodules(&nested, source, context, expiry, received)?;
+  }
+
+  Ok(())
+}
+
+/// What a worker learned while cloning (or updating) a source.
+struct Prepared {
+  /// The time spent in each phase.
+  timings: report::PhaseTimings,
+  /// The number of bytes received from the remote.
+  received_bytes: u64,
+  /// The commit the revision of the source resolved to.
+  commit: git2::Oid,
+  /// What had to be done.
+  outcome: report::SourceOutcome,
+}
+
+/// Times the phases of the work done for a single source, telling the observers of a `JobContext`
+/// about them, and ties the failures of that work to the source.
+struct SourcePhases<'a> {
+  /// The source being worked on.
+  source: &'a manifest::Source,
+  /// The span covering all work done for the source.
+  span: &'a instrument::SourceSpan,
+  /// The configuration of the job, whose observers are notified.
+  context: &'a JobContext,
+}
 
-            return;
+impl SourcePhases<'_> {
+  /// Enters `phase`, until the returned guard is passed to `finish`.
+  fn begin(&self, phase: error::Phase) -> instrument::PhaseGuard {
+    self.context.observer.phase_started(self.source, phase);
+    self.span.phase(phase)
+  }
+
+  /// Ends the phase of `guard`, returning how long it took.
+  fn finish(&self, guard: instrument::PhaseGuard) -> std::time::Duration {
+    let phase = guard.phase();
+    let elapsed = guard.end();
+    self.context.observer.phase_finished(self.source, phase, elapsed);
+    elapsed
+  }
+
+  /// The failure of the source during `phase`, because of `error`.
+  fn fail(&self, phase: error::Phase, error: io::Error) -> error::SourceError {
+    error::SourceError::new(&self.source.destination, self.source.origin(), phase, error)
+  }
+
+  /// The failure of the source during `phase`, because of the libgit2 `error`.
+  fn git_fail(&self, phase: error::Phase, error: git2::Error) -> error::SourceError {
+    self.fail(phase, io::Error::from(error::GitError::from(error)))
+  }
+}
+
+impl JobContext {
+  /// The phases of the work done for `source`, whose span is `span`.
+  fn phases<'a>(
+    &'a self,
+    source: &'a manifest::Source,
+    span: &'a instrument::SourceSpan,
+  ) -> SourcePhases<'a> {
+    SourcePhases {
+      source,
+      span,
+      context: self,
+    }
+  }
+}
+
+/// Performs the git operations for a single source, leaving a checkout of the requested revision at
+/// `temp_dest`.
+fn clone_source(
+  source: &manifest::Source,
+  temp_dest: &std::path::Path,
+  span: &instrument::SourceSpan,
+  context: &JobContext,
+  expiry: Option<watchdog::Expiry>,
+) -> Result<Prepared, error::SourceError> {
+  let mut timings = report::PhaseTimings::default();
+  let received = std::cell::Cell::new(0);
+  let parallel = context.checkout_threads > 1 && source.sparse.is_empty();
+  let phases = context.phases(source, span);
+
+  if let Err(error) = context.audit.create_dir_all(temp_dest) {
+    log::warn!(source = source.destination.as_str(), phase = "prepare"; "failed preparing temp dir - {error:?}");
+    return Err(phases.fail(error::Phase::Prepare, error));
+  }
+
+  log::debug!(source = source.destination.as_str(), phase = "clone"; "starting to clone '{}' into '{temp_dest:?}'", redact::redact(source.origin()));
+
+  let clone_phase = phases.begin(error::Phase::Clone);
+  let depth = context
+    .clone_depth
+    .or(source.clone_depth)
+    .filter(|_| !is_local(source.origin()));
+  if depth.is_none() && context.clone_depth.or(source.clone_depth).is_some() {
+    log::debug!(source = source.destination.as_str(), phase = "clone"; "ignoring clone depth of local remote");
+  }
+
+  let cloned = match context.mirrors.get(source.origin()).filter(|_| depth.is_none()) {
+    Some(mirror) => mirror
+      .ensure(|| fetch_with(source, context, expiry, &received, |options| mirror.fetch(source.origin(), &context.storage, options)))
+      .and_then(|_| {
+        log::debug!(source = source.destination.as_str(), phase = "clone"; "borrowing objects from shared fetch");
+        mirror.attach(source.origin(), temp_dest)
+      }),
+    None => fetch_with(source, context, expiry, &received, |options| {
+      if let Some(depth) = depth {
+        log::debug!(source = source.destination.as_str(), phase = "clone"; "fetching {depth} commit(s) of history");
+        return shallow_clone(source, depth, temp_dest, options);
+      }
+      if let Some(refspecs) = revision::refspecs(&source.revision) {
+        log::debug!(source = source.destination.as_str(), phase = "clone"; "fetching only '{}'", source.revision);
+        return narrow_clone(source, &refspecs, temp_dest, options);
+      }
+
+      let mut builder = git2::build::RepoBuilder::new();
+      builder.fetch_options(options);
+      if !source.sparse.is_empty() {
+        builder.with_checkout(sparse::checkout_options(&source.sparse));
+      }
+      if parallel {
+        // The working tree is written by our own checkout once the revision has been resolved.
+        let mut options = git2::build::CheckoutBuilder::new();
+        options.dry_run();
+        builder.with_checkout(options);
+      }
+      builder.clone(source.origin(), temp_dest)
+    }),
+  };
+  let repo = cloned.map_err(|error| phases.fail(error::Phase::Clone, error))?;
+  timings.clone = phases.finish(clone_phase);
+
+  log::debug!(source = source.destination.as_str(), phase = "clone"; "clone complete in '{temp_dest:?}'");
+
+  let resolve_phase = phases.begin(error::Phase::Resolve);
+  let commit = revision::resolve(&repo, &source.revision)
+    .map_err(|error| phases.git_fail(error::Phase::Resolve, error))?;
+  timings.resolve = phases.finish(resolve_phase);
+
+  log::debug!(source = source.destination.as_str(), phase = "resolve"; "pointing '{}' to {commit:?}", redact::redact(source.origin()));
+  let oid = commit.as_object().id();
+  if context.cancel.is_cancelled() {
+    return Err(phases.fail(error::Phase::Checkout, cancelled()));
+  }
+
+  let checkout_phase = phases.begin(error::Phase::Checkout);
+
+  repo
+    .set_head_detached(oid)
+    .map_err(|error| phases.git_fail(error::Phase::Checkout, error))?;
+
+  log::debug!(source = source.destination.as_str(), phase = "checkout"; "'{}' was updated to '{}'", redact::redact(source.origin()), source.revision);
+
+  let checked_out = if parallel {
+    log::debug!(source = source.destination.as_str(), phase = "checkout"; "checking out on {} threads", context.checkout_threads);
+    commit
+      .tree()
+      .and_then(|tree| checkout::parallel(&repo, &tree, context.checkout_threads))
+  } else {
+    repo
+      .checkout_head(Some(&mut sparse::checkout_options(&source.sparse)))
+      .and_then(|_| {
+        repo.reset(
+          commit.as_object(),
+          git2::ResetType::Hard,
+          Some(&mut sparse::checkout_options(&source.sparse)),
+        )
+      })
+  };
+
+  checked_out.map_err(|error| {
+    log::warn!(source = source.destination.as_str(), phase = "checkout"; "'{}' failed checkout - {error:?}", redact::redact(source.origin()));
+    phases.git_fail(error::Phase::Checkout, error)
+  })?;
+
+  if !source.sparse.is_empty() {
+    log::debug!(source = source.destination.as_str(), phase = "checkout"; "limiting checkout to {:?}", source.sparse);
+    sparse::persist(&repo, &source.sparse).map_err(|error| phases.git_fail(error::Phase::Checkout, error))?;
+  }
+
+  if source.sync_submodules || context.submodules {
+    update_submodules(&repo, source, context, expiry, &received)
+      .map_err(|error| phases.fail(error::Phase::Checkout, error))?;
+  }
+
+  context
+    .storage
+    .apply(&repo)
+    .map_err(|error| phases.git_fail(error::Phase::Checkout, error))?;
+
+  timings.checkout = phases.finish(checkout_phase);
+  log::debug!(source = source.destination.as_str(), phase = "checkout"; "'{}' checkout complete", redact::redact(source.origin()));
+
+  Ok(Prepared {
+    timings,
+    received_bytes: received.get(),
+    commit: oid,
+    outcome: report::SourceOutcome::Cloned,
+  })
+}
+
+/// Brings the existing checkout of `source` at `path` to the manifest revision. Nothing is fetched
+/// when the checkout already has the revision; otherwise the branches and tags of the remote are
+/// fetched, which only transfers the objects the checkout is missing.
+fn update_source(
+  source: &manifest::Source,
+  path: &std::path::Path,
+  span: &instrument::SourceSpan,
+  context: &JobContext,
+  expiry: Option<watchdog::Expiry>,
+) -> Result<Prepared, error::SourceError> {
+  let mut timings = report::PhaseTimings::default();
+  let received = std::cell::Cell::new(0);
+  let phases = context.phases(source, span);
+
+  let repo = git2::Repository::open(path).map_err(|error| phases.git_fail(error::Phase::Prepare, error))?;
+
+  // Branches and tags may have moved since the checkout was made, so only commit ids we already
+  // have spare us the fetch.
+  let clone_phase = phases.begin(error::Phase::Clone);
+  if !revision::is_commit_prefix(&source.revision) || repo.find_commit_by_prefix(&source.revision).is_err() {
+    log::debug!(source = source.destination.as_str(), phase = "clone"; "fetching '{}' into '{path:?}'", redact::redact(source.origin()));
+    fetch_with(source, context, expiry, &received, |mut options| {
+      let mut remote = repo.remote_anonymous(source.origin())?;
+      remote.fetch(&CLONE_REFSPECS, Some(&mut options), None)
+    })
+    .map_err(|error| phases.fail(error::Phase::Clone, error))?;
+  }
+  timings.clone = phases.finish(clone_phase);
+
+  let resolve_phase = phases.begin(error::Phase::Resolve);
+  let commit = revision::resolve(&repo, &source.revision)
+    .map_err(|error| phases.git_fail(error::Phase::Resolve, error))?;
+  timings.resolve = phases.finish(resolve_phase);
+
+  let head = repo.head().ok().and_then(|head| head.target());
+  if head == Some(commit.id()) {
+    log::debug!(source = source.destination.as_str(), phase = "checkout"; "already at '{}'", source.revision);
+    return Ok(Prepared {
+      timings,
+      received_bytes: received.get(),
+      commit: commit.id(),
+      outcome: report::SourceOutcome::Skipped,
+    });
+  }
+
+  if context.cancel.is_cancelled() {
+    return Err(phases.fail(error::Phase::Checkout, cancelled()));
+  }
+
+  if !context.discard_local_work {
+    if let Some(work) = status::unsaved_in(&repo) {
+      let message = format!("the checkout {work}, which updating it would discard");
+      return Err(phases.fail(error::Phase::Checkout, io::Error::other(message)));
+    }
+  }
+
+  let checkout_phase = phases.begin(error::Phase::Checkout);
+  repo
+    .set_head_detached(commit.id())
+    .and_then(|_| {
+      repo.reset(
+        commit.as_object(),
+        git2::ResetType::Hard,
+        Some(&mut sparse::checkout_options(&source.sparse)),
+      )
+    })
+    .map_err(|error| phases.git_fail(error::Phase::Checkout, error))?;
+  if !source.sparse.is_empty() {
+    sparse::persist(&repo, &source.sparse).map_err(|error| phases.git_fail(error::Phase::Checkout, error))?;
+  }
+  if source.sync_submodules || context.submodules {
+    update_submodules(&repo, source, context, expiry, &received)
+      .map_err(|error| phases.fail(error::Phase::Checkout, error))?;
+  }
+  timings.checkout = phases.finish(checkout_phase);
+
+  log::debug!(source = source.destination.as_str(), phase = "checkout"; "'{}' was updated in place to '{}'", redact::redact(source.origin()), source.revision);
+  Ok(Prepared {
+    timings,
+    received_bytes: received.get(),
+    commit: commit.id(),
+    outcome: report::SourceOutcome::Updated,
+  })
+}
+
+/// Creates a bare repository at `path` whose `origin` remote is configured like that of `git clone
+/// --mirror`, so that plain `git fetch` keeps it up to date as well.
+fn init_mirror(path: &std::path::Path, origin: &str) -> Result<git2::Repository, git2::Error> {
+  let repo = git2::Repository::init_bare(path)?;
+  repo.remote_with_fetch("origin", origin, MIRROR_REFSPEC)?;
+  repo.config()?.set_bool("remote.origin.mirror", true)?;
+  Ok(repo)
+}
+
+/// Fetches every ref of the remote of `source` into the bare repository at `path`, which is created
+/// first when `fresh`, and resolves the revision of the source within it.
+fn mirror_source(
+  source: &manifest::Source,
+  path: &std::path::Path,
+  fresh: bool,
+  span: &instrument::SourceSpan,
+  context: &JobContext,
+  expiry: Option<watchdog::Expiry>,
+) -> Result<Prepared, error::SourceError> {
+  let mut timings = report::PhaseTimings::default();
+  let received = std::cell::Cell::new(0);
+  let phases = context.phases(source, span);
+
+  if fresh {
+    context
+      .audit
+      .create_dir_all(path)
+      .map_err(|error| phases.fail(error::Phase::Prepare, error))?;
+  }
+  let repo = if fresh {
+    init_mirror(path, source.origin())
+  } else {
+    git2::Repository::open_bare(path)
+  }
+  .map_err(|error| phases.git_fail(error::Phase::Prepare, error))?;
+
+  log::debug!(source = source.destination.as_str(), phase = "clone"; "mirroring '{}' into '{path:?}'", redact::redact(source.origin()));
+  let clone_phase = phases.begin(error::Phase::Clone);
+  fetch_with(source, context, expiry, &received, |mut options| {
+    options.prune(git2::FetchPrune::On);
+    let mut remote = repo.remote_anonymous(source.origin())?;
+    remote.fetch(&[MIRROR_REFSPEC], Some(&mut options), None)?;
+
+    // Point HEAD at the default branch of the remote, like `git clone --mirror` does.
+    if let Ok(branch) = remote.default_branch() {
+      if let Some(branch) = branch.as_str() {
+        repo.set_head(branch)?;
+      }
+    }
+    Ok(())
+  })
+  .map_err(|error| phases.fail(error::Phase::Clone, error))?;
+  timings.clone = phases.finish(clone_phase);
+
+  let resolve_phase = phases.begin(error::Phase::Resolve);
+  let commit = revision::resolve(&repo, &source.revision)
+    .map_err(|error| phases.git_fail(error::Phase::Resolve, error))?;
+  timings.resolve = phases.finish(resolve_phase);
+
+  context
+    .storage
+    .apply(&repo)
+    .map_err(|error| phases.git_fail(error::Phase::Clone, error))?;
+
+  Ok(Prepared {
+    timings,
+    received_bytes: received.get(),
+    commit: commit.id(),
+    outcome: if fresh {
+      report::SourceOutcome::Cloned
+    } else {
+      report::SourceOutcome::Updated
+    },
+  })
+}
+
+/// Moves every staged checkout into its final destination by dispatching placements to our workers,
+/// one level of the layer tree at a time, returning the completed report of each source.
+fn place(
+  jobs: &std::sync::mpsc::Sender<Job>,
+  context: &std::sync::Arc<JobContext>,
+  levels: Vec<Vec<(std::path::PathBuf, std::path::PathBuf)>>,
+  mut spans: std::collections::HashMap<std::path::PathBuf, instrument::SourceSpan>,
+  mut reports: std::collections::HashMap<std::path::PathBuf, report::SourceReport>,
+) -> Result<Vec<report::SourceReport>, error::Error> {
+  let mut placed = Vec::with_capacity(reports.len());
+
+  for level in levels {
+    if context.cancel.is_cancelled() {
+      return Err(error::Error::Cancelled);
+    }
+
+    let (results, outcomes) = std::sync::mpsc::channel();
+
+    for (destination, temp) in level {
+      let job = Job::Place {
+        span: spans.remove(&destination),
+        destination,
+        temp,
+        context: context.clone(),
+        results: results.clone(),
+      };
+
+      if jobs.send(job).is_err() {
+        return Err(error::Error::Worker(io::Error::other(
+          "no workers available to place sources",
+        )));
+      }
+    }
+
+    drop(results);
+
+    // Every placement of the level is allowed to finish before we report the first failure.
+    let mut failure = None;
+    for Placed { destination, elapsed } in outcomes {
+      match elapsed {
+        Ok(elapsed) => {
+          if let Some(mut report) = reports.remove(&destination) {
+            report.timings.place = elapsed;
+            placed.push(report);
           }
+        }
+        Err(error) => {
+          let (name, origin) = reports
+            .get(&destination)
+            .map(|report| (report.destination.clone(), report.origin.clone()))
+            .unwrap_or_else(|| (destination.display().to_string(), String::default()));
+          let error = error::SourceError::new(&name, &origin, error::Phase::Place, error);
+          failure.get_or_insert(io::Error::from(error));
+        }
+      }
+    }
+
+    if let Some(error) = failure {
+      return Err(error::Error::Placement(error));
+    }
+  }
+
+  Ok(placed)
+}
+
+/// Moves a single staged checkout into its final destination, returning how long it took.
+fn place_one(
+  context: &JobContext,
+  destination: &std::path::Path,
+  temp: &std::path::Path,
+  span: Option<instrument::SourceSpan>,
+) -> io::Result<std::time::Duration> {
+  let place_phase = span.map(|span| span.phase(error::Phase::Place));
+  context.observer.placement_started(destination);
+  log::debug!(phase = "place"; "moving '{temp:?}' to '{destination:?}'");
+  // Only the parent is created: renaming a directory onto an existing (even empty) one fails on
+  // windows.
+  if let Some(parent) = destination.parent() {
+    context.audit.create_dir_all(parent)?;
+  }
+  move_tree(temp, destination, &context.audit)?;
+  context.observer.placement_finished(destination);
+
+  Ok(place_phase.map(instrument::PhaseGuard::end).unwrap_or_default())
+}
+
+/// Removes the `.git` of the checkout at `path`, and those of its checked out submodules (whose
+/// repositories live within it), leaving only the files of its working tree.
+fn strip_git(path: &std::path::Path, audit: &audit::AuditLog) -> io::Result<()> {
+  if let Ok(repo) = git2::Repository::open(path) {
+    let submodules = repo
+      .submodules()
+      .map_err(|error| io::Error::from(error::GitError::from(error)))?;
+    for submodule in submodules {
+      let nested = path.join(submodule.path());
+      if nested.join(".git").exists() {
+        strip_git(&nested, audit)?;
+      }
+    }
+  }
+
+  let git = path.join(".git");
+  log::debug!(phase = "place"; "removing '{git:?}'");
+  match std::fs::symlink_metadata(&git) {
+    Ok(metadata) if metadata.is_dir() => audit.remove_dir_all(&git),
+    Ok(_) => audit.remove_file(&git),
+    Err(_) => Ok(()),
+  }
+  .map_err(|error| {
+    io::Error::new(
+      error.kind(),
+      format!("unable to remove '{}' - {error}", git.display()),
+    )
+  })
+}
+
+/// Performs the `<copyfile>` elements of the placed sources; `copies` pairs each with the root of the
+/// project it belongs to.
+fn copy_files(
+  root: &std::path::Path,
+  copies: &[(std::path::PathBuf, manifest::CopyFile)],
+  audit: &audit::AuditLog,
+) -> io::Result<()> {
+  for (project, copy) in copies {
+    let from = project.join(manifest::relative_path(&copy.src));
+    let to = root.join(manifest::relative_path(&copy.dest));
+    log::debug!(phase = "place"; "copying '{from:?}' to '{to:?}'");
+
+    let copied = match to.parent() {
+      Some(parent) => audit.create_dir_all(parent),
+      None => Ok(()),
+    }
+    .and_then(|()| audit.copy(&from, &to));
+
+    if let Err(error) = copied {
+      return Err(io::Error::new(
+        error.kind(),
+        format!(
+          "unable to copy '{}' to '{}' - {error}",
+          from.display(),
+          to.display()
+        ),
+      ));
+    }
+  }
+
+  Ok(())
+}
+
+/// Creates the symlinks named by the `<linkfile>` elements of the placed sources; `links` pairs each
+/// with the manifest path of the project it belongs to. Links are relative, so that the destination
+/// keeps working if it is moved as a whole. On windows, where creating symlinks usually requires
+/// extra privileges, the linked file (or directory) is copied instead if the link cannot be made.
+fn link_files(
+  root: &std::path::Path,
+  links: &[(String, manifest::LinkFile)],
+  audit: &audit::AuditLog,
+) -> io::Result<()> {
+  for (project, link) in links {
+    let dest = manifest::relative_path(&link.dest);
+    let depth = dest.components().count().saturating_sub(1);
+    let source = manifest::relative_path(project).join(manifest::relative_path(&link.src));
+    let target = std::iter::repeat_n(std::path::Path::new(".."), depth)
+      .collect::<std::path::PathBuf>()
+      .join(&source);
+    let to = root.join(dest);
+    log::debug!(phase = "place"; "linking '{to:?}' to '{target:?}'");
+
+    let linked = match to.parent() {
+      Some(parent) => audit.create_dir_all(parent),
+      None => Ok(()),
+    }
+    .and_then(|()| {
+      // Links left by a previous execution (of an incrementally updated destination) are replaced.
+      match std::fs::symlink_metadata(&to) {
+        Ok(metadata) if metadata.file_type().is_symlink() => audit.remove_file(&to),
+        _ => Ok(()),
+      }
+    })
+    .and_then(|()| audit.symlink(&target, &to));
+
+    #[cfg(windows)]
+    let linked = linked.or_else(|error| {
+      log::warn!("unable to link '{to:?}' ({error}), copying '{source:?}' instead");
+      copy_tree(&root.join(&source), &to, audit)
+    });
+
+    if let Err(error) = linked {
+      return Err(io::Error::new(
+        error.kind(),
+        format!(
+          "unable to link '{}' to '{}' - {error}",
+          to.display(),
+          target.display()
+        ),
+      ));
+    }
+  }
+
+  Ok(())
+}
 
-          log::debug!("'{}' was updated to '{}'", source.origin, source.revision);
+/// Renames `from` to `to`, copying it over (and removing it) instead when the two are on different
+/// devices, where renaming fails (with `EXDEV`).
+fn move_tree(from: &std::path::Path, to: &std::path::Path, audit: &audit::AuditLog) -> io::Result<()> {
+  match audit.rename(from, to) {
+    Err(error) if error.kind() == io::ErrorKind::CrossesDevices => {
+      log::info!(phase = "place"; "'{from:?}' and '{to:?}' are on different devices, copying instead");
+      copy_across(from, to, audit)
+    }
+    moved => moved,
+  }
+}
 
-          if let Err(error) = repo.checkout_head(None) {
-            let wrapped_err = io::Error::new(io::ErrorKind::Other, error.to_string());
+/// Copies the tree at `from` to `to` and removes `from`. If the copy fails, whatever was copied so
+/// far is removed again, leaving `from` where it was.
+pub(crate) fn copy_across(
+  from: &std::path::Path,
+  to: &std::path::Path,
+  audit: &audit::AuditLog,
+) -> io::Result<()> {
+  if let Err(error) = copy_tree(from, to, audit) {
+    if to.exists() {
+      if let Err(cleanup) = audit.remove_dir_all(to) {
+        log::warn!("unable to remove partial copy '{to:?}' - {cleanup}");
+      }
+    }
+    return Err(error);
+  }
 
-            if let Err(error) = sender.send(Err(wrapped_err)) {
-              log::warn!("worker failed to notify pool of error during execution - {error:?}");
+  audit.remove_dir_all(from)
+}
+
+/// Copies `from` to `to`, recursively if it is a directory; used where links cannot be created, and
+/// where trees cannot be renamed. Symlinks are copied as links on unix, and followed elsewhere.
+fn copy_tree(from: &std::path::Path, to: &std::path::Path, audit: &audit::AuditLog) -> io::Result<()> {
+  #[cfg(unix)]
+  if std::fs::symlink_metadata(from)?.file_type().is_symlink() {
+    return audit.symlink(&std::fs::read_link(from)?, to);
+  }
+
+  if !from.is_dir() {
+    return audit.copy(from, to);
+  }
+
+  audit.create_dir_all(to)?;
+  for entry in std::fs::read_dir(from)? {
+    let entry = entry?;
+    copy_tree(&entry.path(), &to.join(entry.file_name()), audit)?;
+  }
+
+  Ok(())
+}
+
+/// Describes the payload of a panic, which is usually the message it was raised with.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+  payload
+    .downcast_ref::<&str>()
+    .map(|message| message.to_string())
+    .or_else(|| payload.downcast_ref::<String>().cloned())
+    .unwrap_or_else(|| "it panicked".to_string())
+}
+
+/// Removes our staging directory once we are done with it, unless asked to keep it around. The
+/// directory holding it is removed too when it is the default one, unless other executions are
+/// still using it.
+fn finish_staging(
+  staging: &std::path::Path,
+  default_temp: Option<&std::path::Path>,
+  keep: bool,
+  audit: &audit::AuditLog,
+) {
+  if keep {
+    log::info!("keeping staging directory '{staging:?}'");
+    return;
+  }
+
+  if staging.exists() {
+    if let Err(error) = audit.remove_dir_all(staging) {
+      log::warn!("unable to clean up staging directory '{staging:?}' - {error}");
+    }
+  }
+
+  let Some(parent) = default_temp.filter(|parent| parent.exists()) else {
+    return;
+  };
+
+  let empty = std::fs::read_dir(parent).is_ok_and(|mut entries| entries.next().is_none());
+  if empty {
+    if let Err(error) = audit.remove_dir(parent) {
+      log::warn!("unable to clean up temporary directory '{parent:?}' - {error}");
+    }
+  }
+}
+
+impl WorkerPool {
+  /// This method will attempt to spawn `amount` number of threads, all pulling jobs from the queue
+  /// of the returned pool, which can then be used to `execute` against some manifest.
+  pub fn create(amount: usize, destination: std::path::PathBuf) -> Result<Self, error::Error> {
+    Self::from_options(PoolOptions::new(destination).with_threads(amount))
+  }
+
+  /// Spawns the workers of a pool configured by `options`; see `create`.
+  pub fn from_options(options: PoolOptions) -> Result<Self, error::Error> {
+    let PoolOptions {
+      threads: amount,
+      destination,
+      temp_dir,
+    } = options;
+    let mut workers = std::collections::HashMap::new();
+    let (jobs, job_receiver) = std::sync::mpsc::channel();
+    let queue: JobQueue = std::sync::Arc::new(std::sync::Mutex::new(job_receiver));
+
+    std::fs::create_dir_all(&destination)?;
+
+    let (temp_root, default_temp) = match temp_dir {
+      Some(dir) => (dir, None),
+      None => {
+        let dir = destination.join(DEFAULT_TEMP_DIR);
+        (dir.clone(), Some(dir))
+      }
+    };
+    let temp_path = temp_root.join(format!("repors-{}", uuid::Uuid::new_v4()));
+
+    for i in 0..amount {
+      let dp = destination.clone();
+      let tp = temp_path.clone();
+      let queue = queue.clone();
+
+      let handle = std::thread::Builder::new()
+        .name(format!("repors-worker-{i}"))
+        .spawn(move || {
+          let id = std::thread::current().name().unwrap_or_default().to_string();
+
+          loop {
+            // The guard is dropped at the end of this statement; we do not hold the queue while working.
+            let next = queue.lock().map(|jobs| jobs.recv());
+
+            let Ok(Ok(job)) = next else {
+              break;
+            };
+
+            let Some((job, permit)) = admit(job) else {
+              continue;
+            };
+
+            let (sender, source, span, context) = match job {
+              Job::Cloner {
+                results,
+                source,
+                span,
+                context,
+              } => (results, source, span, context),
+              Job::Place {
+                destination,
+                temp,
+                span,
+                context,
+                results,
+              } => {
+                let placing = std::panic::AssertUnwindSafe(|| place_one(&context, &destination, &temp, span));
+                let elapsed = std::panic::catch_unwind(placing).unwrap_or_else(|payload| {
+                  let message = format!("placement panicked - {}", panic_message(payload.as_ref()));
+                  Err(io::Error::other(message))
+                });
+                if let Err(error) = results.send(Placed { destination, elapsed }) {
+                  log::error!("unable to send placement result - {error:?}, terminating worker");
+                  break;
+                }
+                continue;
+              }
+              Job::Prefetch {
+                source,
+                context,
+                results,
+              } => {
+                let prefetching = std::panic::AssertUnwindSafe(|| prefetch_one(&source, &context, permit));
+                let fetched = std::panic::catch_unwind(prefetching).unwrap_or_else(|payload| {
+                  let message = format!("the worker panicked - {}", panic_message(payload.as_ref()));
+                  Err(error::SourceError::new(
+                    &source.destination,
+                    source.origin(),
+                    error::Phase::Clone,
+                    io::Error::other(message),
+                  ))
+                });
+                if let Err(error) = results.send(fetched) {
+                  log::error!("unable to send prefetch result - {error:?}, terminating worker");
+                  break;
+                }
+                continue;
+              }
+              Job::Terminate => break,
+            };
+
+            log::debug!("thread[{i}] doing job");
+
+            // Dropping the job (and its sender) is all the pool needs to hear of it.
+            if context.cancel.is_cancelled() {
+              log::debug!("thread[{i}] skipping job of cancelled execution");
+              continue;
             }
 
-            return;
-          }
+            let mut source_path = dp.clone();
+            source_path.push(source.relative_path());
+
+            let mut temp_dest = tp.clone();
+            temp_dest.push(uuid::Uuid::new_v4().to_string());
 
-          if let Err(error) = repo.reset(commit.as_object(), git2::ResetType::Hard, None) {
-            log::warn!("'{}' failed checkout - {error:?}", source.origin);
-            let wrapped_err = io::Error::new(io::ErrorKind::Other, error.to_string());
-            if let Err(error) = sender.send(Err(wrapped_err)) {
-              log::warn!("worker failed to notify pool of error during execution - {error:?}");
+            // Only checkouts of their own are updated, not directories that happen to be inside
+            // of another source's checkout.
+            let marker = if context.bare_mirrors { "HEAD" } else { ".git" };
+            let existing = context.incremental && source_path.join(marker).exists();
+
+            context.observer.job_started(&source);
+            let expiry = context.limits.expiry(std::time::Instant::now());
+            let expired = expiry.filter(watchdog::Expiry::passed);
+            let preparing =
+              std::panic::AssertUnwindSafe(|| match (expired, context.bare_mirrors, existing) {
+                // Sources started once the deadline passed are not even tried.
+                (Some(expired), _, _) => Err(error::SourceError::new(
+                  &source.destination,
+                  source.origin(),
+                  error::Phase::Prepare,
+                  expired.error(),
+                )),
+                (None, true, true) => mirror_source(&source, &source_path, false, &span, &context, expiry),
+                (None, true, false) => mirror_source(&source, &temp_dest, true, &span, &context, expiry),
+                (None, false, true) => update_source(&source, &source_path, &span, &context, expiry),
+                (None, false, false) => clone_source(&source, &temp_dest, &span, &context, expiry),
+              });
+            // A job that panics fails its source alone; we stay available for the jobs that follow.
+            let outcome = std::panic::catch_unwind(preparing).unwrap_or_else(|payload| {
+              let message = format!("the worker panicked - {}", panic_message(payload.as_ref()));
+              Err(error::SourceError::new(
+                &source.destination,
+                source.origin(),
+                error::Phase::Prepare,
+                io::Error::other(message),
+              ))
+            });
+            drop(permit);
+            if let Ok(prepared) = &outcome {
+              context.observer.checkout_finished(&source, prepared.commit);
             }
-            return;
-          }
+            context
+              .observer
+              .job_finished(&source, outcome.as_ref().map(|_| ()));
 
-          log::debug!("'{} checkout complete", source.origin);
+            let prepared = match outcome {
+              Ok(prepared) => prepared,
+              Err(error) => {
+                // The failure is the source's alone; we stay available for the jobs that follow.
+                if let Err(error) = sender.send(Err(error)) {
+                  log::error!("unable to send job failure - {error:?}, terminating worker");
+                  break;
+                }
 
-          if let Err(error) = sender.send(Ok((source_path, temp_dest))) {
-            log::error!("unable to send job execution result - {error:?}, terminating worker");
-            break;
-          }
+                continue;
+              }
+            };
+
+            let (temp, staging) = if existing {
+              (None, source_path.clone())
+            } else {
+              (Some(temp_dest.clone()), temp_dest)
+            };
+            let staged = Staged {
+              destination: source_path.clone(),
+              temp,
+              report: report::SourceReport {
+                origin: redact::redact(source.origin()).into_owned(),
+                destination: source.destination,
+                path: source_path,
+                staging,
+                commit: prepared.commit,
+                outcome: prepared.outcome,
+                timings: prepared.timings,
+                received_bytes: prepared.received_bytes,
+              },
+            };
 
-          if let Err(error) = es.send(WorkerEvent::Idle(id.clone())) {
-            log::error!("unable to worker availability, terminating worker ({error:?})");
-            break;
+            if let Err(error) = sender.send(Ok(staged)) {
+              log::error!("unable to send job execution result - {error:?}, terminating worker");
+              break;
+            }
           }
-        }
 
-        log::info!("worker '{id}' terminating");
-      });
+          log::info!("worker '{id}' terminating");
+        });
 
-      let Ok(WorkerEvent::Online(id, jobs)) = events.recv() else {
-        return Err(io::Error::new(io::ErrorKind::Other, ""));
+      let handle = match handle {
+        Ok(handle) => handle,
+        Err(error) => {
+          stop_workers(&jobs, &mut workers);
+          return Err(error::Error::Worker(io::Error::new(
+            error.kind(),
+            format!("unable to spawn worker thread - {error}"),
+          )));
+        }
       };
 
+      let id = handle.thread().name().unwrap_or_default().to_string();
       log::debug!("worker '{id}' is ready for jobs");
-      workers.insert(id, WorkerHandle { jobs, handle });
+      workers.insert(id, handle);
     }
 
     Ok(Self {
+      context: JobContext {
+        observer: observer::Observers::default(),
+        stall: None,
+        audit: audit::AuditLog::default(),
+        mirrors: mirror::Mirrors::default(),
+        storage: storage::StoragePolicy::default(),
+        checkout_threads: 1,
+        incremental: false,
+        clone_depth: None,
+        submodules: false,
+        bare_mirrors: false,
+        strip_git: false,
+        discard_local_work: false,
+        hosts: std::sync::Arc::default(),
+        limits: watchdog::TimeLimits::default(),
+        credentials: credentials::Credentials::default(),
+        proxies: proxy::Proxies::default(),
+        tls: tls::Tls::default(),
+        cancel: cancel::CancellationToken::default(),
+      },
+      root: destination,
+      staging: temp_path,
+      keep_temp: false,
+      default_temp,
+      reference: None,
       workers,
-      events,
+      jobs,
       results: std::sync::mpsc::channel(),
     })
   }
 
+  /// Registers an observer that will be notified as jobs are queued, make progress and are placed.
+  /// This can be called multiple times; every registered observer receives every event.
+  pub fn with_observer<O>(mut self, observer: O) -> Self
+  where
+    O: observer::ProgressObserver + 'static,
+  {
+    self.context.observer.push(std::sync::Arc::new(observer));
+    self
+  }
+
+  /// Records every destructive filesystem operation performed by the pool to `audit`.
+  pub fn with_audit_log(mut self, audit: audit::AuditLog) -> Self {
+    self.context.audit = audit;
+    self
+  }
+
+  /// When `keep` is true, the staging directory that sources are cloned into is not removed after
+  /// execution, whether or not it was successful. Each `SourceReport` records its staging path.
+  pub fn with_keep_temp(mut self, keep: bool) -> Self {
+    self.keep_temp = keep;
+    self
+  }
+
+  /// Enables stall detection for in-flight fetches. Note that when `abort_after` is provided, it is
+  /// also applied as libgit2's (process wide) server read/write timeout so that fetches which
+  /// receive no data at all are still interrupted. The previous timeout is restored once the
+  /// execution is over; pools running at the same time in one process share (and race on) it.
+  pub fn with_stall_policy(mut self, policy: watchdog::StallPolicy) -> Self {
+    self.context.stall = Some(policy);
+    self
+  }
+
+  /// Writes the working tree of each source on up to `threads` threads within the worker that cloned
+  /// it, independent of the number of workers. Sources with sparse patterns are always checked out
+  /// on a single thread.
+  pub fn with_checkout_threads(mut self, threads: usize) -> Self {
+    self.context.checkout_threads = threads.max(1);
+    self
+  }
+
+  /// When true, sources that already have a checkout at their destination are updated in place
+  /// rather than cloned: only the objects the checkout is missing are fetched, and it is reset to
+  /// the manifest revision (checkouts already at that revision are left alone). Sources without a
+  /// checkout are cloned and placed as usual.
+  pub fn with_incremental(mut self, incremental: bool) -> Self {
+    self.context.incremental = incremental;
+    self
+  }
+
+  /// When provided, every source is cloned with this much history, regardless of its own
+  /// `clone-depth`.
+  pub fn with_clone_depth(mut self, depth: Option<u32>) -> Self {
+    self.context.clone_depth = depth;
+    self
+  }
+
+  /// When true, the submodules of every source are (recursively) checked out, regardless of its own
+  /// `sync-s`.
+  pub fn with_submodules(mut self, submodules: bool) -> Self {
+    self.context.submodules = submodules;
+    self
+  }
+
+  /// When true, every ref of the remote of each source (`refs/*:refs/*`) is fetched into a bare
+  /// repository at its destination, rather than checking the source out; deleted refs are pruned.
+  /// Combined with `with_incremental`, existing mirrors are fetched into in place. The `copyfile`s
+  /// and `linkfile`s of sources are skipped, there being no working tree to place them from.
+  pub fn with_bare_mirrors(mut self, bare_mirrors: bool) -> Self {
+    self.context.bare_mirrors = bare_mirrors;
+    self
+  }
+
+  /// When true, the `.git` of every source (and of its submodules) is removed once it has been
+  /// placed, leaving only the files of the revision checked out, e.g. for source archives. Such
+  /// checkouts cannot be updated in place later.
+  pub fn with_strip_git(mut self, strip_git: bool) -> Self {
+    self.context.strip_git = strip_git;
+    self
+  }
+
+  /// When true, sources updated in place (see `with_incremental`) are reset to their revision even
+  /// when their checkout holds uncommitted changes or unpushed commits; otherwise such sources fail
+  /// rather than lose that work.
+  pub fn with_discard_local_work(mut self, discard: bool) -> Self {
+    self.context.discard_local_work = discard;
+    self
+  }
+
+  /// Allows at most `limit` jobs to work on sources of the same remote host (taken from the url of
+  /// their remote) at once, so that a server is not hit by every worker simultaneously. Workers
+  /// never wait for a host: jobs of a host at its limit are set aside, and queued again as the jobs
+  /// of that host finish. Sources on the local filesystem are never limited.
+  pub fn with_host_limit(mut self, limit: Option<usize>) -> Self {
+    self.context.hosts = std::sync::Arc::new(throttle::HostThrottle::new(limit, self.jobs.clone()));
+    self
+  }
+
+  /// Fails every source whose clone (or update) takes longer than `timeout`, aborting its fetch, so
+  /// that a hung remote cannot hold up the execution forever. The failure has a
+  /// `TimeLimitExceeded::Project` in its `source` chain. Like the `abort_after` of
+  /// `with_stall_policy`, the timeout also bounds libgit2's process wide server timeout for the
+  /// duration of the execution.
+  pub fn with_project_timeout(mut self, timeout: Option<std::time::Duration>) -> Self {
+    self.context.limits.per_source = timeout;
+    self
+  }
+
+  /// Fails every source not done by `deadline`: fetches in flight are aborted and sources not yet
+  /// started fail right away. The failures have a `TimeLimitExceeded::Deadline` in their `source`
+  /// chain.
+  pub fn with_deadline(mut self, deadline: Option<std::time::Instant>) -> Self {
+    self.context.limits.deadline = deadline;
+    self
+  }
+
+  /// Sets the credentials offered to remotes that ask for them.
+  pub fn with_credentials(mut self, credentials: credentials::Credentials) -> Self {
+    self.context.credentials = credentials;
+    self
+  }
+
+  /// Sets the proxies http(s) remotes are fetched through; by default, none is used.
+  pub fn with_proxies(mut self, proxies: proxy::Proxies) -> Self {
+    self.context.proxies = proxies;
+    self
+  }
+
+  /// Sets how the certificates of https remotes are verified; a certificate authority bundle must
+  /// also be `Tls::install`ed.
+  pub fn with_tls(mut self, tls: tls::Tls) -> Self {
+    self.context.tls = tls;
+    self
+  }
+
+  /// Stops the execution once `token` is cancelled: fetches in flight are aborted, sources not yet
+  /// started are not, nothing further is placed and the staging directory is removed. Execution
+  /// then fails with an `Interrupted` error; sources placed before cancellation are left in place.
+  pub fn with_cancellation(mut self, token: cancel::CancellationToken) -> Self {
+    self.context.cancel = token;
+    self
+  }
+
+  /// Keeps a bare repository per remote in `dir`, across executions: every (non-shallow) source is
+  /// fetched into the repository of its remote, which then only has to download what changed since
+  /// the last execution, and its checkout borrows objects from there (like `git clone
+  /// --reference`). The checkouts depend on `dir`, which must be kept for as long as they are.
+  /// Only applies to `execute`, not `execute_streaming`.
+  pub fn with_reference_dir(mut self, dir: std::path::PathBuf) -> Self {
+    self.reference = Some(dir);
+    self
+  }
+
+  /// Configures how objects are stored in the repositories (and shared mirrors) we create.
+  pub fn with_storage_policy(mut self, policy: storage::StoragePolicy) -> Self {
+    self.context.storage = policy;
+    self
+  }
+
   /// This method consumes the manifest, sending each layer as a job into our worker pool for it to
   /// execute. Once the git operations have been completed, will will "place" the layers into their
-  /// final location.
-  pub fn execute(mut self, mut manifest: manifest::Manifest) -> io::Result<()> {
-    let layer_count = manifest.sources.len();
-    let worker_count = self.workers.len();
-    let mut jobs = manifest.sources.drain(0..);
-    let mut finished = Vec::default();
-    let (result_sender, result_receiver) = self.results;
+  /// final location. Once every layer has been placed, the files named by their `<copyfile>`
+  /// elements are copied into place and the links named by their `<linkfile>` elements created, in
+  /// manifest order.
+  ///
+  /// Remotes used by more than one source (whether pinned at the same or different revisions) are
+  /// fetched once into a bare repository under `.repors/mirrors` in the destination, which the
+  /// checkouts of those sources borrow their objects from; that directory must be kept alongside
+  /// them. With a reference directory (see `with_reference_dir`), every remote is fetched there
+  /// instead.
+  pub fn execute(mut self, manifest: manifest::Manifest) -> Result<report::ExecutionReport, error::Error> {
+    // When every source is shallow, there is nothing worth sharing.
+    if self.context.clone_depth.is_none() {
+      self.context.mirrors = mirror::Mirrors::plan(&self.root, &manifest.sources, self.reference.as_deref())?;
+    }
+    let expected = manifest.sources.len();
+    self.run(Some(expected), manifest.sources.into_iter().map(Ok))
+  }
+
+  /// Fetches the remote of every (non-shallow) source into the reference directory (see
+  /// `with_reference_dir`) without checking anything out, so that a later `execute` only has to
+  /// download what changed since. Every remote is fetched once, by whichever worker gets to it,
+  /// and reported to our observers as a job of the first source fetched from it. Returns the
+  /// number of remotes fetched.
+  pub fn prefetch(mut self, manifest: manifest::Manifest) -> Result<usize, error::Error> {
+    let Some(reference) = self.reference.take() else {
+      return Err(error::Error::Io(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "prefetching requires a reference directory",
+      )));
+    };
+    self.context.mirrors = mirror::Mirrors::plan(&self.root, &manifest.sources, Some(&reference))?;
+    let context = std::sync::Arc::new(self.context);
+    let server_timeout = set_server_timeout(&context);
 
-    for (id, handle) in &self.workers {
-      let Some(job) = jobs.next() else {
-        log::debug!("not enough jobs for {worker_count} workers");
-        finished.push(id.clone());
+    let mut origins = std::collections::HashSet::new();
+    let (result_sender, result_receiver) = std::sync::mpsc::channel();
+    let mut remotes = 0;
+    let mut unsent = None;
+    for source in manifest.sources {
+      if source.clone_depth.is_some() || !origins.insert(source.origin().to_string()) {
         continue;
+      }
+      if context.cancel.is_cancelled() {
+        break;
+      }
+
+      context.observer.job_queued(&source);
+      remotes += 1;
+      let job = Job::Prefetch {
+        source,
+        context: context.clone(),
+        results: result_sender.clone(),
       };
+      if self.jobs.send(job).is_err() {
+        unsent = Some(error::Error::Worker(io::Error::other(
+          "no workers available to accept jobs",
+        )));
+        break;
+      }
+    }
+    drop(result_sender);
+
+    let failures = result_receiver.iter().filter_map(Result::err).collect::<Vec<_>>();
+    stop_workers(&self.jobs, &mut self.workers);
+    restore_server_timeout(server_timeout);
 
-      log::debug!("sending clone job to worker '{id}'");
-      let results = result_sender.clone();
-      let _ = handle.jobs.send(Job::Cloner { results, source: job });
+    if let Some(error) = unsent {
+      return Err(error);
     }
+    if context.cancel.is_cancelled() {
+      return Err(error::Error::Cancelled);
+    }
+    if failures.is_empty() {
+      Ok(remotes)
+    } else {
+      Err(error::Error::Git(failures))
+    }
+  }
 
-    loop {
-      if finished.len() == worker_count {
-        log::info!("all workers appear idle, exiting processing loop");
-        break;
-      }
+  /// Like `execute`, but each source is dispatched to the workers as soon as `sources` yields it
+  /// (e.g. from a `ManifestReader` that is still parsing), rather than once the whole manifest is
+  /// known. Since we cannot know ahead of time which remotes are used more than once, no fetches
+  /// are shared between sources. If `sources` yields an error, nothing further is dispatched; jobs
+  /// already in flight are allowed to finish and cleaned up before the error is returned (as an `Error::Parse`).
+  pub fn execute_streaming<I>(self, sources: I) -> Result<report::ExecutionReport, error::Error>
+  where
+    I: IntoIterator<Item = io::Result<manifest::Source>>,
+  {
+    self.run(None, sources)
+  }
 
-      match self.events.recv() {
-        Ok(WorkerEvent::Idle(id)) => {
-          log::info!("worker '{id}' appears idle, checking for jobs");
+  /// Dispatches every source yielded by `sources`, waits for the results and places them.
+  fn run<I>(mut self, expected: Option<usize>, sources: I) -> Result<report::ExecutionReport, error::Error>
+  where
+    I: IntoIterator<Item = io::Result<manifest::Source>>,
+  {
+    let context = std::sync::Arc::new(self.context);
+    let server_timeout = set_server_timeout(&context);
 
-          let Some(worker) = self.workers.get(&id) else {
-            continue;
-          };
+    context.observer.execution_started(expected);
 
-          let Some(next) = jobs.next() else {
-            log::info!("no jobs left for '{id}'");
-            finished.push(id);
-            continue;
-          };
+    let mut spans = std::collections::HashMap::new();
+    let mut copies = Vec::new();
+    let mut links = Vec::new();
+    let mut layer_count = 0;
+    let mut interrupted = None;
+    let mut unsent = None;
+    let (result_sender, result_receiver) = self.results;
 
-          log::info!("sending job to '{id}'");
-          let results = result_sender.clone();
-          let _ = worker.jobs.send(Job::Cloner {
-            results,
-            source: next,
-          });
-        }
-        Ok(other) => {
-          log::warn!("strange message received on result receiver - {other:?}");
-        }
+    // Jobs are queued in the order we receive them; idle workers pull from the queue as soon as
+    // they are free.
+    for source in sources {
+      if context.cancel.is_cancelled() {
+        break;
+      }
+
+      let source = match source {
+        Ok(source) => source,
         Err(error) => {
-          return Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("failed receiving from worker threads - {error:?}"),
-          ));
+          interrupted = Some(error);
+          break;
         }
+      };
+
+      context.observer.job_queued(&source);
+      layer_count += 1;
+      let span = instrument::SourceSpan::new(&source);
+      let project = self.root.join(source.relative_path());
+      // Bare repositories have no working tree to copy or link files out of.
+      if !context.bare_mirrors {
+        copies.extend(
+          source
+            .copyfiles
+            .iter()
+            .map(|copy| (project.clone(), copy.clone())),
+        );
+        links.extend(
+          source
+            .linkfiles
+            .iter()
+            .map(|link| (source.destination.clone(), link.clone())),
+        );
+      }
+      spans.insert(project, span.clone());
+      let job = Job::Cloner {
+        results: result_sender.clone(),
+        source,
+        span,
+        context: context.clone(),
+      };
+
+      // Without workers nothing else can be done, but the jobs already sent are still cleaned up.
+      if self.jobs.send(job).is_err() {
+        unsent = Some(error::Error::Worker(io::Error::other(
+          "no workers available to accept jobs",
+        )));
+        break;
       }
     }
 
+    // Each job holds a sender of its own; once they have all been dropped our receive loop ends.
     drop(result_sender);
-    drop(self.events);
 
-    let mut failed = false;
+    let mut failures = Vec::new();
     let mut layer_tree = tree::LayerTree::default();
+    let mut reports = std::collections::HashMap::new();
+    let mut updated = Vec::new();
     while let Ok(result) = result_receiver.recv() {
       match result {
-        Ok((src, temp)) => {
-          log::debug!("registering '{src:?}' (currently at '{temp:?}'");
-          layer_tree.add(src, temp)
+        Ok(Staged {
+          destination,
+          temp,
+          report,
+        }) => {
+          let Some(temp) = temp else {
+            log::debug!("'{destination:?}' was updated in place");
+            updated.push(report);
+            continue;
+          };
+
+          log::debug!("registering '{destination:?}' (currently at '{temp:?}'");
+          reports.insert(destination.clone(), report);
+          layer_tree.add(destination, temp)
         }
         Err(error) => {
-          log::warn!("error while cloning - {error:?}");
-          failed = true;
+          log::warn!("error while cloning - {}", error::error_chain(&error));
+          failures.push(error);
         }
       }
     }
 
-    if failed {
-      return Err(io::Error::new(
-        io::ErrorKind::Other,
-        "not all cloned completed successfully. check logs",
-      ));
-    }
-
-    for (id, worker) in &self.workers {
-      if let Err(error) = worker.jobs.send(Job::Terminate) {
-        log::warn!("unable to terminate '{id}': {error:?}");
+    let stopped = if let Some(error) = unsent {
+      log::warn!("no workers left after dispatching {layer_count} source(s)");
+      Some(error)
+    } else if context.cancel.is_cancelled() {
+      log::warn!("execution cancelled after dispatching {layer_count} source(s)");
+      Some(error::Error::Cancelled)
+    } else if let Some(error) = interrupted {
+      Some(error::Error::Parse(io::Error::new(
+        error.kind(),
+        format!("unable to read sources after dispatching {layer_count} - {error}"),
+      )))
+    } else if !failures.is_empty() {
+      if self.keep_temp {
+        for (destination, report) in &reports {
+          log::warn!("kept staging '{:?}' for '{destination:?}'", report.staging);
+        }
       }
-    }
 
-    let order = layer_tree.consume();
+      log::warn!("{} of {layer_count} source(s) failed", failures.len());
+      Some(error::Error::Git(failures))
+    } else {
+      None
+    };
 
-    if layer_count != order.len() {
-      log::warn!("we did not clone as many sources as there were in the manifest");
-    }
+    let placed = match stopped {
+      Some(error) => Err(error),
+      None => {
+        let levels = layer_tree.consume_levels();
 
-    log::debug!("received all results, attempting to place into final destinations");
-    for (destination, temp) in order {
-      log::debug!("moving '{temp:?}' to '{destination:?}'");
-      std::fs::create_dir_all(&destination)?;
-      std::fs::rename(&temp, &destination)?;
-    }
+        if layer_count != updated.len() + levels.iter().map(Vec::len).sum::<usize>() {
+          log::warn!("we did not clone as many sources as there were in the manifest");
+        }
 
-    for (id, handle) in self.workers.drain() {
-      if let Err(error) = handle.handle.join() {
-        log::error!("worker handle '{id}' did not close successfully: {error:?}");
+        log::debug!("received all results, attempting to place into final destinations");
+        place(&self.jobs, &context, levels, spans, reports).and_then(|placed| {
+          copy_files(&self.root, &copies, &context.audit)
+            .and_then(|()| link_files(&self.root, &links, &context.audit))
+            .map_err(error::Error::Placement)
+            .map(|()| placed)
+        })
       }
-    }
+    };
 
-    Ok(())
+    stop_workers(&self.jobs, &mut self.workers);
+    restore_server_timeout(server_timeout);
+    finish_staging(
+      &self.staging,
+      self.default_temp.as_deref(),
+      self.keep_temp,
+      &context.audit,
+    );
+
+    let mut sources = placed?;
+    sources.extend(updated);
+    if context.strip_git {
+      for source in &sources {
+        strip_git(&source.path, &context.audit).map_err(error::Error::Placement)?;
+      }
+    }
+    let report = report::ExecutionReport { sources };
+    context.observer.execution_finished(&report);
+    Ok(report)
   }
 }
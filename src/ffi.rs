@@ -0,0 +1,223 @@
+//! A small C ABI over manifest parsing and pool execution, for build tooling (e.g. bitbake, through
+//! python's `ctypes`) that would rather drive `repors` directly than parse the output of the cli.
+//! The matching declarations are in `include/repors.h`.
+//!
+//! Functions that can fail return null (or a negative number) and leave a description of the
+//! failure for `repors_last_error` on the calling thread. Panics never cross the boundary.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+/// Reported to the progress callback as a worker begins cloning a source.
+pub const REPORS_JOB_STARTED: c_int = 0;
+/// Reported to the progress callback as objects are received for a source.
+pub const REPORS_JOB_PROGRESS: c_int = 1;
+/// Reported to the progress callback once a source has been cloned and checked out.
+pub const REPORS_JOB_FINISHED: c_int = 2;
+/// Reported to the progress callback when a source could not be cloned or checked out.
+pub const REPORS_JOB_FAILED: c_int = 3;
+/// Reported to the progress callback once a source has been moved into its destination.
+pub const REPORS_PLACED: c_int = 4;
+
+/// The progress callback: receives the `user_data` it was registered with, one of the event
+/// constants above, the subject of the event (the manifest `path` of a source, or the destination
+/// of a placement) and, for progress events, the number of bytes received so far. The subject is
+/// only valid for the duration of the call. Callbacks are made from worker threads.
+pub type ProgressCallback =
+  extern "C" fn(user_data: *mut c_void, event: c_int, subject: *const c_char, received_bytes: u64);
+
+std::thread_local! {
+  /// The failure of the last call made on this thread that failed.
+  static LAST_ERROR: std::cell::RefCell<Option<CString>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Remembers `message` as the last error of the calling thread.
+fn set_last_error(message: String) {
+  let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+  LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Reads a (utf-8) string argument, recording an error if it is null or not utf-8.
+///
+/// # Safety
+///
+/// `value` must be null or point to a nul terminated string.
+unsafe fn string_arg(value: *const c_char, name: &str) -> Option<String> {
+  if value.is_null() {
+    set_last_error(format!("{name} must not be null"));
+    return None;
+  }
+
+  match CStr::from_ptr(value).to_str() {
+    Ok(value) => Some(value.to_string()),
+    Err(error) => {
+      set_last_error(format!("{name} is not valid utf-8 - {error}"));
+      None
+    }
+  }
+}
+
+/// The `user_data` pointer registered alongside a callback.
+struct UserData(*mut c_void);
+
+// SAFETY: we never dereference the pointer; it is only handed back to the caller's callback, which
+// is documented (in the header) to be called from worker threads.
+unsafe impl Send for UserData {}
+// SAFETY: as above.
+unsafe impl Sync for UserData {}
+
+/// Forwards the events of a pool to a C callback.
+struct CallbackObserver {
+  /// The function to call.
+  callback: ProgressCallback,
+  /// What to call it with.
+  user_data: UserData,
+}
+
+impl CallbackObserver {
+  /// Calls the callback, dropping events whose subject cannot be represented as a C string.
+  fn emit(&self, event: c_int, subject: &str, received_bytes: u64) {
+    if let Ok(subject) = CString::new(subject) {
+      (self.callback)(self.user_data.0, event, subject.as_ptr(), received_bytes);
+    }
+  }
+}
+
+impl crate::ProgressObserver for CallbackObserver {
+  fn job_started(&self, source: &crate::Source) {
+    self.emit(REPORS_JOB_STARTED, &source.destination, 0);
+  }
+
+  fn job_progress(&self, source: &crate::Source, progress: &crate::TransferProgress) {
+    self.emit(
+      REPORS_JOB_PROGRESS,
+      &source.destination,
+      progress.received_bytes as u64,
+    );
+  }
+
+  fn job_finished(&self, source: &crate::Source, result: Result<(), &crate::SourceError>) {
+    let event = match result {
+      Ok(()) => REPORS_JOB_FINISHED,
+      Err(_) => REPORS_JOB_FAILED,
+    };
+    self.emit(event, &source.destination, 0);
+  }
+
+  fn placement_finished(&self, destination: &std::path::Path) {
+    self.emit(REPORS_PLACED, &destination.to_string_lossy(), 0);
+  }
+}
+
+/// Parses the manifest at `path`, returning null on failure. The manifest must be released with
+/// `repors_manifest_free` unless it is handed to `repors_execute`.
+///
+/// # Safety
+///
+/// `path` must be null or point to a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn repors_manifest_open(path: *const c_char) -> *mut crate::Manifest {
+  let Some(path) = string_arg(path, "path") else {
+    return std::ptr::null_mut();
+  };
+
+  let parsed = std::panic::catch_unwind(|| crate::Manifest::from_path(&path));
+
+  match parsed {
+    Ok(Ok(manifest)) => Box::into_raw(Box::new(manifest)),
+    Ok(Err(error)) => {
+      set_last_error(format!("unable to read manifest '{path}' - {error}"));
+      std::ptr::null_mut()
+    }
+    Err(_) => {
+      set_last_error(format!("panicked reading manifest '{path}'"));
+      std::ptr::null_mut()
+    }
+  }
+}
+
+/// The number of sources listed by `manifest`, or 0 if it is null.
+///
+/// # Safety
+///
+/// `manifest` must be null or have been returned by `repors_manifest_open` and not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn repors_manifest_len(manifest: *const crate::Manifest) -> usize {
+  manifest.as_ref().map_or(0, |manifest| manifest.sources.len())
+}
+
+/// Releases a manifest returned by `repors_manifest_open`. Null is ignored.
+///
+/// # Safety
+///
+/// `manifest` must be null or have been returned by `repors_manifest_open` and not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn repors_manifest_free(manifest: *mut crate::Manifest) {
+  if !manifest.is_null() {
+    drop(Box::from_raw(manifest));
+  }
+}
+
+/// Clones every source of `manifest` into `destination` using `threads` workers, reporting progress
+/// to `callback` (which may be null). The manifest is consumed (released) whether or not execution
+/// succeeds. Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `manifest` must have been returned by `repors_manifest_open` and not yet released, and
+/// `destination` must be null or point to a nul terminated string. `callback`, when provided, must
+/// be safe to call from any thread with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn repors_execute(
+  manifest: *mut crate::Manifest,
+  destination: *const c_char,
+  threads: usize,
+  callback: Option<ProgressCallback>,
+  user_data: *mut c_void,
+) -> c_int {
+  if manifest.is_null() {
+    set_last_error("manifest must not be null".to_string());
+    return -1;
+  }
+  let manifest = *Box::from_raw(manifest);
+
+  let Some(destination) = string_arg(destination, "destination") else {
+    return -1;
+  };
+  let observer = callback.map(|callback| CallbackObserver {
+    callback,
+    user_data: UserData(user_data),
+  });
+
+  let executed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+    let options = crate::PoolOptions::new(destination.into()).with_threads(threads.max(1));
+    let mut pool = crate::WorkerPool::from_options(options)?;
+    if let Some(observer) = observer {
+      pool = pool.with_observer(observer);
+    }
+    pool.execute(manifest)
+  }));
+
+  match executed {
+    Ok(Ok(_)) => 0,
+    Ok(Err(error)) => {
+      set_last_error(crate::error_chain(&error));
+      -1
+    }
+    Err(_) => {
+      set_last_error("panicked during execution".to_string());
+      -1
+    }
+  }
+}
+
+/// Describes the last failure on the calling thread, or returns null if nothing has failed. The
+/// string remains valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn repors_last_error() -> *const c_char {
+  LAST_ERROR.with(|last| {
+    last
+      .borrow()
+      .as_ref()
+      .map_or(std::ptr::null(), |message| message.as_ptr())
+  })
+}
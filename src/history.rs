@@ -0,0 +1,163 @@
+use std::io;
+
+/// What we saw the last time a source was cloned from a particular origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct Observation {
+  /// The number of bytes received from the remote while cloning.
+  pub bytes: u64,
+  /// How long the clone, resolve and checkout phases took in total.
+  pub duration: std::time::Duration,
+}
+
+/// Observed clone sizes and durations, keyed by (redacted) origin, persisted between runs so that
+/// later executions can make better scheduling decisions.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct History {
+  /// The most recent observation of each origin.
+  observations: std::collections::HashMap<String, Observation>,
+}
+
+impl History {
+  /// Loads the history stored at `path`. A missing file is not an error; it simply means we have
+  /// not seen anything yet.
+  pub fn load<P>(path: P) -> io::Result<Self>
+  where
+    P: AsRef<std::path::Path>,
+  {
+    let contents = match std::fs::read(path.as_ref()) {
+      Ok(contents) => contents,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+      Err(error) => return Err(error),
+    };
+
+    let parsed: serde_json::Value = serde_json::from_slice(&contents)
+      .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("invalid history - {error}")))?;
+
+    let observations = parsed
+      .get("origins")
+      .and_then(serde_json::Value::as_object)
+      .map(|origins| {
+        origins
+          .iter()
+          .filter_map(|(origin, entry)| {
+            let bytes = entry.get("bytes").and_then(serde_json::Value::as_u64)?;
+            let millis = entry.get("duration_ms").and_then(serde_json::Value::as_u64)?;
+            let duration = std::time::Duration::from_millis(millis);
+            Some((origin.clone(), Observation { bytes, duration }))
+          })
+          .collect()
+      })
+      .unwrap_or_default();
+
+    Ok(Self { observations })
+  }
+
+  /// Writes the history to `path`, replacing whatever was there. The file is written alongside its
+  /// final location and renamed into place so an interrupted run never leaves a truncated file.
+  pub fn save<P>(&self, path: P) -> io::Result<()>
+  where
+    P: AsRef<std::path::Path>,
+  {
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+
+    let origins = self
+      .observations
+      .iter()
+      .map(|(origin, observation)| {
+        let entry = serde_json::json!({
+          "bytes": observation.bytes,
+          "duration_ms": observation.duration.as_millis() as u64,
+        });
+        (origin.clone(), entry)
+      })
+      .collect::<serde_json::Map<String, serde_json::Value>>();
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    std::fs::write(&partial, serde_json::json!({ "origins": origins }).to_string())?;
+    std::fs::rename(&partial, path)
+  }
+
+  /// Returns what we last saw when cloning from `origin`, if anything.
+  pub fn observation(&self, origin: &str) -> Option<&Observation> {
+    self.observations.get(crate::redact::redact(origin).as_ref())
+  }
+
+  /// Records the observations of every freshly cloned source in `report`.
+  pub fn record(&mut self, report: &crate::report::ExecutionReport) {
+    let cloned = report
+      .sources
+      .iter()
+      .filter(|source| source.outcome == crate::report::SourceOutcome::Cloned);
+
+    for source in cloned {
+      let timings = &source.timings;
+      let observation = Observation {
+        bytes: source.received_bytes,
+        duration: timings.clone + timings.resolve + timings.checkout,
+      };
+      self.observations.insert(source.origin.clone(), observation);
+    }
+  }
+
+  /// Sorts `sources` so that those which took the longest last time are started first (longest
+  /// processing time first), keeping them from landing at the end of the critical path; ties are
+  /// broken by size. Sources we know nothing about are scheduled before everything else, since they
+  /// could take any amount of time; otherwise the manifest order is preserved.
+  pub fn schedule(&self, sources: &mut [crate::manifest::Source]) {
+    sources.sort_by_key(|source| {
+      let expected = self
+        .observation(source.origin())
+        .map_or((std::time::Duration::MAX, u64::MAX), |observation| {
+          (observation.duration, observation.bytes)
+        });
+      std::cmp::Reverse(expected)
+    });
+  }
+
+  /// Estimates how long `sources` will take to clone on `threads` workers, when started in their
+  /// current order (see `schedule`): each is handed to whichever worker frees up first, and is
+  /// expected to take as long as it did last time. Returns `None` if any of the sources have not
+  /// been seen before.
+  pub fn estimate(&self, sources: &[crate::manifest::Source], threads: usize) -> Option<std::time::Duration> {
+    let mut workers = vec![std::time::Duration::ZERO; threads.max(1)];
+
+    for source in sources {
+      let expected = self.observation(source.origin())?.duration;
+      if let Some(free) = workers.iter_mut().min() {
+        *free += expected;
+      }
+    }
+
+    workers.into_iter().max()
+  }
+
+  /// Suggests how many workers are worth running for `sources`, never more than `ceiling`. Once
+  /// the largest source is in flight, extra workers only help if there is enough other work to keep
+  /// them busy: many small repositories get more threads, while a few huge ones get fewer (and so
+  /// less contended) fetches. Returns `None` if any of the sources have not been seen before.
+  pub fn parallelism(&self, sources: &[crate::manifest::Source], ceiling: usize) -> Option<usize> {
+    let sizes = sources
+      .iter()
+      .map(|source| {
+        self
+          .observation(source.origin())
+          .map(|observation| observation.bytes)
+      })
+      .collect::<Option<Vec<u64>>>()?;
+
+    let largest = sizes.iter().copied().max()?;
+    let total = sizes.iter().sum::<u64>();
+    let wanted = match largest {
+      0 => sizes.len(),
+      largest => usize::try_from(total.div_ceil(largest)).unwrap_or(usize::MAX),
+    };
+
+    Some(wanted.min(sizes.len()).clamp(1, ceiling.max(1)))
+  }
+}
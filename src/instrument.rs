@@ -0,0 +1,60 @@
+use crate::{error, manifest};
+
+/// A handle on the span covering all of the work performed for a single manifest source. When the
+/// `tracing` feature is disabled, this type is zero-sized and all operations on it are no-ops.
+#[derive(Clone, Debug)]
+pub(crate) struct SourceSpan {
+  /// The underlying span, created as a root span so embedders can correlate all phases of a source.
+  #[cfg(feature = "tracing")]
+  inner: tracing::Span,
+}
+
+/// Returned when entering a phase of a source's lifecycle; the phase ends when this is dropped.
+pub(crate) struct PhaseGuard {
+  /// The phase this guard represents.
+  phase: error::Phase,
+  /// When the phase was entered, used to measure its duration.
+  started: std::time::Instant,
+  /// The entered child span.
+  #[cfg(feature = "tracing")]
+  _inner: tracing::span::EnteredSpan,
+}
+
+impl SourceSpan {
+  /// Creates the span for a source, recording its identifying information as fields.
+  #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+  pub(crate) fn new(source: &manifest::Source) -> Self {
+    Self {
+      #[cfg(feature = "tracing")]
+      inner: tracing::info_span!(
+        parent: None,
+        "source",
+        destination = %source.destination,
+        origin = %crate::redact::redact(source.origin()),
+        revision = %source.revision,
+      ),
+    }
+  }
+
+  /// Enters a child span of this source for some phase (e.g. clone, resolve, checkout).
+  pub(crate) fn phase(&self, phase: error::Phase) -> PhaseGuard {
+    PhaseGuard {
+      phase,
+      started: std::time::Instant::now(),
+      #[cfg(feature = "tracing")]
+      _inner: tracing::info_span!(parent: &self.inner, "phase", name = %phase).entered(),
+    }
+  }
+}
+
+impl PhaseGuard {
+  /// The phase this guard represents.
+  pub(crate) fn phase(&self) -> error::Phase {
+    self.phase
+  }
+
+  /// Explicitly ends the phase, returning how long it took.
+  pub(crate) fn end(self) -> std::time::Duration {
+    self.started.elapsed()
+  }
+}
@@ -1,111 +1,1334 @@
+use std::borrow::Cow;
 use std::io;
 
 /// This type represents a listing the manifest xml file.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct Source {
-  /// The version of the layer we should use.
-  #[allow(dead_code)]
+  /// The version of the layer we should use: a commit id (or a prefix of one), a branch or a tag.
   pub revision: String,
-  /// The url/remote information about this layer.
-  pub origin: String,
+  /// The name of the project, relative to the fetch url of its remote.
+  pub name: String,
+  /// The name of the `<remote>` the project is fetched from, whether named by the project itself
+  /// or by the manifest's `<default>`.
+  pub remote_name: String,
+  /// The url the layer is cloned from; see `origin`.
+  origin: String,
   /// Where, relative to our destination we should store the layer once cloned.
   pub destination: String,
+  /// When not empty, only paths matching these patterns (directories or globs, relative to the
+  /// root of the repository) are checked out. Read from the whitespace separated `sparse`
+  /// attribute of the project.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub sparse: Vec<String>,
+  /// The files of the project to copy elsewhere once it has been placed, read from its
+  /// `<copyfile>` children.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub copyfiles: Vec<CopyFile>,
+  /// The symlinks to create to files (or directories) of the project once it has been placed, read
+  /// from its `<linkfile>` children.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub linkfiles: Vec<LinkFile>,
+  /// The groups the project belongs to, read from its comma (or whitespace) separated `groups`
+  /// attribute. Projects also belong to a few implicit groups; see `in_any_group`.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub groups: Vec<String>,
+  /// When provided, only this many commits of history (ending at the revision) are fetched, read
+  /// from the `clone-depth` attribute of the project.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub clone_depth: Option<u32>,
+  /// When true, the submodules of the project are (recursively) checked out along with it, read
+  /// from the `sync-s` attribute of the project or, failing that, of the `<default>`.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub sync_submodules: bool,
+}
+
+/// A `<copyfile>` child of a `<project>`: a file of the project (e.g. a setup script) that is copied
+/// to another location in the destination once the project has been placed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct CopyFile {
+  /// The file to copy, relative to the root of the project.
+  pub src: String,
+  /// Where to copy it, relative to the root of the destination.
+  pub dest: String,
+}
+
+/// A `<linkfile>` child of a `<project>`: a symlink, created once the project has been placed, from
+/// another location in the destination to a file or directory of the project.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct LinkFile {
+  /// The file or directory linked to, relative to the root of the project.
+  pub src: String,
+  /// Where to create the link, relative to the root of the destination.
+  pub dest: String,
+}
+
+impl CopyFile {
+  /// Where the copy is placed, as a path relative to the root of an execution; see
+  /// `Source::relative_path`.
+  pub fn relative_dest(&self) -> std::path::PathBuf {
+    relative_path(&self.dest)
+  }
+}
+
+impl LinkFile {
+  /// Where the link is created, as a path relative to the root of an execution; see
+  /// `Source::relative_path`.
+  pub fn relative_dest(&self) -> std::path::PathBuf {
+    relative_path(&self.dest)
+  }
+}
+
+impl std::fmt::Display for Source {
+  /// Formats the source as `destination (origin @ revision)`, with any credentials in the origin
+  /// redacted.
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      formatter,
+      "{} ({} @ {})",
+      self.destination,
+      crate::redact::redact(&self.origin),
+      self.revision
+    )
+  }
+}
+
+/// Characters that cannot appear in a file name on Windows.
+const WINDOWS_RESERVED_CHARACTERS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+/// Device names that Windows will not allow as a file name, with or without an extension.
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+  "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+  "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+impl Source {
+  /// The name of the `<remote>` the project is fetched from; see `remote_name` (the field).
+  pub fn remote_name(&self) -> &str {
+    &self.remote_name
+  }
+
+  /// The url the layer is cloned from: the fetch url of its remote joined with its name (see
+  /// `remote_name` and `name`), as it was when the manifest was read and rewritten by the
+  /// `RemoteMap` of the reader, if any.
+  pub fn origin(&self) -> &str {
+    &self.origin
+  }
+
+  /// The destination as a path relative to the root of an execution. Manifests separate
+  /// directories with `/`, but `\` is accepted as well; empty and `.` components are dropped so
+  /// that the result is the same on every platform.
+  pub fn relative_path(&self) -> std::path::PathBuf {
+    relative_path(&self.destination)
+  }
+
+  /// Whether the project belongs to any of `groups`. Besides those listed in its `groups`
+  /// attribute, every project belongs to `all`, `name:<name>` and `path:<path>`, and those not in
+  /// `notdefault` belong to `default`.
+  pub fn in_any_group<S>(&self, groups: &[S]) -> bool
+  where
+    S: AsRef<str>,
+  {
+    groups.iter().any(|group| match group.as_ref() {
+      "all" => true,
+      "default" if !self.groups.iter().any(|own| own == "notdefault") => true,
+      group => match (group.strip_prefix("name:"), group.strip_prefix("path:")) {
+        (Some(name), _) => name == self.name,
+        (_, Some(path)) => path == self.destination,
+        _ => self.groups.iter().any(|own| own == group),
+      },
+    })
+  }
+}
+
+/// A manifest `path` as a relative path on this platform; see `Source::relative_path`.
+pub(crate) fn relative_path(path: &str) -> std::path::PathBuf {
+  components(path).collect()
+}
+
+/// The non-empty components of a manifest `path`, split on either separator.
+fn components(path: &str) -> impl Iterator<Item = &str> {
+  path
+    .split(['/', '\\'])
+    .filter(|component| !component.is_empty() && *component != ".")
+}
+
+/// Ensures a manifest `path` names a location within the destination: it may not be absolute,
+/// empty, or climb out with `..`. When `windows` is true, components must also be valid file names
+/// on Windows.
+pub(crate) fn validate_path(path: &str, windows: bool) -> io::Result<()> {
+  let invalid = |reason: &str| {
+    io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("manifest path '{path}' is invalid - {reason}"),
+    )
+  };
+
+  let drive = matches!(path.as_bytes(), [letter, b':', ..] if letter.is_ascii_alphabetic());
+  if path.starts_with(['/', '\\']) || drive {
+    return Err(invalid("it must be relative to the destination"));
+  }
+
+  let mut count = 0;
+  for component in components(path) {
+    count += 1;
+
+    if component == ".." {
+      return Err(invalid("it may not refer to a parent directory"));
+    }
+
+    if !windows {
+      continue;
+    }
+
+    if component
+      .contains(|character: char| character.is_control() || WINDOWS_RESERVED_CHARACTERS.contains(&character))
+    {
+      return Err(invalid("it contains characters that are not allowed on windows"));
+    }
+
+    if component.ends_with(['.', ' ']) {
+      return Err(invalid(
+        "its components may not end with a dot or space on windows",
+      ));
+    }
+
+    let stem = component.split('.').next().unwrap_or(component);
+    if WINDOWS_RESERVED_NAMES
+      .iter()
+      .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+      return Err(invalid("it uses a device name reserved on windows"));
+    }
+  }
+
+  if count == 0 {
+    return Err(invalid("it is empty"));
+  }
+
+  Ok(())
+}
+
+/// Joins the `fetch` url of a remote with the `name` of a project, the way `repo` does for the
+/// common remote styles: `https://host/path`, `ssh://user@host:port/path`, `file:///path` and
+/// scp-like `user@host:path` urls, with or without trailing slashes. Relative fetch urls (e.g.
+/// `..`) are joined as paths, which git resolves against the working directory, unless first
+/// resolved with `resolve_fetch`.
+pub(crate) fn join_origin(fetch: &str, name: &str) -> String {
+  let name = name.trim_start_matches('/');
+
+  let (prefix, path) = match fetch.find("://") {
+    Some(index) => fetch.split_at(index + 3),
+    None if fetch.ends_with(':') => return format!("{fetch}{name}"),
+    None => ("", fetch),
+  };
+
+  let trimmed = path.trim_end_matches('/');
+  match trimmed {
+    "" if path.starts_with('/') => format!("{prefix}/{name}"),
+    "" => format!("{prefix}{name}"),
+    trimmed => format!("{prefix}{trimmed}/{name}"),
+  }
+}
+
+/// Resolves a relative `fetch` url (`.`, `..`, `../mirror`) against `base`, the url of the
+/// repository the manifest itself came from, the way `repo` does: the last part of `base` is the
+/// manifest repository, so `.` names the directory holding it and `..` the one above. Other fetch
+/// urls are returned as they are.
+pub(crate) fn resolve_fetch(base: &str, fetch: &str) -> String {
+  if fetch != "." && fetch != ".." && !fetch.starts_with("./") && !fetch.starts_with("../") {
+    return fetch.to_string();
+  }
+
+  let base = base.trim_end_matches('/');
+  let (prefix, path) = match base.find("://") {
+    Some(index) => {
+      let authority = base[index + 3..]
+        .find('/')
+        .map_or(base.len(), |end| index + 3 + end);
+      base.split_at(authority)
+    }
+    None => match (base.find(':'), base.find('/')) {
+      (Some(colon), slash) if slash.is_none_or(|slash| colon < slash) => base.split_at(colon + 1),
+      _ => ("", base),
+    },
+  };
+
+  let mut parts = path
+    .split('/')
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>();
+  parts.pop();
+  for part in fetch.split('/') {
+    match part {
+      "" | "." => (),
+      ".." => {
+        parts.pop();
+      }
+      part => parts.push(part),
+    }
+  }
+
+  if prefix.contains("://") || path.starts_with('/') {
+    format!("{prefix}/{}", parts.join("/"))
+  } else {
+    format!("{prefix}{}", parts.join("/"))
+  }
+}
+
+/// Reads the value of a boolean `attribute` (e.g. `sync-s`), which is either `true` or `false`.
+fn boolean(attribute: &str, value: &str) -> io::Result<bool> {
+  match value {
+    "true" => Ok(true),
+    "false" => Ok(false),
+    other => Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("{attribute} '{other}' is neither true nor false"),
+    )),
+  }
+}
+
+/// Serializes `map` in key order, so that serializing a manifest is repeatable.
+#[cfg(feature = "serde")]
+fn sorted<S>(map: &std::collections::HashMap<String, String>, serializer: S) -> Result<S::Ok, S::Error>
+where
+  S: serde::Serializer,
+{
+  serde::Serialize::serialize(
+    &map.iter().collect::<std::collections::BTreeMap<_, _>>(),
+    serializer,
+  )
 }
 
 /// This type represents what we will deserialize _from_ the manifest xml file.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct Manifest {
-  #[allow(dead_code, clippy::missing_docs_in_private_items)]
+  /// The remote named by the `<default>` element, if any.
+  #[cfg_attr(feature = "serde", serde(default))]
   default_remote: Option<String>,
-  #[allow(dead_code, clippy::missing_docs_in_private_items)]
+  /// The fetch urls of the `<remote>` elements, by name.
+  #[cfg_attr(feature = "serde", serde(default, serialize_with = "sorted"))]
   remotes: std::collections::HashMap<String, String>,
   /// The parsed list of layers.
   pub sources: Vec<Source>,
 }
 
-/// This method is used to handle grabbing a string from an element in our `quick_xml` parsing.
-fn string_attr<B>(boundary: &quick_xml::events::BytesStart<'_>, key: B) -> Option<String>
+/// The attributes of a manifest element that we care about, read from the element in a single pass.
+/// Values borrow from the reader's event buffer where possible; callers decide when an owned copy
+/// is actually worth making.
+#[derive(Default)]
+struct Attributes<'a> {
+  /// The `name` attribute of a `<project>` or `<remote>`.
+  name: Option<Cow<'a, str>>,
+  /// The `path` attribute of a `<project>`.
+  path: Option<Cow<'a, str>>,
+  /// The `revision` attribute of a `<project>`, `<remote>` or `<default>`.
+  revision: Option<Cow<'a, str>>,
+  /// The `remote` attribute of a `<project>` or `<default>`.
+  remote: Option<Cow<'a, str>>,
+  /// The `fetch` attribute of a `<remote>`.
+  fetch: Option<Cow<'a, str>>,
+  /// The `sparse` attribute of a `<project>`.
+  sparse: Option<Cow<'a, str>>,
+  /// The `clone-depth` attribute of a `<project>`.
+  clone_depth: Option<Cow<'a, str>>,
+  /// The `sync-s` attribute of a `<project>` or `<default>`.
+  sync_s: Option<Cow<'a, str>>,
+  /// The `groups` attribute of a `<project>`.
+  groups: Option<Cow<'a, str>>,
+  /// The `optional` attribute of a `<remove-project>`.
+  optional: Option<Cow<'a, str>>,
+  /// The `src` attribute of a `<copyfile>` or `<linkfile>`.
+  src: Option<Cow<'a, str>>,
+  /// The `dest` attribute of a `<copyfile>` or `<linkfile>`.
+  dest: Option<Cow<'a, str>>,
+}
+
+impl<'a> Attributes<'a> {
+  /// Reads the attributes of `boundary`. Should an attribute appear more than once, the first
+  /// occurrence wins; values that are not valid utf-8 are ignored.
+  fn parse(boundary: &'a quick_xml::events::BytesStart<'_>) -> Self {
+    let mut attributes = Self::default();
+
+    for attribute in boundary.attributes().flatten() {
+      let slot = match attribute.key.as_ref() {
+        b"name" => &mut attributes.name,
+        b"path" => &mut attributes.path,
+        b"revision" => &mut attributes.revision,
+        b"remote" => &mut attributes.remote,
+        b"fetch" => &mut attributes.fetch,
+        b"sparse" => &mut attributes.sparse,
+        b"optional" => &mut attributes.optional,
+        b"groups" => &mut attributes.groups,
+        b"clone-depth" => &mut attributes.clone_depth,
+        b"sync-s" => &mut attributes.sync_s,
+        b"src" => &mut attributes.src,
+        b"dest" => &mut attributes.dest,
+        _ => continue,
+      };
+
+      if slot.is_none() {
+        *slot = match attribute.value {
+          Cow::Borrowed(bytes) => std::str::from_utf8(bytes).ok().map(Cow::Borrowed),
+          Cow::Owned(bytes) => String::from_utf8(bytes).ok().map(Cow::Owned),
+        };
+      }
+    }
+
+    attributes
+  }
+}
+
+/// Bounds on the manifests we are willing to parse, since manifests sometimes come from repositories
+/// we only partly trust. Exceeding any of them fails parsing with an `InvalidData` error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ManifestLimits {
+  /// The largest manifest, in bytes, that will be read.
+  pub max_bytes: u64,
+  /// How deeply elements may be nested.
+  pub max_depth: usize,
+  /// The most `<project>` elements a manifest may list.
+  pub max_projects: usize,
+  /// How deeply `<include>` elements may be nested.
+  pub max_include_depth: usize,
+}
+
+impl Default for ManifestLimits {
+  /// Limits comfortably above the largest real-world manifests: 64MiB (per file), 32 levels of
+  /// nesting, 100,000 projects and 16 levels of includes.
+  fn default() -> Self {
+    Self {
+      max_bytes: 64 * 1024 * 1024,
+      max_depth: 32,
+      max_projects: 100_000,
+      max_include_depth: 16,
+    }
+  }
+}
+
+impl ManifestLimits {
+  /// No limits at all, for manifests that are fully trusted.
+  pub fn unlimited() -> Self {
+    Self {
+      max_bytes: u64::MAX,
+      max_depth: usize::MAX,
+      max_projects: usize::MAX,
+      max_include_depth: usize::MAX,
+    }
+  }
+
+  /// Sets the largest manifest, in bytes, that will be read.
+  pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+    self.max_bytes = max_bytes;
+    self
+  }
+
+  /// Sets how deeply elements may be nested.
+  pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+    self.max_depth = max_depth;
+    self
+  }
+
+  /// Sets the most `<project>` elements a manifest may list.
+  pub fn with_max_projects(mut self, max_projects: usize) -> Self {
+    self.max_projects = max_projects;
+    self
+  }
+
+  /// Sets how deeply `<include>` elements may be nested.
+  pub fn with_max_include_depth(mut self, max_include_depth: usize) -> Self {
+    self.max_include_depth = max_include_depth;
+    self
+  }
+}
+
+/// A `<project>` element as read, before its remote and revision are resolved against the
+/// `<remote>` and `<default>` elements.
+struct ProjectElement {
+  /// The `name` of the project.
+  name: String,
+  /// The `path` of the project, if any.
+  destination: Option<String>,
+  /// The `revision` of the project itself, if any.
+  revision: Option<String>,
+  /// The `remote` of the project itself, if any.
+  remote: Option<String>,
+  /// The `sparse` patterns of the project.
+  sparse: Vec<String>,
+  /// The `groups` of the project.
+  groups: Vec<String>,
+  /// The `clone-depth` of the project, if any.
+  clone_depth: Option<u32>,
+  /// The `sync-s` of the project itself, if any.
+  sync_submodules: Option<bool>,
+  /// The `<copyfile>` children of the project.
+  copyfiles: Vec<CopyFile>,
+  /// The `<linkfile>` children of the project.
+  linkfiles: Vec<LinkFile>,
+}
+
+impl ProjectElement {
+  /// Checks the attributes of a `<project>` element that do not depend on any other element.
+  fn parse(attributes: Attributes<'_>) -> io::Result<Self> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let Attributes {
+      name,
+      path,
+      revision,
+      remote,
+      sparse,
+      groups,
+      clone_depth,
+      sync_s,
+      ..
+    } = attributes;
+
+    let name = name.ok_or_else(|| invalid("<project> is missing its name".to_string()))?;
+    let clone_depth = clone_depth
+      .map(|depth| {
+        depth
+          .parse::<u32>()
+          .ok()
+          .filter(|depth| *depth > 0)
+          .ok_or_else(|| invalid(format!("clone-depth '{depth}' is not a positive number")))
+      })
+      .transpose()?;
+    let sync_submodules = sync_s
+      .as_deref()
+      .map(|value| boolean("sync-s", value))
+      .transpose()?;
+    if let Some(path) = path.as_deref() {
+      validate_path(path, cfg!(windows))?;
+    }
+    let sparse = sparse
+      .map(|patterns| patterns.split_whitespace().map(str::to_string).collect())
+      .unwrap_or_default();
+    let groups = groups
+      .map(|groups| {
+        groups
+          .split(|character: char| character == ',' || character.is_whitespace())
+          .filter(|group| !group.is_empty())
+          .map(str::to_string)
+          .collect()
+      })
+      .unwrap_or_default();
+
+    Ok(Self {
+      name: name.into_owned(),
+      destination: path.map(Cow::into_owned),
+      revision: revision.map(Cow::into_owned),
+      remote: remote.map(Cow::into_owned),
+      sparse,
+      groups,
+      clone_depth,
+      sync_submodules,
+      copyfiles: Vec::default(),
+      linkfiles: Vec::default(),
+    })
+  }
+}
+
+/// A manifest pulled in by an `<include>` element, read to completion before the element that
+/// included it continues.
+struct Include {
+  /// The xml reader of the included file, limited like the reader of the root manifest.
+  xml_reader: quick_xml::Reader<io::Take<io::BufReader<std::fs::File>>>,
+  /// The canonical path of the included file, used to detect cycles.
+  path: std::path::PathBuf,
+  /// How many elements of the included file are currently open.
+  depth: usize,
+}
+
+/// Reads the next event of `xml_reader` into `buffer`, holding it to `limits` and tracking how many
+/// elements are open in `depth`.
+fn read_event<'b, B>(
+  xml_reader: &mut quick_xml::Reader<io::Take<B>>,
+  buffer: &'b mut Vec<u8>,
+  limits: &ManifestLimits,
+  depth: &mut usize,
+) -> io::Result<quick_xml::events::Event<'b>>
 where
-  B: AsRef<[u8]>,
+  B: io::BufRead,
 {
-  boundary
-    .attributes()
-    .flatten()
-    .find(|att| att.key.as_ref() == key.as_ref())
-    .and_then(|origin_att| {
-      let parsed_str = std::str::from_utf8(origin_att.value.as_ref());
-      parsed_str.map(|inner| inner.to_string()).ok()
-    })
+  let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+  let event = xml_reader.read_event_into(buffer);
+
+  if xml_reader.buffer_position() > limits.max_bytes {
+    return Err(invalid(format!(
+      "manifest is larger than the {} byte limit",
+      limits.max_bytes
+    )));
+  }
+
+  let event = event.map_err(|error| {
+    invalid(format!(
+      "malformed manifest xml at byte {} - {error}",
+      xml_reader.error_position()
+    ))
+  })?;
+
+  match &event {
+    quick_xml::events::Event::Eof if *depth > 0 => {
+      return Err(invalid(format!(
+        "manifest ended with {} element(s) left open",
+        *depth
+      )));
+    }
+    quick_xml::events::Event::DocType(doctype)
+      if doctype.to_ascii_uppercase().windows(8).any(|w| w == b"<!ENTITY") =>
+    {
+      return Err(invalid(
+        "manifest declares xml entities, which are not supported".to_string(),
+      ));
+    }
+    quick_xml::events::Event::Start(_) => {
+      *depth += 1;
+      if *depth > limits.max_depth {
+        return Err(invalid(format!(
+          "manifest nests elements deeper than the limit of {}",
+          limits.max_depth
+        )));
+      }
+    }
+    quick_xml::events::Event::End(_) => *depth = depth.saturating_sub(1),
+    _ => (),
+  }
+
+  Ok(event)
 }
 
-impl Manifest {
-  /// This method will attempt to create a `Manifest` from some type that implements `io::Read`.
-  pub fn from_reader<R>(reader: R) -> io::Result<Self>
+/// Parses the sources of a manifest one at a time, as their `<project>` elements are read, so that
+/// work can begin on the first sources before the rest of the manifest has even been received. The
+/// xml is streamed from the reader one event at a time, reusing a single event buffer, and is held
+/// to the `ManifestLimits` it was created with. `<include>` elements are followed (in place) when
+/// the reader knows where the manifest lives; see `with_path`. Since projects are resolved as they
+/// are read, a streamed manifest must declare its `<remote>` and `<default>` elements before the
+/// projects using them; parsing a whole `Manifest` has no such requirement.
+pub struct ManifestReader<R> {
+  /// The underlying xml reader; the reader is limited to one byte more than we are willing to
+  /// parse, so that we can tell an oversized manifest from one that ends exactly at the limit.
+  xml_reader: quick_xml::Reader<io::Take<R>>,
+  /// What we are willing to parse.
+  limits: ManifestLimits,
+  /// How many elements are currently open.
+  depth: usize,
+  /// How many projects we have seen so far.
+  projects: usize,
+  /// The directory `<include>` names are relative to, if known.
+  include_dir: Option<std::path::PathBuf>,
+  /// The canonical path of the root manifest, if known.
+  path: Option<std::path::PathBuf>,
+  /// The included manifests currently being read, innermost last.
+  includes: Vec<Include>,
+  /// The event buffer, cleared before every event.
+  buffer: Vec<u8>,
+  /// The `<remote>` elements seen so far, by name.
+  remotes: std::collections::HashMap<String, String>,
+  /// The remote named by the `<default>` element, if seen so far.
+  default_remote: Option<String>,
+  /// The `revision` of the `<default>` element, if seen so far.
+  default_revision: Option<String>,
+  /// The `sync-s` of the `<default>` element, if seen so far.
+  default_sync_submodules: bool,
+  /// The `revision` attributes of the `<remote>` elements seen so far, by name.
+  remote_revisions: std::collections::HashMap<String, String>,
+  /// The names of the projects we have returned, in order.
+  emitted: Vec<String>,
+  /// The projects named by `<remove-project>` elements, with the number of projects returned
+  /// before each element; only those projects are removed.
+  removals: Vec<(String, usize)>,
+  /// When false, removing a project that has already been returned is an error, since whoever we
+  /// returned it to may already be acting on it.
+  defer_removals: bool,
+  /// The `<project>` element currently open, which is resolved once the element is closed (and its
+  /// children read).
+  pending: Option<ProjectElement>,
+  /// When reading the whole manifest, the `<project>` elements read so far, which are resolved
+  /// against the remotes and defaults once every element has been read; see `read_all`.
+  deferred: Option<Vec<ProjectElement>>,
+  /// When linting, the problems with individual elements, which are skipped rather than ending
+  /// the read; see `recover`.
+  problems: Option<Vec<crate::Diagnostic>>,
+  /// The rewriting applied to the origin of every source.
+  remote_map: crate::RemoteMap,
+  /// The remotes sources may be fetched from.
+  remote_policy: crate::RemotePolicy,
+  /// The names of the sources fetched from remotes the policy refuses, and why they are refused;
+  /// reported once the manifest has been read.
+  refused: Vec<(String, String)>,
+  /// The url of the repository the manifest came from, which relative fetch urls are resolved
+  /// against.
+  origin: Option<String>,
+  /// Set once we have hit the end of the document, or an error.
+  done: bool,
+}
+
+impl ManifestReader<io::BufReader<std::fs::File>> {
+  /// Opens the manifest at `path`, following any `<include>` elements relative to its directory.
+  pub fn open<P>(path: P) -> io::Result<Self>
   where
-    R: io::Read + io::BufRead,
+    P: AsRef<std::path::Path>,
   {
-    let mut xml_reader = quick_xml::Reader::from_reader(reader);
-    let mut buffer = Vec::default();
+    let file = std::fs::File::open(path.as_ref())?;
+    Self::new(io::BufReader::new(file)).with_path(path)
+  }
+}
 
-    let mut remotes = std::collections::HashMap::default();
-    let mut sources = Vec::default();
-    let mut default_remote = None;
+impl<R> ManifestReader<R>
+where
+  R: io::BufRead,
+{
+  /// Prepares to parse the manifest xml provided by `reader`, with the default `ManifestLimits`.
+  pub fn new(reader: R) -> Self {
+    Self::with_limits(reader, ManifestLimits::default())
+  }
+
+  /// Prepares to parse the manifest xml provided by `reader`, held to `limits`.
+  pub fn with_limits(reader: R, limits: ManifestLimits) -> Self {
+    let reader = io::Read::take(reader, limits.max_bytes.saturating_add(1));
+    Self {
+      xml_reader: quick_xml::Reader::from_reader(reader),
+      limits,
+      depth: 0,
+      projects: 0,
+      include_dir: None,
+      path: None,
+      includes: Vec::default(),
+      buffer: Vec::default(),
+      remotes: std::collections::HashMap::default(),
+      default_remote: None,
+      default_revision: None,
+      default_sync_submodules: false,
+      remote_revisions: std::collections::HashMap::default(),
+      emitted: Vec::default(),
+      removals: Vec::default(),
+      defer_removals: false,
+      pending: None,
+      deferred: None,
+      problems: None,
+      remote_map: crate::RemoteMap::default(),
+      remote_policy: crate::RemotePolicy::default(),
+      refused: Vec::default(),
+      origin: None,
+      done: false,
+    }
+  }
+
+  /// Tells the reader that the manifest it is reading lives at `path`, so that `<include>` elements
+  /// can be resolved (relative to its directory) and include cycles back to it detected.
+  pub fn with_path<P>(mut self, path: P) -> io::Result<Self>
+  where
+    P: AsRef<std::path::Path>,
+  {
+    let path = std::fs::canonicalize(path.as_ref())?;
+    self.include_dir = path.parent().map(std::path::Path::to_path_buf);
+    self.path = Some(path);
+    Ok(self)
+  }
+
+  /// Resolves `<include>` elements relative to `dir`, for manifests that do not live in a file of
+  /// their own (e.g. those read from stdin).
+  pub fn with_include_dir(mut self, dir: std::path::PathBuf) -> Self {
+    self.include_dir = Some(dir);
+    self
+  }
+
+  /// Rewrites the origin of every source with `map`, e.g. to fetch from internal mirrors.
+  pub fn with_remote_map(mut self, map: crate::RemoteMap) -> Self {
+    self.remote_map = map;
+    self
+  }
+
+  /// Refuses sources fetched (once rewritten by the remote map) from remotes `policy` does not
+  /// permit. They are never returned; once the whole manifest has been read, an error listing all
+  /// of them is.
+  pub fn with_remote_policy(mut self, policy: crate::RemotePolicy) -> Self {
+    self.remote_policy = policy;
+    self
+  }
+
+  /// Holds `source` back when it is fetched from a remote that is not allowed.
+  fn allowed(&mut self, source: Source) -> Option<Source> {
+    let Some(reason) = self.remote_policy.refusal(source.origin()) else {
+      return Some(source);
+    };
+    log::warn!(
+      "refusing project '{}', fetched from '{}' - {reason}",
+      source.name,
+      crate::redact(source.origin())
+    );
+    self.refused.push((source.name, reason));
+    None
+  }
+
+  /// The error reporting every source held back by `allowed`, if any.
+  fn check_refused(&self) -> io::Result<()> {
+    if self.refused.is_empty() {
+      return Ok(());
+    }
+
+    let refused = self
+      .refused
+      .iter()
+      .map(|(name, reason)| format!("'{name}' ({reason})"))
+      .collect::<Vec<_>>()
+      .join(", ");
+    Err(io::Error::new(
+      io::ErrorKind::PermissionDenied,
+      format!(
+        "{} project(s) are fetched from remotes that are not allowed - {refused}",
+        self.refused.len()
+      ),
+    ))
+  }
+
+  /// Tells the reader that the manifest came from the repository at `url`, so that remotes with
+  /// relative fetch urls (e.g. `..`) are fetched from its neighbours, as `repo` does.
+  pub fn with_origin(mut self, url: String) -> Self {
+    self.origin = Some(url);
+    self
+  }
+
+  /// Keeps reading past problems with individual elements (e.g. a project naming an undeclared
+  /// remote), recording them instead; see `take_problems`. Malformed xml still ends the read.
+  pub(crate) fn linting(mut self) -> Self {
+    self.problems = Some(Vec::default());
+    self
+  }
+
+  /// Records `error`, a problem with an element of `project`, when linting, and returns it when not.
+  fn recover(&mut self, project: Option<String>, error: io::Error) -> io::Result<()> {
+    match self.problems.as_mut() {
+      Some(problems) => {
+        problems.push(crate::Diagnostic::error(project, error.to_string()));
+        Ok(())
+      }
+      None => Err(error),
+    }
+  }
+
+  /// The problems recorded so far while linting.
+  pub(crate) fn take_problems(&mut self) -> Vec<crate::Diagnostic> {
+    self.problems.as_mut().map(std::mem::take).unwrap_or_default()
+  }
+
+  /// The `<remote>` elements seen so far: their fetch urls, by name.
+  pub(crate) fn remotes(&self) -> &std::collections::HashMap<String, String> {
+    &self.remotes
+  }
+
+  /// Reads every remaining source, dropping those removed by `<remove-project>`. Reading happens
+  /// in two passes: every element is read first, and only then are projects resolved against the
+  /// remotes and defaults, so that (unlike when streaming) their order does not matter.
+  pub(crate) fn read_all(&mut self) -> io::Result<Vec<Source>> {
+    self.defer_removals = true;
+    self.deferred = Some(Vec::default());
+    let mut position = self.emitted.len();
+    let mut sources = self.by_ref().collect::<io::Result<Vec<Source>>>()?;
+    let projects = self.deferred.take().unwrap_or_default();
+
+    for project in projects {
+      // Only projects with a path are counted by removals; see `finish`.
+      if project.destination.is_some() {
+        position += 1;
+        let removed = self
+          .removals
+          .iter()
+          .any(|(name, before)| *name == project.name && position - 1 < *before);
+        if removed {
+          continue;
+        }
+      }
+
+      let name = project.name.clone();
+      match self.resolve(project) {
+        Ok(Some(source)) => sources.push(source),
+        Ok(None) => (),
+        Err(error) => self.recover(Some(name), error)?,
+      }
+    }
+
+    let sources = sources
+      .into_iter()
+      .filter_map(|source| self.allowed(source))
+      .collect();
+    self.check_refused()?;
+    Ok(sources)
+  }
+
+  /// Deals with a `<project>` element once it (and its children) have been read: when reading the
+  /// whole manifest it is kept for later, otherwise it is resolved right away.
+  fn finish(&mut self, project: ProjectElement) -> io::Result<Option<Source>> {
+    if let Some(deferred) = self.deferred.as_mut() {
+      if project.destination.is_some() {
+        self.emitted.push(project.name.clone());
+      }
+      deferred.push(project);
+      return Ok(None);
+    }
+
+    let name = project.name.clone();
+    match self.resolve(project) {
+      Ok(Some(source)) => {
+        self.emitted.push(source.name.clone());
+        Ok(Some(source))
+      }
+      Ok(None) => Ok(None),
+      Err(error) => self.recover(Some(name), error).map(|_| None),
+    }
+  }
+
+  /// Resolves the remote and revision of `project` against the remotes and defaults read so far.
+  /// Projects without a path are not checked out, and resolve to nothing.
+  fn resolve(&self, project: ProjectElement) -> io::Result<Option<Source>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let ProjectElement {
+      name,
+      destination,
+      revision,
+      remote,
+      sparse,
+      groups,
+      clone_depth,
+      sync_submodules,
+      copyfiles,
+      linkfiles,
+    } = project;
+
+    let remote_name = remote.or_else(|| self.default_remote.clone()).ok_or_else(|| {
+      invalid(format!(
+        "project '{name}' names no remote, and there is no <default> remote"
+      ))
+    })?;
+    let fetch = self.remotes.get(&remote_name).ok_or_else(|| {
+      invalid(format!(
+        "project '{name}' names the remote '{remote_name}', which has not been declared"
+      ))
+    })?;
+    let origin = self.remote_map.apply(&join_origin(fetch, &name));
+
+    let Some(destination) = destination else {
+      return Ok(None);
+    };
+    // A project's own revision wins over that of its remote, which wins over the default.
+    let revision = revision
+      .or_else(|| self.remote_revisions.get(&remote_name).cloned())
+      .or_else(|| self.default_revision.clone())
+      .ok_or_else(|| {
+        invalid(format!(
+          "project '{name}' has no revision, and neither its remote nor <default> provide one"
+        ))
+      })?;
+
+    Ok(Some(Source {
+      revision,
+      destination,
+      name,
+      remote_name,
+      origin,
+      sparse,
+      copyfiles,
+      linkfiles,
+      groups,
+      clone_depth,
+      sync_submodules: sync_submodules.unwrap_or(self.default_sync_submodules),
+    }))
+  }
+
+  /// Starts reading the manifest named by an `<include>` element.
+  fn include(&mut self, name: Option<String>) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let name = name.ok_or_else(|| invalid("<include> is missing its name".to_string()))?;
+    let Some(dir) = self.include_dir.as_ref() else {
+      return Err(invalid(format!(
+        "unable to include '{name}' without knowing where the manifest lives"
+      )));
+    };
+    validate_path(&name, false)?;
+
+    if self.includes.len() >= self.limits.max_include_depth {
+      return Err(invalid(format!(
+        "manifest includes are nested deeper than the limit of {}",
+        self.limits.max_include_depth
+      )));
+    }
+
+    let path = std::fs::canonicalize(dir.join(&name))
+      .map_err(|error| io::Error::new(error.kind(), format!("unable to include '{name}' - {error}")))?;
+    let cycle = self.path.as_ref() == Some(&path) || self.includes.iter().any(|include| include.path == path);
+    if cycle {
+      return Err(invalid(format!("manifest include cycle through '{name}'")));
+    }
+
+    let file = std::fs::File::open(&path)
+      .map_err(|error| io::Error::new(error.kind(), format!("unable to include '{name}' - {error}")))?;
+    let reader = io::Read::take(io::BufReader::new(file), self.limits.max_bytes.saturating_add(1));
+    log::debug!("including manifest '{}'", path.display());
+    self.includes.push(Include {
+      xml_reader: quick_xml::Reader::from_reader(reader),
+      path,
+      depth: 0,
+    });
+    Ok(())
+  }
+
+  /// Reads events until the next complete source, returning `None` at the end of the document.
+  fn next_source(&mut self) -> io::Result<Option<Source>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
 
     loop {
-      let event = xml_reader
-        .read_event_into(&mut buffer)
-        .map_err(|error| io::Error::new(io::ErrorKind::Other, format!("xml parsing error: {error:?}")))?;
-
-      match event {
-        quick_xml::events::Event::Eof => break,
-        quick_xml::events::Event::Empty(boundary) => {
-          let name = boundary.name();
-          match name.as_ref() {
-            b"project" => {
-              let name = string_attr(&boundary, "name");
-              let path = string_attr(&boundary, "path");
-              let rev = string_attr(&boundary, "revision");
-              let remote = string_attr(&boundary, "remote");
-              let fully_qualified_remote = remote
-                .as_ref()
-                .or(default_remote.as_ref())
-                .and_then(|value| remotes.get(value))
-                .zip(name)
-                .map(|(origin, name)| format!("{origin}/{name}"))
-                .ok_or_else(|| {
-                  let error_message = format!("unable to find actual remote for '{boundary:?}'");
-                  io::Error::new(io::ErrorKind::Other, error_message)
-                })?;
-
-              if let Some((revision, destination)) = rev.zip(path) {
-                sources.push(Source {
-                  revision,
-                  destination,
-                  origin: fully_qualified_remote,
-                });
-              }
-            }
-            b"default" => {
-              default_remote = string_attr(&boundary, "remote");
+      self.buffer.clear();
+      let event = match self.includes.last_mut() {
+        Some(include) => read_event(
+          &mut include.xml_reader,
+          &mut self.buffer,
+          &self.limits,
+          &mut include.depth,
+        )
+        .map_err(|error| {
+          let context = format!("in included manifest '{}' - {error}", include.path.display());
+          io::Error::new(error.kind(), context)
+        }),
+        None => read_event(
+          &mut self.xml_reader,
+          &mut self.buffer,
+          &self.limits,
+          &mut self.depth,
+        ),
+      }?;
+
+      let (boundary, open) = match event {
+        quick_xml::events::Event::Eof if self.includes.pop().is_some() => continue,
+        quick_xml::events::Event::Eof => return Ok(None),
+        quick_xml::events::Event::Empty(boundary) => (boundary, false),
+        quick_xml::events::Event::Start(boundary) => (boundary, true),
+        quick_xml::events::Event::End(end) if end.name().as_ref() == b"project" => {
+          match self.pending.take() {
+            Some(project) => match self.finish(project)? {
+              Some(source) => return Ok(Some(source)),
+              None => continue,
+            },
+            None => continue,
+          }
+        }
+        _ => continue,
+      };
+
+      let name = boundary.name();
+      match name.as_ref() {
+        b"project" => {
+          self.projects += 1;
+          if self.projects > self.limits.max_projects {
+            return Err(invalid(format!(
+              "manifest lists more than the limit of {} projects",
+              self.limits.max_projects
+            )));
+          }
+
+          let attributes = Attributes::parse(&boundary);
+          let name = attributes.name.as_deref().map(str::to_string);
+          let project = match ProjectElement::parse(attributes) {
+            Ok(project) => project,
+            Err(error) => {
+              self.recover(name, error)?;
+              continue;
             }
-            b"remote" => {
-              let name = string_attr(&boundary, "name");
-              let origin = string_attr(&boundary, "fetch");
-              if let Some((name, origin)) = name.zip(origin) {
-                remotes.insert(name, origin);
+          };
+
+          // A project with children is returned once they have all been read.
+          if open {
+            self.pending = Some(project);
+            continue;
+          }
+
+          if let Some(source) = self.finish(project)? {
+            return Ok(Some(source));
+          }
+        }
+        element @ (b"copyfile" | b"linkfile") => {
+          let Attributes { src, dest, .. } = Attributes::parse(&boundary);
+          let checked = src
+            .zip(dest)
+            .ok_or_else(|| {
+              let element = String::from_utf8_lossy(element);
+              invalid(format!("<{element}> must have both a src and a dest"))
+            })
+            .and_then(|(src, dest)| {
+              // A link may point at the project as a whole.
+              if !(element == b"linkfile" && src == ".") {
+                validate_path(&src, cfg!(windows))?;
               }
+              validate_path(&dest, cfg!(windows))?;
+              Ok((src, dest))
+            });
+          let (src, dest) = match checked {
+            Ok(checked) => checked,
+            Err(error) => {
+              let project = self.pending.as_ref().map(|project| project.name.clone());
+              self.recover(project, error)?;
+              continue;
             }
-            _ => (),
+          };
+
+          // Children of projects we skip (and strays outside of any project) are ignored.
+          let Some(project) = self.pending.as_mut() else {
+            continue;
+          };
+          let (src, dest) = (src.into_owned(), dest.into_owned());
+          match element {
+            b"copyfile" => project.copyfiles.push(CopyFile { src, dest }),
+            _ => project.linkfiles.push(LinkFile { src, dest }),
+          }
+        }
+        b"remove-project" => {
+          let Attributes { name, optional, .. } = Attributes::parse(&boundary);
+          let name = name.ok_or_else(|| invalid("<remove-project> is missing its name".to_string()))?;
+          let optional = optional.is_some_and(|optional| optional == "true");
+          let exists = self.emitted.iter().any(|emitted| *emitted == name);
+
+          if !exists && !optional {
+            let error = invalid(format!("<remove-project> names '{name}', which is not a project"));
+            let name = name.into_owned();
+            self.recover(Some(name), error)?;
+            continue;
+          }
+
+          if exists && !self.defer_removals {
+            return Err(invalid(format!(
+              "'{name}' is removed after it was already streamed; parse the whole manifest first"
+            )));
+          }
+
+          self.removals.push((name.into_owned(), self.emitted.len()));
+        }
+        b"default" => {
+          let Attributes {
+            remote,
+            revision,
+            sync_s,
+            ..
+          } = Attributes::parse(&boundary);
+          self.default_remote = remote.map(Cow::into_owned);
+          self.default_revision = revision.map(Cow::into_owned);
+          match sync_s
+            .as_deref()
+            .map(|value| boolean("sync-s", value))
+            .transpose()
+          {
+            Ok(sync_submodules) => self.default_sync_submodules = sync_submodules.unwrap_or_default(),
+            Err(error) => self.recover(None, error)?,
+          }
+        }
+        b"include" => {
+          let name = Attributes::parse(&boundary).name.map(Cow::into_owned);
+          self.include(name)?;
+        }
+        b"remote" => {
+          let Attributes {
+            name,
+            fetch,
+            revision,
+            ..
+          } = Attributes::parse(&boundary);
+          if let Some((name, origin)) = name.zip(fetch) {
+            if let Some(revision) = revision {
+              self
+                .remote_revisions
+                .insert(name.to_string(), revision.into_owned());
+            }
+            let origin = match &self.origin {
+              Some(base) => resolve_fetch(base, &origin),
+              None => origin.into_owned(),
+            };
+            self.remotes.insert(name.into_owned(), origin);
           }
         }
         _ => (),
       }
     }
+  }
+}
+
+impl<R> Iterator for ManifestReader<R>
+where
+  R: io::BufRead,
+{
+  type Item = io::Result<Source>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+
+    loop {
+      let next = match self.next_source() {
+        Ok(Some(source)) => match self.allowed(source) {
+          Some(source) => Ok(Some(source)),
+          None => continue,
+        },
+        Ok(None) => self.check_refused().map(|_| None),
+        Err(error) => Err(error),
+      };
+      self.done = !matches!(next, Ok(Some(_)));
+      return next.transpose();
+    }
+  }
+}
+
+impl Manifest {
+  /// The fetch urls of the `<remote>` elements of the manifest, by remote name.
+  pub fn remotes(&self) -> &std::collections::HashMap<String, String> {
+    &self.remotes
+  }
+
+  /// The remote named by the `<default>` element of the manifest, if any.
+  pub fn default_remote(&self) -> Option<&str> {
+    self.default_remote.as_deref()
+  }
+
+  /// This method will attempt to create a `Manifest` from some type that implements `io::BufRead`.
+  /// The xml is streamed from the reader (see `ManifestReader`), so callers can hand us a
+  /// `BufReader<File>` directly rather than reading the whole file first.
+  pub fn from_reader<R>(reader: R) -> Result<Self, crate::Error>
+  where
+    R: io::BufRead,
+  {
+    Self::from_reader_with_limits(reader, ManifestLimits::default())
+  }
+
+  /// Like `from_reader`, but held to `limits` rather than the defaults.
+  pub fn from_reader_with_limits<R>(reader: R, limits: ManifestLimits) -> Result<Self, crate::Error>
+  where
+    R: io::BufRead,
+  {
+    Self::try_from(ManifestReader::with_limits(reader, limits))
+  }
+
+  /// Parses the manifest at `path`, including the manifests named by its `<include>` elements
+  /// (relative to its directory). `from_reader` cannot follow includes, since it does not know
+  /// where to look for them.
+  pub fn from_path<P>(path: P) -> Result<Self, crate::Error>
+  where
+    P: AsRef<std::path::Path>,
+  {
+    Self::try_from(ManifestReader::open(path)?)
+  }
+
+  /// Pins every source to the commit `report` says it was checked out at, so that the manifest
+  /// (see `to_writer`) reproduces exactly that checkout. Sources missing from the report are left
+  /// as they are.
+  pub fn pinned(mut self, report: &crate::ExecutionReport) -> Self {
+    for source in &mut self.sources {
+      let executed = report
+        .sources
+        .iter()
+        .find(|executed| executed.destination == source.destination);
+      if let Some(executed) = executed {
+        source.revision = executed.commit.to_string();
+      }
+    }
+
+    self
+  }
+
+  /// Writes the manifest as xml: its remotes (in name order), its default remote and every source,
+  /// each with an explicit revision. Includes and `<remove-project>` elements have already been
+  /// applied, so the result stands on its own.
+  pub fn to_writer<W>(&self, writer: W) -> io::Result<()>
+  where
+    W: io::Write,
+  {
+    use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, Event};
+
+    let mut xml = quick_xml::Writer::new_with_indent(writer, b' ', 2);
+    xml.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    xml.write_event(Event::Start(BytesStart::new("manifest")))?;
+
+    let mut remotes = self.remotes.iter().collect::<Vec<_>>();
+    remotes.sort();
+    for (name, fetch) in remotes {
+      let remote =
+        BytesStart::new("remote").with_attributes([("name", name.as_str()), ("fetch", fetch.as_str())]);
+      xml.write_event(Event::Empty(remote))?;
+    }
+
+    if let Some(remote) = self.default_remote.as_deref() {
+      xml.write_event(Event::Empty(
+        BytesStart::new("default").with_attributes([("remote", remote)]),
+      ))?;
+    }
+
+    for source in &self.sources {
+      let mut project = BytesStart::new("project").with_attributes([
+        ("name", source.name.as_str()),
+        ("path", source.destination.as_str()),
+        ("remote", source.remote_name.as_str()),
+        ("revision", source.revision.as_str()),
+      ]);
+      if !source.groups.is_empty() {
+        project.push_attribute(("groups", source.groups.join(",").as_str()));
+      }
+      if !source.sparse.is_empty() {
+        project.push_attribute(("sparse", source.sparse.join(" ").as_str()));
+      }
+      if let Some(depth) = source.clone_depth {
+        project.push_attribute(("clone-depth", depth.to_string().as_str()));
+      }
+      if source.sync_submodules {
+        project.push_attribute(("sync-s", "true"));
+      }
+
+      if source.copyfiles.is_empty() && source.linkfiles.is_empty() {
+        xml.write_event(Event::Empty(project))?;
+        continue;
+      }
+
+      xml.write_event(Event::Start(project))?;
+      let copies = source
+        .copyfiles
+        .iter()
+        .map(|copy| ("copyfile", &copy.src, &copy.dest));
+      let links = source
+        .linkfiles
+        .iter()
+        .map(|link| ("linkfile", &link.src, &link.dest));
+      for (element, src, dest) in copies.chain(links) {
+        let child =
+          BytesStart::new(element).with_attributes([("src", src.as_str()), ("dest", dest.as_str())]);
+        xml.write_event(Event::Empty(child))?;
+      }
+      xml.write_event(Event::End(BytesEnd::new("project")))?;
+    }
+
+    xml.write_event(Event::End(BytesEnd::new("manifest")))?;
+    let mut writer = xml.into_inner();
+    writer.write_all(b"\n")?;
+    writer.flush()
+  }
+}
+
+impl<R> TryFrom<ManifestReader<R>> for Manifest
+where
+  R: io::BufRead,
+{
+  type Error = crate::Error;
+
+  /// Reads every remaining source of `reader`, dropping those removed by `<remove-project>`.
+  fn try_from(mut reader: ManifestReader<R>) -> Result<Self, crate::Error> {
+    let sources = reader.read_all().map_err(crate::Error::Parse)?;
 
     Ok(Self {
-      remotes,
+      remotes: reader.remotes,
       sources,
-      default_remote,
+      default_remote: reader.default_remote,
     })
   }
 }
@@ -0,0 +1,146 @@
+use std::io;
+
+/// The refspecs fetched into a mirror; the same refs a regular clone would bring down.
+const MIRROR_REFSPECS: [&str; 2] = ["+refs/heads/*:refs/heads/*", "+refs/tags/*:refs/tags/*"];
+
+/// A bare repository holding the objects of a remote that more than one source is cloned from. It
+/// is fetched once, and the checkout of every one of those sources borrows its objects through
+/// git's `objects/info/alternates` mechanism rather than fetching (and storing) them again.
+pub(crate) struct Mirror {
+  /// Where the bare repository lives.
+  path: std::path::PathBuf,
+  /// The outcome of fetching the mirror, set by whichever job gets to it first.
+  fetched: std::sync::OnceLock<Result<(), (io::ErrorKind, String)>>,
+}
+
+impl Mirror {
+  /// Runs `fetch` if this is the first time the mirror has been needed; every other caller blocks
+  /// until that first fetch is complete and shares its outcome.
+  pub(crate) fn ensure<F>(&self, fetch: F) -> io::Result<()>
+  where
+    F: FnOnce() -> io::Result<()>,
+  {
+    self
+      .fetched
+      .get_or_init(|| fetch().map_err(|error| (error.kind(), crate::error::error_chain(&error))))
+      .clone()
+      .map_err(|(kind, message)| io::Error::new(kind, format!("shared fetch failed - {message}")))
+  }
+
+  /// Creates (or reuses) the bare repository and fetches `origin` into it.
+  pub(crate) fn fetch(
+    &self,
+    origin: &str,
+    storage: &crate::storage::StoragePolicy,
+    mut options: git2::FetchOptions<'_>,
+  ) -> Result<(), git2::Error> {
+    let repo = match git2::Repository::open_bare(&self.path) {
+      Ok(repo) => repo,
+      Err(_) => git2::Repository::init_bare(&self.path)?,
+    };
+    storage.apply(&repo)?;
+    let mut remote = repo.remote_anonymous(origin)?;
+    remote.fetch(&MIRROR_REFSPECS, Some(&mut options), None)
+  }
+
+  /// Creates a repository at `destination` whose objects are borrowed from the mirror, with an
+  /// `origin` remote (and remote-tracking refs and tags) matching what a regular clone would have produced.
+  pub(crate) fn attach(&self, origin: &str, destination: &std::path::Path) -> io::Result<git2::Repository> {
+    let git = |error| io::Error::from(crate::error::GitError::from(error));
+    let mirror = git2::Repository::open_bare(&self.path).map_err(git)?;
+    let repo = git2::Repository::init(destination).map_err(git)?;
+
+    let info = repo.path().join("objects").join("info");
+    std::fs::create_dir_all(&info)?;
+    let objects = self.path.join("objects");
+    std::fs::write(info.join("alternates"), format!("{}\n", objects.display()))?;
+
+    // The object database of the repository we just created was loaded before the alternates
+    // existed; reopening picks them up.
+    let repo = git2::Repository::open(destination).map_err(git)?;
+    repo.remote("origin", origin).map_err(git)?;
+
+    for reference in mirror.references_glob("refs/heads/*").map_err(git)?.flatten() {
+      let (Some(name), Some(target)) = (reference.shorthand(), reference.target()) else {
+        continue;
+      };
+      let tracking = format!("refs/remotes/origin/{name}");
+      repo
+        .reference(&tracking, target, true, "repors: shared fetch")
+        .map_err(git)?;
+    }
+
+    for reference in mirror.references_glob("refs/tags/*").map_err(git)?.flatten() {
+      let (Some(name), Some(target)) = (reference.name(), reference.target()) else {
+        continue;
+      };
+      repo
+        .reference(name, target, true, "repors: shared fetch")
+        .map_err(git)?;
+    }
+
+    Ok(repo)
+  }
+}
+
+/// The mirrors of an execution, keyed by origin.
+#[derive(Default)]
+pub(crate) struct Mirrors(std::collections::HashMap<String, Mirror>);
+
+impl Mirrors {
+  /// Creates a mirror (under `root/.repors/mirrors`) for every origin that is used by more than one
+  /// of `sources` or, given a `reference` directory, a mirror (in that directory, where it is kept
+  /// between executions) for every origin. Shallow sources fetch only what they need on their own,
+  /// and do not count.
+  pub(crate) fn plan(
+    root: &std::path::Path,
+    sources: &[crate::manifest::Source],
+    reference: Option<&std::path::Path>,
+  ) -> io::Result<Self> {
+    let mut uses = std::collections::HashMap::<&str, usize>::new();
+    for source in sources.iter().filter(|source| source.clone_depth.is_none()) {
+      *uses.entry(source.origin()).or_default() += 1;
+    }
+
+    // Alternates are resolved relative to the borrowing repository, which we move after checkout.
+    let (base, shared) = match reference {
+      Some(reference) => (std::path::absolute(reference)?, 1),
+      None => (std::path::absolute(root)?.join(".repors").join("mirrors"), 2),
+    };
+
+    let mirrors = uses
+      .into_iter()
+      .filter(|(_, count)| *count >= shared)
+      .map(|(origin, _)| {
+        let mirror = Mirror {
+          path: base.join(directory_name(origin)),
+          fetched: std::sync::OnceLock::new(),
+        };
+        (origin.to_string(), mirror)
+      })
+      .collect();
+
+    Ok(Self(mirrors))
+  }
+
+  /// Returns the mirror shared by sources cloned from `origin`, if there is one.
+  pub(crate) fn get(&self, origin: &str) -> Option<&Mirror> {
+    self.0.get(origin)
+  }
+}
+
+/// Turns an origin into something usable as a directory name, without any credentials it may hold.
+/// Bytes other than letters, digits, `-`, `_` and (past the first) `.` are percent-escaped, so that
+/// distinct origins never share a directory (`a-b` and `a/b` become `a-b` and `a%2Fb`).
+fn directory_name(origin: &str) -> String {
+  let mut name = String::new();
+  for (index, byte) in crate::redact::redact(origin).bytes().enumerate() {
+    match byte {
+      b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => name.push(char::from(byte)),
+      b'.' if index > 0 => name.push('.'),
+      _ => name.push_str(&format!("%{byte:02X}")),
+    }
+  }
+
+  format!("{name}.git")
+}
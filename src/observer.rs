@@ -0,0 +1,150 @@
+use crate::{error, manifest, report};
+
+/// A snapshot of the transfer statistics reported by git while a source is being fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct TransferProgress {
+  /// The number of objects the remote said it will send.
+  pub total_objects: usize,
+  /// The number of objects received so far.
+  pub received_objects: usize,
+  /// The number of received objects that have been indexed.
+  pub indexed_objects: usize,
+  /// The number of bytes received so far.
+  pub received_bytes: usize,
+}
+
+impl From<&git2::Progress<'_>> for TransferProgress {
+  fn from(stats: &git2::Progress<'_>) -> Self {
+    Self {
+      total_objects: stats.total_objects(),
+      received_objects: stats.received_objects(),
+      indexed_objects: stats.indexed_objects(),
+      received_bytes: stats.received_bytes(),
+    }
+  }
+}
+
+/// Implementations of this trait are notified by the `WorkerPool` as it works through a manifest.
+/// Every method has a no-op default, so implementors only need to provide the events they care
+/// about. Job methods are called from worker threads, which is why implementations must be
+/// `Send + Sync`.
+///
+/// The library itself never writes to stdout; applications embedding `repors` are expected to
+/// surface whatever they need through an observer (and through the `log` facade, whose logger is
+/// also left for the application to install).
+pub trait ProgressObserver: Send + Sync {
+  /// Called once, before any jobs are queued, with the number of sources that will be executed. The
+  /// number is not known up front when sources are streamed (`WorkerPool::execute_streaming`).
+  fn execution_started(&self, _sources: Option<usize>) {}
+
+  /// Called once per source, as it is queued for the workers.
+  fn job_queued(&self, _source: &manifest::Source) {}
+
+  /// Called by a worker immediately before it begins cloning a source.
+  fn job_started(&self, _source: &manifest::Source) {}
+
+  /// Called by a worker as it begins some phase of work on a source.
+  fn phase_started(&self, _source: &manifest::Source, _phase: error::Phase) {}
+
+  /// Called by a worker as it completes some phase of work on a source.
+  fn phase_finished(&self, _source: &manifest::Source, _phase: error::Phase, _elapsed: std::time::Duration) {}
+
+  /// Called by a worker as objects are received for a source.
+  fn job_progress(&self, _source: &manifest::Source, _progress: &TransferProgress) {}
+
+  /// Called by a worker once a source has been checked out at `commit`, the commit its revision
+  /// resolved to, just before `job_finished`. For bare mirrors nothing is checked out; `commit` is
+  /// then only what the revision resolved to.
+  fn checkout_finished(&self, _source: &manifest::Source, _commit: git2::Oid) {}
+
+  /// Called by a worker once a source has been cloned and checked out, or has failed.
+  fn job_finished(&self, _source: &manifest::Source, _result: Result<(), &error::SourceError>) {}
+
+  /// Called before the staged checkout is moved to its final destination.
+  fn placement_started(&self, _destination: &std::path::Path) {}
+
+  /// Called after the staged checkout has been moved to its final destination.
+  fn placement_finished(&self, _destination: &std::path::Path) {}
+
+  /// Called once every source has been successfully placed.
+  fn execution_finished(&self, _report: &report::ExecutionReport) {}
+}
+
+/// Fans every event out to a list of observers, in the order they were added.
+#[derive(Default, Clone)]
+pub(crate) struct Observers(Vec<std::sync::Arc<dyn ProgressObserver>>);
+
+impl Observers {
+  /// Adds an observer to the end of our list.
+  pub(crate) fn push(&mut self, observer: std::sync::Arc<dyn ProgressObserver>) {
+    self.0.push(observer);
+  }
+}
+
+impl ProgressObserver for Observers {
+  fn execution_started(&self, sources: Option<usize>) {
+    self.0.iter().for_each(|inner| inner.execution_started(sources));
+  }
+
+  fn job_queued(&self, source: &manifest::Source) {
+    self.0.iter().for_each(|inner| inner.job_queued(source));
+  }
+
+  fn job_started(&self, source: &manifest::Source) {
+    self.0.iter().for_each(|inner| inner.job_started(source));
+  }
+
+  fn phase_started(&self, source: &manifest::Source, phase: error::Phase) {
+    self.0.iter().for_each(|inner| inner.phase_started(source, phase));
+  }
+
+  fn phase_finished(&self, source: &manifest::Source, phase: error::Phase, elapsed: std::time::Duration) {
+    self
+      .0
+      .iter()
+      .for_each(|inner| inner.phase_finished(source, phase, elapsed));
+  }
+
+  fn job_progress(&self, source: &manifest::Source, progress: &TransferProgress) {
+    self
+      .0
+      .iter()
+      .for_each(|inner| inner.job_progress(source, progress));
+  }
+
+  fn checkout_finished(&self, source: &manifest::Source, commit: git2::Oid) {
+    self
+      .0
+      .iter()
+      .for_each(|inner| inner.checkout_finished(source, commit));
+  }
+
+  fn job_finished(&self, source: &manifest::Source, result: Result<(), &error::SourceError>) {
+    self.0.iter().for_each(|inner| inner.job_finished(source, result));
+  }
+
+  fn placement_started(&self, destination: &std::path::Path) {
+    self
+      .0
+      .iter()
+      .for_each(|inner| inner.placement_started(destination));
+  }
+
+  fn placement_finished(&self, destination: &std::path::Path) {
+    self
+      .0
+      .iter()
+      .for_each(|inner| inner.placement_finished(destination));
+  }
+
+  fn execution_finished(&self, report: &report::ExecutionReport) {
+    self.0.iter().for_each(|inner| inner.execution_finished(report));
+  }
+}
+
+/// The observer used by a `WorkerPool` when none has been provided.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl ProgressObserver for NoopObserver {}
@@ -0,0 +1,134 @@
+use std::io;
+
+/// Chooses the proxy http(s) remotes are fetched through, for build machines that can only reach
+/// remotes through one. In order of precedence, a remote uses: the proxy of the override whose url
+/// prefix matches it (the longest, when several do), the proxy given with `with_proxy`, or the
+/// proxy named by the `https_proxy`/`http_proxy` environment variables (see `from_env`). Remotes
+/// listed in `no_proxy` skip the latter two. Ssh and local remotes never use a proxy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Proxies {
+  /// The proxy used by every http(s) remote without an override.
+  default: Option<String>,
+  /// The url prefixes of remotes using a proxy of their own, and that proxy; an empty proxy means
+  /// none.
+  overrides: Vec<(String, String)>,
+  /// The proxy of `https` remotes named by the environment.
+  env_https: Option<String>,
+  /// The proxy of `http` remotes named by the environment.
+  env_http: Option<String>,
+  /// The hosts (or domain suffixes) that are reached directly, or `*` for all.
+  no_proxy: Vec<String>,
+}
+
+impl Proxies {
+  /// Uses the proxies named by the `https_proxy`, `http_proxy` and `no_proxy` environment variables
+  /// (or their upper case forms), like git and curl do.
+  pub fn from_env() -> Self {
+    let var = |name: &str| {
+      std::env::var(name)
+        .or_else(|_| std::env::var(name.to_ascii_uppercase()))
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+    };
+
+    Self {
+      env_https: var("https_proxy"),
+      env_http: var("http_proxy"),
+      no_proxy: var("no_proxy")
+        .map(|hosts| {
+          hosts
+            .split(',')
+            .map(|host| host.trim().trim_start_matches('.').to_ascii_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect()
+        })
+        .unwrap_or_default(),
+      ..Self::default()
+    }
+  }
+
+  /// Fetches every http(s) remote without an override through `proxy` (e.g.
+  /// `http://proxy.example.com:3128`).
+  pub fn with_proxy(mut self, proxy: String) -> Self {
+    self.default = Some(proxy);
+    self
+  }
+
+  /// Fetches remotes whose url starts with `prefix` through `proxy`, or directly when `proxy` is
+  /// empty.
+  pub fn with_override(mut self, prefix: String, proxy: String) -> Self {
+    self.overrides.push((prefix, proxy));
+    self
+  }
+
+  /// Adds an override written as `prefix=proxy`, the form used on the command line.
+  pub fn with_override_str(self, rule: &str) -> io::Result<Self> {
+    match rule.split_once('=') {
+      Some((prefix, proxy)) if !prefix.trim().is_empty() => {
+        Ok(self.with_override(prefix.trim().to_string(), proxy.trim().to_string()))
+      }
+      _ => Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("proxy override '{rule}' is not of the form prefix=proxy"),
+      )),
+    }
+  }
+
+  /// The proxy `url` is fetched through, if any.
+  pub fn proxy_for(&self, url: &str) -> Option<&str> {
+    let scheme = url
+      .split_once("://")
+      .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    let https = match scheme.as_deref() {
+      Some("https") => true,
+      Some("http") => false,
+      _ => return None,
+    };
+
+    let matched = self
+      .overrides
+      .iter()
+      .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+      .max_by_key(|(prefix, _)| prefix.len());
+    if let Some((_, proxy)) = matched {
+      return Some(proxy.as_str()).filter(|proxy| !proxy.is_empty());
+    }
+
+    if self.bypassed(url) {
+      return None;
+    }
+
+    let env = if https {
+      self.env_https.as_deref()
+    } else {
+      self.env_http.as_deref()
+    };
+    self.default.as_deref().or(env)
+  }
+
+  /// True when `url` is on a host listed in `no_proxy`.
+  fn bypassed(&self, url: &str) -> bool {
+    let Some(host) = crate::throttle::host(url).map(str::to_ascii_lowercase) else {
+      return false;
+    };
+
+    self.no_proxy.iter().any(|entry| {
+      let entry = entry.split(':').next().unwrap_or_default();
+      entry == "*" || host == entry || host.ends_with(&format!(".{entry}"))
+    })
+  }
+
+  /// The proxy options of a connection to `url`.
+  pub(crate) fn options(&self, url: &str) -> git2::ProxyOptions<'_> {
+    let mut options = git2::ProxyOptions::new();
+    if let Some(proxy) = self.proxy_for(url) {
+      log::debug!(
+        "fetching '{}' through proxy '{}'",
+        crate::redact(url),
+        crate::redact(proxy)
+      );
+      options.url(proxy);
+    }
+    options
+  }
+}
@@ -0,0 +1,110 @@
+use std::io;
+
+/// Where, within a destination, the paths of the projects checked out there are recorded.
+const PROJECT_LIST: &str = ".repors/project.list";
+
+/// Reads the paths recorded by `record_projects` under `root`; none when nothing was recorded. Paths
+/// that are not valid manifest paths (e.g. absolute, or climbing out with `..`), which only a
+/// tampered list holds, are skipped so that nothing outside of `root` is ever pruned.
+fn recorded(root: &std::path::Path) -> io::Result<std::collections::BTreeSet<String>> {
+  match std::fs::read_to_string(root.join(PROJECT_LIST)) {
+    Ok(contents) => Ok(
+      contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .filter(|line| match crate::manifest::validate_path(line, false) {
+          Ok(()) => true,
+          Err(error) => {
+            log::warn!(
+              "skipping an entry of '{}' - {error}",
+              root.join(PROJECT_LIST).display()
+            );
+            false
+          }
+        })
+        .map(str::to_string)
+        .collect(),
+    ),
+    Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+    Err(error) => Err(error),
+  }
+}
+
+/// Writes `paths` as the projects checked out under `root`.
+fn write(root: &std::path::Path, paths: &std::collections::BTreeSet<String>) -> io::Result<()> {
+  let path = root.join(PROJECT_LIST);
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let contents = paths.iter().map(|path| format!("{path}\n")).collect::<String>();
+  std::fs::write(path, contents)
+}
+
+/// The manifest paths of the projects recorded (see `record_projects`) as checked out under `root`,
+/// in path order.
+pub fn recorded_projects(root: &std::path::Path) -> io::Result<Vec<String>> {
+  recorded(root).map(|paths| paths.into_iter().collect())
+}
+
+/// Records the manifest `paths` of projects as placed under `root` (checkouts, bare mirrors and
+/// checkouts stripped of their `.git` alike), so that `orphans` can later tell which ones a manifest
+/// no longer lists. Paths recorded earlier are kept (a sync of some groups does not forget the rest)
+/// until they are pruned or their directory is gone.
+pub fn record_projects<'a, I>(root: &std::path::Path, paths: I) -> io::Result<()>
+where
+  I: IntoIterator<Item = &'a str>,
+{
+  let mut recorded = recorded(root)?;
+  recorded.retain(|path| root.join(path).exists());
+  recorded.extend(paths.into_iter().map(str::to_string));
+  write(root, &recorded)
+}
+
+/// The checkouts under `root` that an earlier execution placed there (see `record_projects`) but
+/// that `sources` no longer places a project at, in path order. Only recorded paths are ever
+/// considered, so that unrelated repositories within the destination (e.g. in a build directory)
+/// are never mistaken for orphans. Checkouts that still hold the checkout of a source nested within
+/// them are left out, since removing them would remove it too.
+pub fn orphans(root: &std::path::Path, sources: &[crate::Source]) -> io::Result<Vec<String>> {
+  let wanted = sources
+    .iter()
+    .map(crate::Source::relative_path)
+    .collect::<Vec<_>>();
+
+  Ok(
+    recorded(root)?
+      .into_iter()
+      .filter(|path| root.join(path).exists())
+      .filter(|path| {
+        let path = std::path::Path::new(path);
+        !wanted.iter().any(|wanted| wanted.starts_with(path))
+      })
+      .collect(),
+  )
+}
+
+/// Removes the checkouts of `orphans` (as returned by `orphans`) from under `root`, along with the
+/// directories left empty above them, and forgets them. Check them for `unsaved_work` first.
+pub fn prune(root: &std::path::Path, orphans: &[String], audit: &crate::AuditLog) -> io::Result<()> {
+  let mut recorded = recorded(root)?;
+
+  for orphan in orphans {
+    let path = root.join(orphan);
+    audit.remove_dir_all(&path)?;
+    recorded.remove(orphan);
+    write(root, &recorded)?;
+
+    let mut parent = path.parent();
+    while let Some(dir) = parent.filter(|dir| dir.starts_with(root) && *dir != root) {
+      let empty = std::fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none());
+      if !empty {
+        break;
+      }
+      audit.remove_dir(dir)?;
+      parent = dir.parent();
+    }
+  }
+
+  Ok(())
+}
@@ -0,0 +1,64 @@
+use std::io;
+
+/// Rewrites the urls sources are fetched from, so that a manifest naming public remotes can be
+/// executed against internal mirrors (e.g. on air-gapped build machines). Each rule replaces a
+/// prefix of the url, like git's `url.<base>.insteadOf`; when several rules match, the one with the
+/// longest prefix wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteMap {
+  /// The prefixes replaced, and what they are replaced with.
+  rules: Vec<(String, String)>,
+}
+
+impl RemoteMap {
+  /// Replaces the `from` prefix of urls with `to`.
+  pub fn with_rule(mut self, from: String, to: String) -> Self {
+    self.rules.push((from, to));
+    self
+  }
+
+  /// Adds a rule written as `from=to`, the form used on the command line and in map files.
+  pub fn with_rule_str(self, rule: &str) -> io::Result<Self> {
+    match rule.split_once('=') {
+      Some((from, to)) if !from.trim().is_empty() => {
+        Ok(self.with_rule(from.trim().to_string(), to.trim().to_string()))
+      }
+      _ => Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("remote mapping '{rule}' is not of the form from=to"),
+      )),
+    }
+  }
+
+  /// Reads the rules of the map file at `path`: a `from=to` rule per line, where blank lines and
+  /// lines starting with `#` are ignored.
+  pub fn from_path<P>(path: P) -> io::Result<Self>
+  where
+    P: AsRef<std::path::Path>,
+  {
+    let contents = std::fs::read_to_string(path.as_ref())?;
+    let lines = contents.lines().enumerate();
+    lines
+      .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+      .try_fold(Self::default(), |map, (number, line)| {
+        map.with_rule_str(line).map_err(|error| {
+          let path = path.as_ref().display();
+          io::Error::new(error.kind(), format!("{path}:{} - {error}", number + 1))
+        })
+      })
+  }
+
+  /// The url `url` is rewritten to.
+  pub fn apply(&self, url: &str) -> String {
+    let matched = self
+      .rules
+      .iter()
+      .filter(|(from, _)| url.starts_with(from.as_str()))
+      .max_by_key(|(from, _)| from.len());
+
+    match matched {
+      Some((from, to)) => format!("{to}{}", &url[from.len()..]),
+      None => url.to_string(),
+    }
+  }
+}
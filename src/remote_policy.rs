@@ -0,0 +1,96 @@
+use std::io;
+
+/// Decides which remotes sources may be fetched from, so that typo'd or unapproved urls fail
+/// before anything is downloaded from them. By default every remote is allowed.
+///
+/// Once any host is given, only those hosts are allowed: each is either named exactly
+/// (`git.internal`) or by a pattern matching it and its subdomains (`*.corp.example`), case
+/// insensitively. When only secure remotes are allowed, remotes reached over cleartext transports
+/// (`http://` and `git://`) are refused. Local paths and `file://` urls, which are not fetched from
+/// any host, are always allowed; any other remote whose host cannot be found is refused once hosts
+/// are given, rather than let through unchecked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemotePolicy {
+  /// The hosts allowed, once any is given.
+  hosts: Option<Vec<String>>,
+  /// True when remotes reached over cleartext transports are refused.
+  secure_only: bool,
+}
+
+impl RemotePolicy {
+  /// Allows sources to be fetched from `host`.
+  pub fn with_host(mut self, host: String) -> Self {
+    let host = host.trim().to_ascii_lowercase();
+    self.hosts.get_or_insert_with(Vec::default).push(host);
+    self
+  }
+
+  /// Adds the hosts of the allowlist file at `path`: a host per line, where blank lines and lines
+  /// starting with `#` are ignored. A file without any host allows none.
+  pub fn with_hosts_file<P>(mut self, path: P) -> io::Result<Self>
+  where
+    P: AsRef<std::path::Path>,
+  {
+    let contents = std::fs::read_to_string(path.as_ref())?;
+    let hosts = contents
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .map(str::to_ascii_lowercase);
+    self.hosts.get_or_insert_with(Vec::default).extend(hosts);
+    Ok(self)
+  }
+
+  /// Refuses remotes reached over cleartext transports (`http://` and `git://`) when `secure_only`.
+  pub fn with_secure_only(mut self, secure_only: bool) -> Self {
+    self.secure_only = secure_only;
+    self
+  }
+
+  /// True when sources may be fetched from `origin`.
+  pub fn permits(&self, origin: &str) -> bool {
+    self.refusal(origin).is_none()
+  }
+
+  /// Why sources may not be fetched from `origin`, if they may not.
+  pub fn refusal(&self, origin: &str) -> Option<String> {
+    let scheme = origin
+      .split_once("://")
+      .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    if self.secure_only && matches!(scheme.as_deref(), Some("http" | "git")) {
+      return Some(format!("{} is not encrypted", scheme.unwrap_or_default()));
+    }
+
+    let hosts = self.hosts.as_ref()?;
+    let Some(host) = crate::throttle::host(origin) else {
+      return (!is_local(origin)).then(|| "no host could be found to check".to_string());
+    };
+    let host = host.to_ascii_lowercase();
+    let allowed = hosts.iter().any(|allowed| match allowed.strip_prefix("*.") {
+      Some(domain) => host == domain || host.ends_with(&format!(".{domain}")),
+      None => host == *allowed,
+    });
+    if allowed {
+      None
+    } else {
+      Some(format!("{host} is not an allowed host"))
+    }
+  }
+}
+
+/// Whether `origin` is a local path or `file://` url. As with git, an origin without a scheme is a
+/// path unless a colon comes before its first slash (the scp form, `host:path`), drive letters
+/// (`c:\...`) aside.
+fn is_local(origin: &str) -> bool {
+  if let Some((scheme, _)) = origin.split_once("://") {
+    return scheme.eq_ignore_ascii_case("file");
+  }
+
+  match origin.find(':') {
+    None => true,
+    Some(colon) => {
+      origin[..colon].contains(['/', '\\'])
+        || (colon == 1 && origin.starts_with(|c: char| c.is_ascii_alphabetic()))
+    }
+  }
+}
@@ -0,0 +1,261 @@
+use std::io;
+
+/// A reference advertised by a remote, as listed by `git ls-remote`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AdvertisedRef {
+  /// The full name of the reference, e.g. `refs/heads/kirkstone`.
+  pub name: String,
+  /// The object the reference points to.
+  pub oid: git2::Oid,
+}
+
+/// An advertisement, and when we received it.
+#[derive(Debug, Clone)]
+struct Advertisement {
+  /// When the remote was queried.
+  fetched: std::time::SystemTime,
+  /// What it advertised.
+  refs: Vec<AdvertisedRef>,
+}
+
+/// Remembers the references advertised by remotes for `ttl`, so that looking up branches and tags
+/// repeatedly (and, when persisted with `save` and `load`, across runs) does not query every remote
+/// every time. Entries are keyed by the redacted origin so that credentials are never persisted.
+#[derive(Debug)]
+pub struct RefCache {
+  /// How long an advertisement is considered current.
+  ttl: std::time::Duration,
+  /// The advertisements we know about.
+  entries: std::sync::Mutex<std::collections::HashMap<String, Advertisement>>,
+  /// The credentials offered to remotes that ask for them.
+  credentials: crate::credentials::Credentials,
+  /// The proxies remotes are reached through.
+  proxies: crate::proxy::Proxies,
+  /// How the certificates of https remotes are verified.
+  tls: crate::tls::Tls,
+}
+
+impl RefCache {
+  /// Creates an empty cache whose entries expire after `ttl`.
+  pub fn new(ttl: std::time::Duration) -> Self {
+    Self {
+      ttl,
+      entries: std::sync::Mutex::default(),
+      credentials: crate::credentials::Credentials::default(),
+      proxies: crate::proxy::Proxies::default(),
+      tls: crate::tls::Tls::default(),
+    }
+  }
+
+  /// Sets the credentials offered to remotes that ask for them.
+  pub fn with_credentials(mut self, credentials: crate::credentials::Credentials) -> Self {
+    self.credentials = credentials;
+    self
+  }
+
+  /// Sets the proxies http(s) remotes are reached through.
+  pub fn with_proxies(mut self, proxies: crate::proxy::Proxies) -> Self {
+    self.proxies = proxies;
+    self
+  }
+
+  /// Sets how the certificates of https remotes are verified.
+  pub fn with_tls(mut self, tls: crate::tls::Tls) -> Self {
+    self.tls = tls;
+    self
+  }
+
+  /// Loads the advertisements stored at `path`. A missing file results in an empty cache; expired
+  /// entries are dropped.
+  pub fn load<P>(path: P, ttl: std::time::Duration) -> io::Result<Self>
+  where
+    P: AsRef<std::path::Path>,
+  {
+    let cache = Self::new(ttl);
+    let contents = match std::fs::read(path.as_ref()) {
+      Ok(contents) => contents,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(cache),
+      Err(error) => return Err(error),
+    };
+
+    let parsed: serde_json::Value = serde_json::from_slice(&contents)
+      .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("invalid ref cache - {error}")))?;
+
+    let remotes = parsed
+      .get("remotes")
+      .and_then(serde_json::Value::as_object)
+      .into_iter()
+      .flatten();
+
+    let mut entries = cache.lock()?;
+    for (origin, entry) in remotes {
+      let Some(millis) = entry.get("fetched_ms").and_then(serde_json::Value::as_u64) else {
+        continue;
+      };
+      let fetched = std::time::UNIX_EPOCH + std::time::Duration::from_millis(millis);
+      let refs = entry
+        .get("refs")
+        .and_then(serde_json::Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, oid)| {
+          let oid = git2::Oid::from_str(oid.as_str()?).ok()?;
+          Some(AdvertisedRef {
+            name: name.clone(),
+            oid,
+          })
+        })
+        .collect();
+
+      let advertisement = Advertisement { fetched, refs };
+      if cache.is_current(&advertisement) {
+        entries.insert(origin.clone(), advertisement);
+      }
+    }
+    drop(entries);
+
+    Ok(cache)
+  }
+
+  /// Writes every current advertisement to `path`, replacing whatever was there.
+  pub fn save<P>(&self, path: P) -> io::Result<()>
+  where
+    P: AsRef<std::path::Path>,
+  {
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+
+    let remotes = self
+      .lock()?
+      .iter()
+      .filter(|(_, advertisement)| self.is_current(advertisement))
+      .map(|(origin, advertisement)| {
+        let fetched = advertisement
+          .fetched
+          .duration_since(std::time::UNIX_EPOCH)
+          .map(|elapsed| elapsed.as_millis() as u64)
+          .unwrap_or_default();
+        let refs = advertisement
+          .refs
+          .iter()
+          .map(|advertised| (advertised.name.clone(), advertised.oid.to_string().into()))
+          .collect::<serde_json::Map<String, serde_json::Value>>();
+        let entry = serde_json::json!({ "fetched_ms": fetched, "refs": refs });
+        (origin.clone(), entry)
+      })
+      .collect::<serde_json::Map<String, serde_json::Value>>();
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    std::fs::write(&partial, serde_json::json!({ "remotes": remotes }).to_string())?;
+    std::fs::rename(&partial, path)
+  }
+
+  /// Returns the references advertised by `origin`, querying the remote only if we do not have a
+  /// current advertisement for it.
+  pub fn advertised(&self, origin: &str) -> io::Result<Vec<AdvertisedRef>> {
+    let key = crate::redact::redact(origin).into_owned();
+
+    let cached = self
+      .lock()?
+      .get(&key)
+      .filter(|advertisement| self.is_current(advertisement))
+      .map(|advertisement| advertisement.refs.clone());
+
+    if let Some(refs) = cached {
+      log::debug!("using cached ref advertisement of '{key}'");
+      return Ok(refs);
+    }
+
+    log::debug!("listing refs advertised by '{key}'");
+    let refs = list_remote(origin, &self.credentials, &self.proxies, &self.tls)
+      .map_err(|error| io::Error::from(crate::error::GitError::from(error)))?;
+    let advertisement = Advertisement {
+      fetched: std::time::SystemTime::now(),
+      refs: refs.clone(),
+    };
+    self.lock()?.insert(key, advertisement);
+
+    Ok(refs)
+  }
+
+  /// Resolves `revision` against the references advertised by `origin`: a full object id resolves
+  /// to itself, otherwise branches, tags and full reference names are matched (in that order),
+  /// falling back to an abbreviated object id of one of the advertised references.
+  pub fn resolve(&self, origin: &str, revision: &str) -> io::Result<Option<git2::Oid>> {
+    if revision.len() == 40 {
+      if let Ok(oid) = git2::Oid::from_str(revision) {
+        return Ok(Some(oid));
+      }
+    }
+
+    let refs = self.advertised(origin)?;
+    let candidates = [
+      format!("refs/heads/{revision}"),
+      format!("refs/tags/{revision}"),
+      revision.to_string(),
+    ];
+    let named = candidates
+      .iter()
+      .find_map(|candidate| refs.iter().find(|advertised| &advertised.name == candidate));
+
+    let lowered = revision.to_ascii_lowercase();
+    let found = named.or_else(|| {
+      let abbreviated = revision.len() >= 4 && revision.chars().all(|c| c.is_ascii_hexdigit());
+      refs
+        .iter()
+        .filter(|_| abbreviated)
+        .find(|advertised| advertised.oid.to_string().starts_with(&lowered))
+    });
+
+    Ok(found.map(|advertised| advertised.oid))
+  }
+
+  /// True if `advertisement` was received within our ttl.
+  fn is_current(&self, advertisement: &Advertisement) -> bool {
+    advertisement
+      .fetched
+      .elapsed()
+      .is_ok_and(|elapsed| elapsed <= self.ttl)
+  }
+
+  /// Locks our entries, translating a poisoned lock into an io error.
+  fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, std::collections::HashMap<String, Advertisement>>> {
+    self
+      .entries
+      .lock()
+      .map_err(|error| io::Error::other(format!("ref cache poisoned - {error}")))
+  }
+}
+
+/// Connects to `origin` and lists the references it advertises, without fetching anything.
+fn list_remote(
+  origin: &str,
+  credentials: &crate::credentials::Credentials,
+  proxies: &crate::proxy::Proxies,
+  tls: &crate::tls::Tls,
+) -> Result<Vec<AdvertisedRef>, git2::Error> {
+  let mut remote = git2::Remote::create_detached(origin)?;
+  let mut callbacks = git2::RemoteCallbacks::new();
+  callbacks.credentials(credentials.callback());
+  tls.apply(&mut callbacks);
+  let mut connection = remote.connect_auth(
+    git2::Direction::Fetch,
+    Some(callbacks),
+    Some(proxies.options(origin)),
+  )?;
+  let refs = connection
+    .list()?
+    .iter()
+    .map(|head| AdvertisedRef {
+      name: head.name().to_string(),
+      oid: head.oid(),
+    })
+    .collect();
+  connection.remote().disconnect()?;
+  Ok(refs)
+}
@@ -0,0 +1,108 @@
+/// The time spent in each phase of work for a single source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct PhaseTimings {
+  /// Time spent cloning (fetching objects from) the remote.
+  pub clone: std::time::Duration,
+  /// Time spent resolving the manifest revision to a commit.
+  pub resolve: std::time::Duration,
+  /// Time spent checking out the working tree of the resolved commit.
+  pub checkout: std::time::Duration,
+  /// Time spent moving the checkout from our staging area into its final destination.
+  pub place: std::time::Duration,
+}
+
+impl PhaseTimings {
+  /// The sum of all phases.
+  pub fn total(&self) -> std::time::Duration {
+    self.clone + self.resolve + self.checkout + self.place
+  }
+}
+
+/// What was actually done for a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SourceOutcome {
+  /// The source was freshly cloned from its remote.
+  Cloned,
+  /// An existing checkout of the source was fetched and moved to the manifest revision.
+  Updated,
+  /// The source was populated from a local cache rather than its remote.
+  Cached,
+  /// The source was already at the manifest revision, nothing was done.
+  Skipped,
+}
+
+/// The number of sources that ended up with each `SourceOutcome`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct OutcomeCounts {
+  /// Sources that were freshly cloned.
+  pub cloned: usize,
+  /// Sources that were updated in place.
+  pub updated: usize,
+  /// Sources that were served from a cache.
+  pub cached: usize,
+  /// Sources that were skipped.
+  pub skipped: usize,
+}
+
+impl std::fmt::Display for OutcomeCounts {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      formatter,
+      "{} cloned, {} updated, {} cached, {} skipped",
+      self.cloned, self.updated, self.cached, self.skipped
+    )
+  }
+}
+
+/// The outcome of executing a single source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SourceReport {
+  /// The path, relative to the execution root, of the source.
+  pub destination: String,
+  /// The remote the source was cloned from.
+  pub origin: String,
+  /// Where the source was placed: its destination joined to the execution root.
+  pub path: std::path::PathBuf,
+  /// Where the source was staged (cloned into) before being placed; the same as `path` for
+  /// sources updated in place.
+  pub staging: std::path::PathBuf,
+  /// The commit the revision of the source resolved to, and that was checked out.
+  pub commit: git2::Oid,
+  /// What was done for this source.
+  pub outcome: SourceOutcome,
+  /// How long each phase took.
+  pub timings: PhaseTimings,
+  /// The number of bytes received from the remote.
+  pub received_bytes: u64,
+}
+
+/// This type is returned by a successful `WorkerPool::execute`, describing what was done.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct ExecutionReport {
+  /// One entry per source that was cloned and placed (or updated in place). Should any source
+  /// fail, execution fails with an error describing every failure instead.
+  pub sources: Vec<SourceReport>,
+}
+
+impl ExecutionReport {
+  /// Tallies the outcome of every source in the report.
+  pub fn counts(&self) -> OutcomeCounts {
+    self
+      .sources
+      .iter()
+      .fold(OutcomeCounts::default(), |mut counts, source| {
+        match source.outcome {
+          SourceOutcome::Cloned => counts.cloned += 1,
+          SourceOutcome::Updated => counts.updated += 1,
+          SourceOutcome::Cached => counts.cached += 1,
+          SourceOutcome::Skipped => counts.skipped += 1,
+        }
+        counts
+      })
+  }
+}
@@ -0,0 +1,90 @@
+/// Whether `revision` is a full (sha-1) commit id, rather than a branch, tag or abbreviated id.
+pub(crate) fn is_commit_id(revision: &str) -> bool {
+  revision.len() == 40 && is_commit_prefix(revision)
+}
+
+/// Whether `revision` may be a (full or abbreviated) commit id: nothing but hex digits. Branches and
+/// tags may be named like that too, so this only says that it is worth looking for such a commit.
+pub(crate) fn is_commit_prefix(revision: &str) -> bool {
+  !revision.is_empty()
+    && revision.len() <= 40
+    && revision.chars().all(|character| character.is_ascii_hexdigit())
+}
+
+/// The refspecs fetching only the commit, branch or tag `revision` names into a clone, rather than
+/// every branch and tag of the remote: full commit ids are kept as `refs/remotes/origin/<id>`, like
+/// `repo` does, so that the commit is known to come from the remote. `None` when it cannot name any
+/// of them, as with abbreviated commit ids (when made of hex digits alone) and other refs.
+pub(crate) fn refspecs(revision: &str) -> Option<Vec<String>> {
+  if is_commit_id(revision) {
+    return Some(vec![format!("+{revision}:refs/remotes/origin/{revision}")]);
+  }
+
+  let branch = |name: &str| format!("+refs/heads/{name}:refs/remotes/origin/{name}");
+  let tag = |name: &str| format!("+refs/tags/{name}:refs/tags/{name}");
+
+  let refspecs = if let Some(name) = revision.strip_prefix("refs/heads/") {
+    vec![branch(name)]
+  } else if let Some(name) = revision.strip_prefix("refs/tags/") {
+    vec![tag(name)]
+  } else if revision.starts_with("refs/") || is_commit_prefix(revision) {
+    return None;
+  } else {
+    vec![branch(revision), tag(revision)]
+  };
+
+  let valid = refspecs.iter().all(|refspec| {
+    refspec
+      .split_once(':')
+      .is_some_and(|(_, local)| git2::Reference::is_valid_name(local))
+  });
+  valid.then_some(refspecs)
+}
+
+/// The references `revision` may name in a clone, most likely first: branches are looked for
+/// among the remote-tracking refs of `origin` (or, in a `bare` mirror of the remote, among its own
+/// branches), and tags as they are.
+fn candidates(revision: &str, bare: bool) -> Vec<String> {
+  let branches = if bare { "refs/heads" } else { "refs/remotes/origin" };
+
+  if let Some(branch) = revision.strip_prefix("refs/heads/") {
+    return vec![format!("{branches}/{branch}")];
+  }
+
+  if revision.starts_with("refs/") {
+    return vec![revision.to_string()];
+  }
+
+  vec![format!("{branches}/{revision}"), format!("refs/tags/{revision}")]
+}
+
+/// Resolves the manifest `revision` to a commit of `repo`, a clone (or bare mirror) of the remote:
+/// full commit ids are looked up directly, then branches (`kirkstone` or `refs/heads/kirkstone`) and
+/// tags (`v5.15.1` or `refs/tags/v5.15.1`, peeling annotated tags) are looked for, and finally
+/// abbreviated commit ids.
+pub(crate) fn resolve<'r>(
+  repo: &'r git2::Repository,
+  revision: &str,
+) -> Result<git2::Commit<'r>, git2::Error> {
+  if is_commit_id(revision) {
+    return repo.find_commit(git2::Oid::from_str(revision)?);
+  }
+
+  for name in candidates(revision, repo.is_bare()) {
+    let found = repo
+      .find_reference(&name)
+      .and_then(|reference| reference.peel_to_commit());
+    if let Ok(commit) = found {
+      log::debug!("resolved revision '{revision}' through '{name}'");
+      return Ok(commit);
+    }
+  }
+
+  repo.find_commit_by_prefix(revision).map_err(|_| {
+    git2::Error::new(
+      git2::ErrorCode::NotFound,
+      git2::ErrorClass::Reference,
+      format!("revision '{revision}' is not a branch, tag or commit of the remote"),
+    )
+  })
+}
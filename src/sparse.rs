@@ -0,0 +1,49 @@
+/// The extended index entry flag git uses to mark paths that are intentionally absent from the
+/// working tree (`GIT_INDEX_ENTRY_SKIP_WORKTREE` in libgit2).
+const SKIP_WORKTREE: u16 = 1 << 14;
+
+/// Builds the options used when checking out a source with sparse `patterns`; only matching paths
+/// are written to the working tree.
+pub(crate) fn checkout_options<'a>(patterns: &[String]) -> git2::build::CheckoutBuilder<'a> {
+  let mut options = git2::build::CheckoutBuilder::new();
+  for pattern in patterns {
+    options.path(pattern.as_str());
+  }
+  options
+}
+
+/// Makes the sparseness of a checkout stick: index entries outside of `patterns` are marked as
+/// skip-worktree (so they do not show up as deleted), and the patterns are written to
+/// `info/sparse-checkout` with `core.sparseCheckout` enabled so that git keeps the tree sparse on
+/// later operations.
+pub(crate) fn persist(repo: &git2::Repository, patterns: &[String]) -> Result<(), git2::Error> {
+  let pathspec = git2::Pathspec::new(patterns.iter())?;
+  let mut index = repo.index()?;
+
+  let skipped = index
+    .iter()
+    .filter(|entry| {
+      let path = String::from_utf8_lossy(&entry.path);
+      !pathspec.matches_path(std::path::Path::new(path.as_ref()), git2::PathspecFlags::DEFAULT)
+    })
+    .collect::<Vec<git2::IndexEntry>>();
+
+  for mut entry in skipped {
+    entry.flags_extended |= SKIP_WORKTREE;
+    index.add(&entry)?;
+  }
+  index.write()?;
+
+  let rules = patterns
+    .iter()
+    .map(|pattern| format!("/{}\n", pattern.trim_start_matches('/')))
+    .collect::<String>();
+  let info = repo.path().join("info");
+  std::fs::create_dir_all(&info)
+    .and_then(|_| std::fs::write(info.join("sparse-checkout"), rules))
+    .map_err(|error| git2::Error::from_str(&format!("unable to write sparse-checkout - {error}")))?;
+
+  let mut config = repo.config()?;
+  config.set_bool("core.sparseCheckout", true)?;
+  config.set_bool("core.sparseCheckoutCone", false)
+}
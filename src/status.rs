@@ -0,0 +1,163 @@
+/// How the checkout of a single project compares to the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProjectStatus {
+  /// The manifest path of the project.
+  pub destination: String,
+  /// The revision the manifest asks for.
+  pub revision: String,
+  /// False when there is no checkout of the project at its path.
+  pub present: bool,
+  /// The commit checked out, if there is a checkout and its HEAD points at a commit.
+  pub head: Option<git2::Oid>,
+  /// The commit the revision resolves to within the checkout, if it can be resolved there (it may
+  /// not have been fetched yet).
+  pub expected: Option<git2::Oid>,
+  /// True when tracked files have been modified or untracked files added to the checkout.
+  pub dirty: bool,
+}
+
+impl ProjectStatus {
+  /// True when the project is checked out at its manifest revision, without local changes.
+  pub fn up_to_date(&self) -> bool {
+    self.present && self.head.is_some() && self.head == self.expected && !self.dirty
+  }
+}
+
+/// Compares the checkouts under `root` to `sources`, without fetching anything: whether each one
+/// exists, whether its HEAD is the commit its revision resolves to, and whether its working tree
+/// has local changes (the checkouts of projects nested within it aside). Checkouts that cannot be
+/// read are reported as present without a HEAD.
+pub fn status(root: &std::path::Path, sources: &[crate::Source]) -> Vec<ProjectStatus> {
+  let paths = sources
+    .iter()
+    .map(crate::Source::relative_path)
+    .collect::<Vec<_>>();
+
+  sources
+    .iter()
+    .zip(&paths)
+    .map(|(source, path)| {
+      let nested = paths
+        .iter()
+        .filter(|other| *other != path)
+        .filter_map(|other| other.strip_prefix(path).ok())
+        .collect::<Vec<_>>();
+      project_status(root, source, &nested)
+    })
+    .collect()
+}
+
+/// The status of the checkout of `source`, under `root`; changes within the `nested` paths (the
+/// checkouts of other projects, relative to this one) are not counted.
+fn project_status(
+  root: &std::path::Path,
+  source: &crate::Source,
+  nested: &[&std::path::Path],
+) -> ProjectStatus {
+  let path = root.join(source.relative_path());
+  let mut status = ProjectStatus {
+    destination: source.destination.clone(),
+    revision: source.revision.clone(),
+    present: path.join(".git").exists(),
+    head: None,
+    expected: None,
+    dirty: false,
+  };
+
+  if !status.present {
+    return status;
+  }
+
+  let repo = match git2::Repository::open(&path) {
+    Ok(repo) => repo,
+    Err(error) => {
+      log::warn!(source = source.destination.as_str(); "unable to open checkout - {}", error.message());
+      return status;
+    }
+  };
+
+  status.head = repo
+    .head()
+    .ok()
+    .and_then(|head| head.peel_to_commit().ok())
+    .map(|commit| commit.id());
+  status.expected = crate::revision::resolve(&repo, &source.revision)
+    .ok()
+    .map(|commit| commit.id());
+
+  let mut options = git2::StatusOptions::new();
+  options.include_untracked(true).include_ignored(false);
+  status.dirty = match repo.statuses(Some(&mut options)) {
+    Ok(statuses) => statuses.iter().any(|entry| {
+      let changed = entry.path().map(std::path::Path::new);
+      changed.is_none_or(|changed| !nested.iter().any(|inner| changed.starts_with(inner)))
+    }),
+    Err(error) => {
+      log::warn!(source = source.destination.as_str(); "unable to read status - {}", error.message());
+      false
+    }
+  };
+
+  status
+}
+
+/// True when tracked files of `repo` have been modified or untracked files added, the checkouts
+/// of projects nested within it aside. Bare repositories never have changes.
+pub(crate) fn has_changes(repo: &git2::Repository) -> Result<bool, git2::Error> {
+  let Some(workdir) = repo.workdir() else {
+    return Ok(false);
+  };
+  let mut options = git2::StatusOptions::new();
+  options.include_untracked(true).include_ignored(false);
+  let statuses = repo.statuses(Some(&mut options))?;
+  Ok(statuses.iter().any(|entry| {
+    let nested = entry.status() == git2::Status::WT_NEW
+      && entry
+        .path()
+        .is_some_and(|changed| workdir.join(changed).join(".git").exists());
+    !nested
+  }))
+}
+
+/// Describes the work in the checkout at `path` that replacing it would lose: uncommitted changes
+/// (untracked files included, ignored files and the checkouts of nested projects not) and commits
+/// on HEAD or a local branch that no remote-tracking branch or tag contains. `None` when there is
+/// nothing to lose, or no checkout at all. Bare mirrors hold only the refs of their remote, which
+/// fetching it again restores, so they never hold any.
+pub fn unsaved_work(path: &std::path::Path) -> Option<String> {
+  let repo = git2::Repository::open(path).ok()?;
+  let mirror = repo.is_bare()
+    && repo
+      .config()
+      .and_then(|config| config.get_bool("remote.origin.mirror"))
+      .unwrap_or(false);
+  if mirror {
+    return None;
+  }
+  unsaved_in(&repo)
+}
+
+/// Describes the work in `repo` that replacing it would lose; see `unsaved_work`. A checkout that
+/// cannot be inspected is assumed to hold some.
+pub(crate) fn unsaved_in(repo: &git2::Repository) -> Option<String> {
+  let dirty = has_changes(repo).unwrap_or(true);
+
+  let unpushed = repo.revwalk().and_then(|mut walk| {
+    if repo.head().is_ok() {
+      walk.push_head()?;
+    }
+    walk.push_glob("refs/heads/*")?;
+    walk.hide_glob("refs/remotes/*")?;
+    walk.hide_glob("refs/tags/*")?;
+    Ok(walk.count())
+  });
+
+  match (dirty, unpushed) {
+    (_, Err(error)) => Some(format!("could not be inspected - {}", error.message())),
+    (false, Ok(0)) => None,
+    (true, Ok(0)) => Some("has uncommitted changes".to_string()),
+    (false, Ok(count)) => Some(format!("has {count} unpushed commit(s)")),
+    (true, Ok(count)) => Some(format!("has uncommitted changes and {count} unpushed commit(s)")),
+  }
+}
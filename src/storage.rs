@@ -0,0 +1,57 @@
+/// Controls how git stores objects in the repositories we create, once they are in the hands of the
+/// user. Objects received while cloning are always written by libgit2 as a single pack (it has no
+/// equivalent of git's `unpackLimit`); these settings govern what later git commands do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StoragePolicy {
+  /// When false, `gc.auto` and `maintenance.auto` are disabled so that later git commands never
+  /// repack or prune the repository in the background.
+  pub auto_gc: bool,
+  /// The zlib compression level (`core.compression`, 0 to 9) used for objects written later; lower
+  /// levels trade disk space for speed.
+  pub compression: Option<u8>,
+}
+
+impl Default for StoragePolicy {
+  fn default() -> Self {
+    Self {
+      auto_gc: true,
+      compression: None,
+    }
+  }
+}
+
+impl StoragePolicy {
+  /// Enables (the default) or disables git's automatic garbage collection.
+  pub fn with_auto_gc(mut self, auto_gc: bool) -> Self {
+    self.auto_gc = auto_gc;
+    self
+  }
+
+  /// Sets the zlib compression level used for objects written later, if provided.
+  pub fn with_compression(mut self, compression: Option<u8>) -> Self {
+    self.compression = compression;
+    self
+  }
+
+  /// Writes the policy into the local configuration of `repo`. The default policy leaves the
+  /// configuration untouched.
+  pub(crate) fn apply(&self, repo: &git2::Repository) -> Result<(), git2::Error> {
+    if self.auto_gc && self.compression.is_none() {
+      return Ok(());
+    }
+
+    let mut config = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+
+    if !self.auto_gc {
+      config.set_i32("gc.auto", 0)?;
+      config.set_bool("maintenance.auto", false)?;
+    }
+
+    if let Some(level) = self.compression {
+      config.set_i32("core.compression", i32::from(level.min(9)))?;
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,163 @@
+use std::io;
+
+/// Limits how many jobs may work on sources of the same remote host at once, so that a large
+/// manifest does not get us throttled by (or overload) a single server. Jobs of a host already at
+/// its limit are set aside rather than waited for, leaving their worker free to take jobs of other
+/// hosts; they are queued again (on `requeue`) once a job of their host finishes.
+pub(crate) struct HostThrottle<T> {
+  /// The most jobs per host, if limited.
+  limit: Option<usize>,
+  /// The jobs working on sources of each host, and those set aside for it.
+  hosts: std::sync::Mutex<std::collections::HashMap<String, HostJobs<T>>>,
+  /// Where jobs set aside are queued again.
+  requeue: Option<std::sync::mpsc::Sender<T>>,
+}
+
+/// The jobs of a single host.
+struct HostJobs<T> {
+  /// The number of jobs currently working on sources of the host.
+  active: usize,
+  /// The jobs set aside until one of those finishes.
+  deferred: Vec<T>,
+}
+
+impl<T> Default for HostJobs<T> {
+  fn default() -> Self {
+    Self {
+      active: 0,
+      deferred: Vec::new(),
+    }
+  }
+}
+
+/// Held by a job for as long as it works on a source of `host`; dropping it makes room for the
+/// jobs of the same host that were set aside.
+pub(crate) struct HostPermit<T> {
+  /// The throttle the permit was taken from.
+  throttle: std::sync::Arc<HostThrottle<T>>,
+  /// The host the permit is for.
+  host: String,
+}
+
+/// What became of a job offered to a `HostThrottle`.
+pub(crate) enum Admission<T> {
+  /// The job may run now, holding the permit (if its host is limited at all) while it does.
+  Admitted(T, Option<HostPermit<T>>),
+  /// The job was set aside, to be queued again once a job of its host finishes.
+  Deferred,
+}
+
+impl<T> Default for HostThrottle<T> {
+  fn default() -> Self {
+    Self {
+      limit: None,
+      hosts: std::sync::Mutex::default(),
+      requeue: None,
+    }
+  }
+}
+
+impl<T> HostThrottle<T> {
+  /// Allows at most `limit` jobs per host at once, queueing jobs set aside on `requeue`; none
+  /// limits nothing.
+  pub(crate) fn new(limit: Option<usize>, requeue: std::sync::mpsc::Sender<T>) -> Self {
+    Self {
+      limit: limit.map(|limit| limit.max(1)),
+      hosts: std::sync::Mutex::default(),
+      requeue: Some(requeue),
+    }
+  }
+
+  /// Admits `job`, which works on a source fetched from `origin`, when its host has room for it
+  /// and sets it aside otherwise. Jobs are always admitted when we are not limited or `origin` is
+  /// a local path. Fails, handing the job back, if the throttle was poisoned by a panic.
+  pub(crate) fn admit(
+    self: &std::sync::Arc<Self>,
+    origin: &str,
+    job: T,
+  ) -> Result<Admission<T>, (T, io::Error)> {
+    let (Some(limit), Some(host)) = (self.limit, host(origin)) else {
+      return Ok(Admission::Admitted(job, None));
+    };
+    let host = host.to_ascii_lowercase();
+
+    let Ok(mut hosts) = self.hosts.lock() else {
+      return Err((job, poisoned()));
+    };
+    let jobs = hosts.entry(host.clone()).or_default();
+    if jobs.active >= limit {
+      jobs.deferred.push(job);
+      return Ok(Admission::Deferred);
+    }
+    jobs.active += 1;
+    drop(hosts);
+
+    let permit = HostPermit {
+      throttle: self.clone(),
+      host,
+    };
+    Ok(Admission::Admitted(job, Some(permit)))
+  }
+}
+
+/// The error of a throttle whose lock was poisoned, whose counts can no longer be trusted.
+fn poisoned() -> io::Error {
+  io::Error::other("the host limit is unusable, a job panicked while updating it")
+}
+
+impl<T> Drop for HostPermit<T> {
+  fn drop(&mut self) {
+    // Every job set aside is queued again; those that lose the race for the free slot are simply
+    // set aside again, behind a job that will queue them once more when it finishes. Jobs set
+    // aside are queued even when the throttle was poisoned, so that they fail rather than hang.
+    let mut hosts = self.throttle.hosts.lock().unwrap_or_else(|poison| {
+      log::error!("releasing the host limit of '{}' - {}", self.host, poisoned());
+      poison.into_inner()
+    });
+    let deferred = hosts
+      .get_mut(&self.host)
+      .map(|jobs| {
+        jobs.active = jobs.active.saturating_sub(1);
+        std::mem::take(&mut jobs.deferred)
+      })
+      .unwrap_or_default();
+    drop(hosts);
+
+    let Some(requeue) = &self.throttle.requeue else {
+      return;
+    };
+    for job in deferred {
+      if requeue.send(job).is_err() {
+        log::error!(
+          "unable to queue a job of '{}' again, no workers are left",
+          self.host
+        );
+      }
+    }
+  }
+}
+
+/// The host `origin` is fetched from, for both `scheme://[user@]host[:port]/path` urls and the scp
+/// form (`[user@]host:path`) of ssh; none for local paths and `file://` urls. This is the one place
+/// hosts are parsed, so that host limits, proxies, credentials and the remote policy all agree.
+pub(crate) fn host(origin: &str) -> Option<&str> {
+  let authority = match origin.split_once("://") {
+    Some((scheme, _)) if scheme.eq_ignore_ascii_case("file") => return None,
+    Some((_, rest)) => rest.split('/').next()?,
+    None => {
+      let (authority, _) = origin.split_once(':')?;
+      // A drive letter (`c:\...`) or a path containing a colon is not a host.
+      if authority.len() < 2 || authority.contains(['/', '\\']) {
+        return None;
+      }
+      authority
+    }
+  };
+
+  let host = authority.rsplit('@').next()?;
+  let host = match host.strip_prefix('[') {
+    Some(bracketed) => bracketed.split(']').next()?,
+    None => host.split(':').next()?,
+  };
+  Some(host).filter(|host| !host.is_empty())
+}
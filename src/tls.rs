@@ -0,0 +1,83 @@
+use std::io;
+
+/// How the certificates of https remotes are verified, for internal servers whose certificates are
+/// issued by a private certificate authority. By default, libgit2 trusts the certificate
+/// authorities of the system.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tls {
+  /// The file of (pem encoded) certificate authorities trusted instead of the system ones.
+  ca_bundle: Option<std::path::PathBuf>,
+  /// True when certificates are not verified at all.
+  insecure: bool,
+}
+
+impl Tls {
+  /// Trusts the certificate authorities of the (pem encoded) file at `path` instead of the system
+  /// ones; takes effect once `install`ed.
+  pub fn with_ca_bundle(mut self, path: std::path::PathBuf) -> Self {
+    self.ca_bundle = Some(path);
+    self
+  }
+
+  /// Accepts any certificate an https remote presents, leaving connections open to interception.
+  /// Meant only as a last resort for servers whose certificates cannot be verified otherwise.
+  pub fn with_insecure(mut self, insecure: bool) -> Self {
+    self.insecure = insecure;
+    self
+  }
+
+  /// True when certificates are not verified.
+  pub fn is_insecure(&self) -> bool {
+    self.insecure
+  }
+
+  /// The file of certificate authorities trusted instead of the system ones, if any.
+  pub(crate) fn ca_bundle(&self) -> Option<&std::path::Path> {
+    self.ca_bundle.as_deref()
+  }
+
+  /// Makes libgit2 trust the certificate authority bundle, if any. The setting is global to the
+  /// process, so this must be called before any remote is connected to (and not while other
+  /// threads may be doing so).
+  pub fn install(&self) -> io::Result<()> {
+    let Some(path) = &self.ca_bundle else {
+      return Ok(());
+    };
+
+    if !path.is_file() {
+      return Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("certificate authority bundle '{}' not found", path.display()),
+      ));
+    }
+
+    // SAFETY: libgit2 reads the setting when it sets up a tls connection; our callers install it
+    // before making any.
+    unsafe { git2::opts::set_ssl_cert_file(path) }.map_err(|error| {
+      io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+          "unable to use certificate authority bundle '{}' - {}",
+          path.display(),
+          error.message()
+        ),
+      )
+    })
+  }
+
+  /// Sets the certificate check of `callbacks` (of connections made outside a `WorkerPool`), which
+  /// accepts every tls certificate when insecure and leaves the verification to libgit2 otherwise.
+  pub fn apply(&self, callbacks: &mut git2::RemoteCallbacks<'_>) {
+    if !self.insecure {
+      return;
+    }
+
+    callbacks.certificate_check(|certificate, host| {
+      if certificate.as_x509().is_none() {
+        return Ok(git2::CertificateCheckStatus::CertificatePassthrough);
+      }
+      log::warn!("not verifying the tls certificate of '{host}'");
+      Ok(git2::CertificateCheckStatus::CertificateOk)
+    });
+  }
+}
@@ -1,41 +1,42 @@
 /// When we are cloning our repositories, we will clone them outside of where they will ultimately
 /// live to avoid any internal repo nuances for `git2`. This type is used to make sure we can order
-/// the "placement operations" in a way that makes sense and is simple.
+/// the "placement operations" in a way that makes sense and is simple. Each location carries a
+/// `payload` alongside its path; `repors` itself uses the path a checkout is staged at.
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct Location {
+#[non_exhaustive]
+pub struct Location<T> {
   /// The path that this location represents.
-  pub(crate) root: std::path::PathBuf,
-  /// The path where our content is actually stored.
-  pub(crate) temp: std::path::PathBuf,
+  pub root: std::path::PathBuf,
+  /// Whatever the caller associated with this path when it was added.
+  pub payload: T,
   /// A list of childrent that should be placed _after we are_.
   #[allow(clippy::vec_box)]
-  pub(crate) children: Vec<Box<Location>>,
+  pub children: Vec<Box<Location<T>>>,
 }
 
-impl Location {
+impl<T> Location<T>
+where
+  T: Clone,
+{
   /// Creates an empty node.
-  pub(crate) fn root(root: std::path::PathBuf, temp: std::path::PathBuf) -> Self {
+  pub fn root(root: std::path::PathBuf, payload: T) -> Self {
     Self {
       root,
-      temp,
+      payload,
       children: Default::default(),
     }
   }
 
   /// Will attempt to update this node if the provided path is a child of us, or one of our
   /// children has some sort of relationship to that path.
-  pub(crate) fn recognize(
-    &mut self,
-    other: std::path::PathBuf,
-    temp: std::path::PathBuf,
-  ) -> Option<std::path::PathBuf> {
+  pub fn recognize(&mut self, other: std::path::PathBuf, payload: T) -> Option<std::path::PathBuf> {
     for child in self.children.iter_mut() {
-      child.recognize(other.clone(), temp.clone())?;
+      child.recognize(other.clone(), payload.clone())?;
     }
 
     for ancestor in other.ancestors() {
       if ancestor == self.root {
-        let new_child = Self::root(other, temp);
+        let new_child = Self::root(other, payload);
         self.children.push(Box::new(new_child));
         return None;
       }
@@ -47,24 +48,35 @@ impl Location {
 
 /// During the "placement phase" of the `repors` cloning process, we use this type to order the
 /// operations such that we do not have any conflicts with attempting to rename a directory to one
-/// that was already created for a child.
-#[derive(Default)]
-pub struct LayerTree {
+/// that was already created for a child. It is generic over the payload carried by each path so
+/// that other tools can reuse the ordering of nested paths for their own work.
+pub struct LayerTree<T> {
   /// The list of locations, which is effectively a graph-like representation of filesystem paths.
-  pub(crate) locations: Vec<Location>,
+  pub(crate) locations: Vec<Location<T>>,
 }
 
-impl LayerTree {
+impl<T> Default for LayerTree<T> {
+  fn default() -> Self {
+    Self {
+      locations: Vec::default(),
+    }
+  }
+}
+
+impl<T> LayerTree<T>
+where
+  T: Clone,
+{
   /// Attempts to place the path into our tree.
-  pub fn add(&mut self, path: std::path::PathBuf, temp: std::path::PathBuf) {
+  pub fn add(&mut self, path: std::path::PathBuf, payload: T) {
     if self.locations.is_empty() {
-      self.locations.push(Location::root(path, temp));
+      self.locations.push(Location::root(path, payload));
       return;
     }
 
     let mut remainder = None;
     for loc in self.locations.iter_mut() {
-      let rem = loc.recognize(path.clone(), temp.clone());
+      let rem = loc.recognize(path.clone(), payload.clone());
       if rem.is_none() {
         return;
       }
@@ -72,13 +84,13 @@ impl LayerTree {
     }
 
     if let Some(rem) = remainder {
-      self.locations.push(Location::root(rem, temp));
+      self.locations.push(Location::root(rem, payload));
     }
   }
 
-  /// Returns the correctly-ordered list of filesystem path pairs that can be iterated over for
-  /// placement.
-  pub fn consume(self) -> Vec<(std::path::PathBuf, std::path::PathBuf)> {
+  /// Returns the correctly-ordered list of paths (and their payloads) that can be iterated over for
+  /// placement: every path comes after any of its ancestors.
+  pub fn consume(self) -> Vec<(std::path::PathBuf, T)> {
     let mut out = Vec::default();
     let mut queue = self.locations;
 
@@ -88,7 +100,7 @@ impl LayerTree {
       };
 
       if current.children.is_empty() {
-        out.push((current.root, current.temp));
+        out.push((current.root, current.payload));
         continue;
       }
 
@@ -101,4 +113,30 @@ impl LayerTree {
 
     out
   }
+
+  /// Like `consume`, but groups the placements into levels: the placements within a level are
+  /// independent of each other (none is nested inside another) and may be performed concurrently,
+  /// as long as every level is complete before the next begins.
+  pub fn consume_levels(self) -> Vec<Vec<(std::path::PathBuf, T)>> {
+    let mut levels: Vec<Vec<(std::path::PathBuf, T)>> = Vec::default();
+    let mut placed: Vec<(std::path::PathBuf, usize)> = Vec::default();
+
+    for (path, payload) in self.consume() {
+      let level = placed
+        .iter()
+        .filter(|(earlier, _)| path.starts_with(earlier))
+        .map(|(_, level)| level + 1)
+        .max()
+        .unwrap_or_default();
+
+      if levels.len() <= level {
+        levels.resize_with(level + 1, Vec::default);
+      }
+
+      placed.push((path.clone(), level));
+      levels[level].push((path, payload));
+    }
+
+    levels
+  }
 }
@@ -0,0 +1,113 @@
+use std::io;
+
+/// How serious a `Diagnostic` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Severity {
+  /// The manifest cannot be executed as written.
+  Error,
+  /// The manifest can be executed, but probably does not say what its author meant.
+  Warning,
+}
+
+impl std::fmt::Display for Severity {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Severity::Error => write!(formatter, "error"),
+      Severity::Warning => write!(formatter, "warning"),
+    }
+  }
+}
+
+/// A single problem found by `validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+  /// How serious the problem is.
+  pub severity: Severity,
+  /// The name of the project the problem is with, if it is with a project.
+  pub project: Option<String>,
+  /// A description of the problem.
+  pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(formatter, "{}: {}", self.severity, self.message)
+  }
+}
+
+impl Diagnostic {
+  /// An error with `project` (if any), described by `message`.
+  pub(crate) fn error(project: Option<String>, message: String) -> Self {
+    Self {
+      severity: Severity::Error,
+      project,
+      message,
+    }
+  }
+
+  /// A warning about `project` (if any), described by `message`.
+  pub(crate) fn warning(project: Option<String>, message: String) -> Self {
+    Self {
+      severity: Severity::Warning,
+      project,
+      message,
+    }
+  }
+}
+
+/// Checks the manifest read by `reader` for problems, without cloning or contacting any remote:
+/// projects without a revision, naming undeclared remotes or with invalid paths, projects sharing a
+/// path, and remotes no project uses. Unlike parsing, every problem is reported rather than only the
+/// first; malformed xml ends the check early, as nothing after it can be trusted. The diagnostics
+/// are returned in the order the problems were found.
+pub fn validate<R>(reader: crate::ManifestReader<R>) -> Vec<Diagnostic>
+where
+  R: io::BufRead,
+{
+  let mut reader = reader.linting();
+  let read = reader.read_all();
+  let mut diagnostics = reader.take_problems();
+
+  let sources = match read {
+    Ok(sources) => sources,
+    Err(error) => {
+      diagnostics.push(Diagnostic::error(None, error.to_string()));
+      return diagnostics;
+    }
+  };
+
+  let mut paths = std::collections::HashMap::<std::path::PathBuf, &crate::Source>::new();
+  for source in &sources {
+    match paths.entry(source.relative_path()) {
+      std::collections::hash_map::Entry::Occupied(first) => diagnostics.push(Diagnostic::error(
+        Some(source.name.clone()),
+        format!(
+          "project '{}' is placed at '{}', which is already taken by '{}'",
+          source.name,
+          source.destination,
+          first.get().name
+        ),
+      )),
+      std::collections::hash_map::Entry::Vacant(vacant) => {
+        vacant.insert(source);
+      }
+    }
+  }
+
+  let mut unused = reader
+    .remotes()
+    .keys()
+    .filter(|remote| !sources.iter().any(|source| source.remote_name == **remote))
+    .collect::<Vec<_>>();
+  unused.sort();
+  diagnostics.extend(unused.into_iter().map(|remote| {
+    Diagnostic::warning(
+      None,
+      format!("remote '{remote}' is declared but not used by any project"),
+    )
+  }));
+
+  diagnostics
+}
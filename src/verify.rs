@@ -0,0 +1,135 @@
+/// A way in which the checkout of a project differs from what was expected of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Mismatch {
+  /// There is no checkout at the path of the project.
+  Missing,
+  /// The checkout could not be opened or read; this describes why.
+  Unreadable(String),
+  /// The revision of the project could not be resolved within the checkout; this describes why.
+  Unresolved(String),
+  /// HEAD is not the expected commit; `actual` is `None` when HEAD does not point at a commit.
+  Head {
+    /// The commit the checkout should be at.
+    expected: git2::Oid,
+    /// The commit the checkout is at.
+    actual: Option<git2::Oid>,
+  },
+  /// Tracked files have been modified or untracked files added.
+  Dirty,
+}
+
+impl std::fmt::Display for Mismatch {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Missing => write!(formatter, "no checkout"),
+      Self::Unreadable(reason) => write!(formatter, "unreadable checkout - {reason}"),
+      Self::Unresolved(reason) => write!(formatter, "revision not found in the checkout - {reason}"),
+      Self::Head {
+        expected,
+        actual: Some(actual),
+      } => write!(formatter, "HEAD is {actual}, expected {expected}"),
+      Self::Head { expected, .. } => write!(formatter, "HEAD is not a commit, expected {expected}"),
+      Self::Dirty => write!(formatter, "the working tree has local changes"),
+    }
+  }
+}
+
+/// The mismatches found in the checkout of a single project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProjectMismatch {
+  /// The manifest path of the project.
+  pub destination: String,
+  /// Every way in which the checkout differs; never empty.
+  pub mismatches: Vec<Mismatch>,
+}
+
+/// The result of verifying a set of checkouts.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct Verification {
+  /// How many checkouts were verified.
+  pub checked: usize,
+  /// The checkouts that did not match, in the order they were checked.
+  pub failures: Vec<ProjectMismatch>,
+}
+
+impl Verification {
+  /// True when every checkout matched.
+  pub fn is_ok(&self) -> bool {
+    self.failures.is_empty()
+  }
+
+  /// Checks the checkout at `path` of the project at `destination`, which should be at the commit
+  /// `expected` returns for it, recording any mismatch.
+  fn check<F>(&mut self, destination: &str, path: &std::path::Path, expected: F)
+  where
+    F: FnOnce(&git2::Repository) -> Result<git2::Oid, git2::Error>,
+  {
+    self.checked += 1;
+    let mismatches = mismatches(path, expected);
+    if !mismatches.is_empty() {
+      self.failures.push(ProjectMismatch {
+        destination: destination.to_string(),
+        mismatches,
+      });
+    }
+  }
+}
+
+/// Every way in which the checkout at `path` differs from the commit `expected` returns for it.
+fn mismatches<F>(path: &std::path::Path, expected: F) -> Vec<Mismatch>
+where
+  F: FnOnce(&git2::Repository) -> Result<git2::Oid, git2::Error>,
+{
+  if !path.join(".git").exists() {
+    return vec![Mismatch::Missing];
+  }
+  let repo = match git2::Repository::open(path) {
+    Ok(repo) => repo,
+    Err(error) => return vec![Mismatch::Unreadable(error.message().to_string())],
+  };
+
+  let mut mismatches = Vec::new();
+  let actual = repo
+    .head()
+    .ok()
+    .and_then(|head| head.peel_to_commit().ok())
+    .map(|commit| commit.id());
+  match expected(&repo) {
+    Ok(expected) if actual != Some(expected) => mismatches.push(Mismatch::Head { expected, actual }),
+    Ok(_) => (),
+    Err(error) => mismatches.push(Mismatch::Unresolved(error.message().to_string())),
+  }
+  match crate::status::has_changes(&repo) {
+    Ok(true) => mismatches.push(Mismatch::Dirty),
+    Ok(false) => (),
+    Err(error) => mismatches.push(Mismatch::Unreadable(error.message().to_string())),
+  }
+  mismatches
+}
+
+/// Verifies that every source of a finished execution is checked out where `report` says it was
+/// placed, at the commit `report` says was checked out, with a clean working tree. Meant to be run
+/// right after `WorkerPool::execute`, to catch checkouts that were disturbed while placing them.
+pub fn verify_execution(report: &crate::ExecutionReport) -> Verification {
+  let mut verification = Verification::default();
+  for source in &report.sources {
+    verification.check(&source.destination, &source.path, |_| Ok(source.commit));
+  }
+  verification
+}
+
+/// Verifies that the checkout of every source under `root` is at the commit its revision resolves
+/// to (within the checkout; nothing is fetched) with a clean working tree.
+pub fn verify(root: &std::path::Path, sources: &[crate::Source]) -> Verification {
+  let mut verification = Verification::default();
+  for source in sources {
+    let path = root.join(source.relative_path());
+    verification.check(&source.destination, &path, |repo| {
+      crate::revision::resolve(repo, &source.revision).map(|commit| commit.id())
+    });
+  }
+  verification
+}
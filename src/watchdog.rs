@@ -0,0 +1,184 @@
+use std::sync::atomic;
+
+/// Controls how the pool reacts to fetches that stop making progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StallPolicy {
+  /// How long a fetch may go without receiving data before we log a warning. The warning is
+  /// repeated every `warn_after` for as long as the stall lasts.
+  pub warn_after: std::time::Duration,
+  /// When set, a fetch that has gone this long without receiving data is aborted.
+  pub abort_after: Option<std::time::Duration>,
+}
+
+impl StallPolicy {
+  /// Creates a policy that warns about fetches stalled for `warn_after`, without aborting them.
+  pub fn new(warn_after: std::time::Duration) -> Self {
+    Self {
+      warn_after,
+      abort_after: None,
+    }
+  }
+
+  /// Aborts fetches that have stalled for `abort_after`, if provided.
+  pub fn with_abort_after(mut self, abort_after: Option<std::time::Duration>) -> Self {
+    self.abort_after = abort_after;
+    self
+  }
+}
+
+/// The time limits of an execution: how long each source may take, and when the whole execution
+/// must be done by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct TimeLimits {
+  /// How long the work on a single source may take.
+  pub(crate) per_source: Option<std::time::Duration>,
+  /// When every source must be done by.
+  pub(crate) deadline: Option<std::time::Instant>,
+}
+
+impl TimeLimits {
+  /// When the work on a source started at `started` runs out of time, if it can; whichever of our
+  /// limits comes first applies.
+  pub(crate) fn expiry(&self, started: std::time::Instant) -> Option<Expiry> {
+    let per_source = self.per_source.map(|limit| Expiry {
+      at: started + limit,
+      exceeded: crate::error::TimeLimitExceeded::Project(limit),
+    });
+    let deadline = self.deadline.map(|at| Expiry {
+      at,
+      exceeded: crate::error::TimeLimitExceeded::Deadline,
+    });
+    per_source
+      .into_iter()
+      .chain(deadline)
+      .min_by_key(|expiry| expiry.at)
+  }
+
+  /// The shortest time any single source may have left, as of now; used as the timeout of reads
+  /// from remotes, which are not interrupted otherwise.
+  pub(crate) fn longest_wait(&self) -> Option<std::time::Duration> {
+    let remaining = self
+      .deadline
+      .map(|deadline| deadline.saturating_duration_since(std::time::Instant::now()));
+    self.per_source.into_iter().chain(remaining).min()
+  }
+}
+
+/// When the work on a source runs out of time, and which limit it then exceeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Expiry {
+  /// The moment time runs out.
+  pub(crate) at: std::time::Instant,
+  /// The limit exceeded then.
+  pub(crate) exceeded: crate::error::TimeLimitExceeded,
+}
+
+impl Expiry {
+  /// True once time has run out.
+  pub(crate) fn passed(&self) -> bool {
+    std::time::Instant::now() >= self.at
+  }
+
+  /// The error of work that ran out of time.
+  pub(crate) fn error(&self) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::TimedOut, self.exceeded)
+  }
+}
+
+/// Shared between a fetch (which records progress) and the thread watching it.
+pub(crate) struct Heartbeat {
+  /// The last time we saw progress, and the number of bytes received at that point.
+  last: std::sync::Mutex<(std::time::Instant, usize)>,
+  /// Set by the watcher once the stall has lasted longer than our abort threshold.
+  aborted: atomic::AtomicBool,
+}
+
+impl Heartbeat {
+  /// Creates a heartbeat that considers "now" as the last time progress was made.
+  fn new() -> Self {
+    Self {
+      last: std::sync::Mutex::new((std::time::Instant::now(), 0)),
+      aborted: atomic::AtomicBool::new(false),
+    }
+  }
+
+  /// Records that the remote has sent us something. The timer is only reset when the number of
+  /// received bytes has changed (or when `bytes` is `None`, for non-transfer activity).
+  pub(crate) fn beat(&self, bytes: Option<usize>) {
+    let Ok(mut last) = self.last.lock() else {
+      return;
+    };
+
+    match bytes {
+      Some(received) if received == last.1 => (),
+      Some(received) => *last = (std::time::Instant::now(), received),
+      None => last.0 = std::time::Instant::now(),
+    }
+  }
+
+  /// Returns false once the watcher has decided this fetch should be aborted; used as the return
+  /// value of our git callbacks.
+  pub(crate) fn alive(&self) -> bool {
+    !self.aborted.load(atomic::Ordering::SeqCst)
+  }
+
+  /// Returns true if the watcher aborted the fetch.
+  pub(crate) fn aborted(&self) -> bool {
+    !self.alive()
+  }
+}
+
+/// Runs `work`, watching the heartbeat it is given on a separate thread according to `policy`. If
+/// there is no policy, `work` is executed with a heartbeat that is never checked.
+pub(crate) fn watch<F, T>(policy: Option<&StallPolicy>, label: &str, work: F) -> T
+where
+  F: FnOnce(&Heartbeat) -> T,
+{
+  let heartbeat = Heartbeat::new();
+
+  let Some(policy) = policy else {
+    return work(&heartbeat);
+  };
+
+  let heartbeat = &heartbeat;
+  let (done, finished) = std::sync::mpsc::channel::<()>();
+  let tick = (policy.warn_after / 4).clamp(
+    std::time::Duration::from_millis(10),
+    std::time::Duration::from_secs(1),
+  );
+
+  std::thread::scope(|scope| {
+    scope.spawn(move || {
+      let mut warned_at = None;
+
+      while let Err(std::sync::mpsc::RecvTimeoutError::Timeout) = finished.recv_timeout(tick) {
+        let Ok((since, bytes)) = heartbeat.last.lock().map(|last| *last) else {
+          break;
+        };
+        let stalled = since.elapsed();
+
+        if let Some(abort_after) = policy.abort_after.filter(|limit| stalled >= *limit) {
+          log::warn!(source = label, phase = "clone"; "aborting fetch after {abort_after:?} without progress ({bytes} bytes received)");
+          heartbeat.aborted.store(true, atomic::Ordering::SeqCst);
+          break;
+        }
+
+        if stalled < policy.warn_after {
+          warned_at = None;
+          continue;
+        }
+
+        let due = warned_at.is_none_or(|at: std::time::Instant| at.elapsed() >= policy.warn_after);
+        if due {
+          log::warn!(source = label, phase = "clone"; "no progress for {}s ({bytes} bytes received so far)", stalled.as_secs());
+          warned_at = Some(std::time::Instant::now());
+        }
+      }
+    });
+
+    let result = work(heartbeat);
+    drop(done);
+    result
+  })
+}